@@ -1,28 +1,59 @@
 use std::{
-    collections::HashMap,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
 use sysinfo::{System, Networks};
 
+use crate::cli::Args;
+use crate::network::capture::{interface_ipv6_networks, CaptureContext};
+use crate::network::flow::{sorted_processes, FlowTable};
+use crate::network::asn::AsnDatabase;
+use crate::network::dns::DnsStats;
+use crate::network::http::HttpStats;
+use crate::network::icmp::IcmpStats;
+use crate::network::fragment::FragmentTable;
+use crate::network::recorder::{self, Recorder};
+use crate::network::services::ServiceTable;
+use crate::network::geoip::GeoIp;
+use crate::network::names::HostNames;
+use crate::network::neighbors::NeighborTable;
+use crate::network::oui::OuiTable;
 use crate::network::types::*;
 use crate::utils::IpRange;
 
 pub struct App {
     pub system: System,
     pub networks: Networks,
-    pub network_stats: NetworkStats,
+    pub sources: Vec<CaptureSource>,
+    pub interface_stats: Vec<NetworkStats>,
+    pub interface_view: Option<usize>,  // Index into interface_stats, None shows totals
     pub packet_stats: Arc<Mutex<PacketStats>>,
-    pub connections: Arc<Mutex<HashMap<ConnectionId, ConnectionStats>>>,
+    pub connections: Arc<Mutex<FlowTable>>,
     pub geo_stats: Arc<Mutex<GeoStats>>,
+    pub geoip: Option<Arc<GeoIp>>,      // None when no database was given
+    pub asn_db: Option<Arc<AsnDatabase>>,
+    pub host_names: Arc<Mutex<HostNames>>,
+    pub dns_stats: Arc<Mutex<DnsStats>>,
+    pub http_stats: Arc<Mutex<HttpStats>>,
+    pub icmp_stats: Arc<Mutex<IcmpStats>>,
+    pub fragments: Arc<Mutex<FragmentTable>>,
+    pub neighbors: Arc<Mutex<NeighborTable>>,
+    pub recorder: Option<Arc<Mutex<Recorder>>>,    // None unless recording or keeping a ring buffer
+    pub services: Arc<ServiceTable>,
     pub local_networks: Vec<IpRange>,
     pub running: Arc<AtomicBool>,
+    pub capture_finished: Arc<AtomicBool>,
+    pub capture_clock: Arc<CaptureClock>,
+    pub capture_filter: Arc<Mutex<CaptureFilter>>,
+    pub filter_input: Option<String>,   // Text of the filter prompt while it is open
+    pub decapsulate: bool,
     pub current_tab: Tab,
+    pub tick_rate: Duration,
     // Visualization options
     pub graph_scale: GraphScale,
     pub protocol_grouping: ProtocolGrouping,
@@ -30,111 +61,344 @@ pub struct App {
     pub connection_sort: ConnectionSort,
     pub connection_filter: ConnectionFilter,
     pub connection_scroll: usize,
+    pub connection_segment_filter: Option<Segment>,
+    pub connection_state_filter: Option<TcpState>,
+    pub connection_process_filter: Option<u32>,     // pid
+    pub tls_search: String,             // Matched against SNI, ALPN and fingerprints; empty shows all
+    pub tls_search_input: Option<String>,   // Text of the search prompt while it is open
+    pub show_hostnames: bool,           // Names instead of addresses in the connections table
+    pub process_attribution: bool,      // Connections are being mapped to processes
+    pub process_selection: usize,
+    pub dns_scroll: usize,
+    pub http_scroll: usize,
+    pub neighbor_scroll: usize,
     pub geo_mode: GeoMode,
     pub geo_country_selection: usize,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
+    pub fn new(sources: Vec<CaptureSource>, args: &Args) -> Result<Self> {
         let system = System::new();
         let networks = Networks::new_with_refreshed_list();
         
-        // Default to a common interface name, will be overridden in main
-        let interface_name = String::from("en0");
-        
-        // Get network stats for default interface (this will be updated later)
-        let (rx_bytes, tx_bytes) = (0, 0);
-        
-        let network_stats = NetworkStats {
-            interface_name,
-            rx_bytes,
-            tx_bytes,
-            rx_packets: 0,
-            tx_packets: 0,
-            rx_speed: 0.0,
-            tx_speed: 0.0,
-            last_update: Instant::now(),
-        };
-
-        let packet_stats = Arc::new(Mutex::new(PacketStats::new()));
+        // One entry per interface, or the capture file path when replaying a recording
+        let interface_stats: Vec<NetworkStats> = sources.iter()
+            .map(|source| {
+                let interface_name = source.name();
+                
+                // Initial counters, updated from sysinfo for live interfaces
+                let (rx_bytes, tx_bytes) = match networks.get(&interface_name) {
+                    Some(network) => (network.received(), network.transmitted()),
+                    None => (0, 0),
+                };
+                
+                NetworkStats {
+                    interface_name,
+                    rx_bytes,
+                    tx_bytes,
+                    rx_packets: 0,
+                    tx_packets: 0,
+                    rx_speed: 0.0,
+                    tx_speed: 0.0,
+                    last_update: Instant::now(),
+                }
+            })
+            .collect();
+        
+        let interface_names: Vec<String> = interface_stats.iter()
+            .map(|stats| stats.interface_name.clone())
+            .collect();
+        
+        let packet_stats = Arc::new(Mutex::new(PacketStats::new(args.history as usize, &interface_names)));
         let running = Arc::new(AtomicBool::new(true));
-
+        let capture_finished = Arc::new(AtomicBool::new(false));
+        let capture_filter = Arc::new(Mutex::new(CaptureFilter::new(args.filter.clone())));
+        
         // Initialize with common local network ranges for connection direction detection
-        let local_networks = vec![
+        let mut local_networks = vec![
             IpRange::new([10, 0, 0, 0], 8),    // 10.0.0.0/8
             IpRange::new([172, 16, 0, 0], 12), // 172.16.0.0/12
             IpRange::new([192, 168, 0, 0], 16), // 192.168.0.0/16
             IpRange::new([127, 0, 0, 0], 8),   // 127.0.0.0/8
             IpRange::new([169, 254, 0, 0], 16), // 169.254.0.0/16
+            IpRange::new_v6([0xfc00, 0, 0, 0, 0, 0, 0, 0], 7),  // fc00::/7
+            IpRange::new_v6([0xfe80, 0, 0, 0, 0, 0, 0, 0], 10), // fe80::/10
+            IpRange::new_v6([0, 0, 0, 0, 0, 0, 0, 1], 128),     // ::1/128
         ];
         
-        // Create the connections hashmap
-        let connections = Arc::new(Mutex::new(HashMap::new()));
+        // The IPv6 prefixes configured on the monitored interfaces
+        for source in &sources {
+            if let CaptureSource::Interface(name) = source {
+                local_networks.extend(interface_ipv6_networks(name));
+            }
+        }
+        
+        // Plus any ranges given on the command line
+        local_networks.extend(args.local_networks.iter().cloned());
+        
+        // Create the flow table
+        let connections = Arc::new(Mutex::new(FlowTable::new(args.flow_timeouts(), args.max_flows as usize)));
         
         // Create geo stats
         let geo_stats = Arc::new(Mutex::new(GeoStats::new()));
+        let geoip = match &args.geoip_db {
+            Some(path) => Some(Arc::new(GeoIp::open(path)?)),
+            None => None,
+        };
+        let asn_db = if args.asn_db.is_empty() {
+            None
+        } else {
+            Some(Arc::new(AsnDatabase::open(&args.asn_db)?))
+        };
+        let services = Arc::new(ServiceTable::load(args.services.as_deref())?);
+        let oui = OuiTable::load(args.oui_db.as_deref())?;
+        let recorder = match args.recorder_options() {
+            Some(options) => Some(Arc::new(Mutex::new(Recorder::new(options)?))),
+            None => None,
+        };
         
         Ok(App {
             system,
             networks,
-            network_stats,
+            sources,
+            interface_stats,
+            interface_view: None,
             packet_stats,
             connections,
             geo_stats,
+            geoip,
+            asn_db,
+            host_names: Arc::new(Mutex::new(HostNames::new())),
+            dns_stats: Arc::new(Mutex::new(DnsStats::new())),
+            http_stats: Arc::new(Mutex::new(HttpStats::new())),
+            icmp_stats: Arc::new(Mutex::new(IcmpStats::new())),
+            fragments: Arc::new(Mutex::new(FragmentTable::new())),
+            neighbors: Arc::new(Mutex::new(NeighborTable::new(oui))),
+            recorder,
+            services,
             local_networks,
             running,
-            current_tab: Tab::Overview,
+            capture_finished,
+            capture_clock: Arc::new(CaptureClock::default()),
+            capture_filter,
+            filter_input: None,
+            decapsulate: !args.no_decap,
+            current_tab: args.tab,
+            tick_rate: Duration::from_millis(args.tick_rate),
             // Default visualization options
-            graph_scale: GraphScale::Linear,
+            graph_scale: if args.log_scale { GraphScale::Logarithmic } else { GraphScale::Linear },
             protocol_grouping: ProtocolGrouping::Detailed,
             show_help: false,
             connection_sort: ConnectionSort::PacketCount,
             connection_filter: ConnectionFilter::All,
             connection_scroll: 0,
+            connection_segment_filter: None,
+            connection_state_filter: None,
+            connection_process_filter: None,
+            tls_search: String::new(),
+            tls_search_input: None,
+            show_hostnames: false,
+            process_attribution: false,
+            process_selection: 0,
+            dns_scroll: 0,
+            http_scroll: 0,
+            neighbor_scroll: 0,
             geo_mode: GeoMode::CountryList,
             geo_country_selection: 0,
         })
     }
-
+    
+    // Handles to the shared state, for the capture thread of one source
+    pub fn capture_context(&self, source: &CaptureSource) -> CaptureContext {
+        CaptureContext {
+            interface: Arc::from(source.name()),
+            packet_stats: self.packet_stats.clone(),
+            connections: self.connections.clone(),
+            geo_stats: self.geo_stats.clone(),
+            geoip: self.geoip.clone(),
+            asn_db: self.asn_db.clone(),
+            host_names: self.host_names.clone(),
+            dns_stats: self.dns_stats.clone(),
+            http_stats: self.http_stats.clone(),
+            icmp_stats: self.icmp_stats.clone(),
+            fragments: self.fragments.clone(),
+            neighbors: self.neighbors.clone(),
+            recorder: self.recorder.clone(),
+            services: self.services.clone(),
+            local_networks: self.local_networks.clone(),
+            running: self.running.clone(),
+            capture_finished: self.capture_finished.clone(),
+            clock: self.capture_clock.clone(),
+            filter: self.capture_filter.clone(),
+            decapsulate: self.decapsulate,
+        }
+    }
+    
+    // Current time on the capture's clock: the wall clock for live
+    // interfaces, the newest packet's timestamp when reading a file
+    pub fn capture_time(&self) -> Timestamp {
+        if self.sources.iter().any(|source| matches!(source, CaptureSource::File { .. })) {
+            self.capture_clock.latest()
+        } else {
+            wall_clock()
+        }
+    }
+    
+    // Name of the interface being viewed, None when showing totals
+    pub fn selected_interface(&self) -> Option<&str> {
+        self.interface_view
+            .and_then(|idx| self.interface_stats.get(idx))
+            .map(|stats| stats.interface_name.as_str())
+    }
+    
+    // Cycle between the totals and each monitored interface
+    pub fn next_interface_view(&mut self) {
+        self.interface_view = match self.interface_view {
+            None if self.interface_stats.len() > 1 => Some(0),
+            Some(idx) if idx + 1 < self.interface_stats.len() => Some(idx + 1),
+            _ => None,
+        };
+        self.connection_scroll = 0;
+    }
+    
+    // Cycle the Connections tab through every VLAN / tunnel seen so far
+    pub fn next_segment_filter(&mut self) {
+        let mut segments: Vec<Segment> = match self.connections.try_lock() {
+            Ok(conns) => conns.flows.keys().map(|id| id.segment).collect(),
+            Err(_) => return,
+        };
+        segments.sort();
+        segments.dedup();
+        
+        self.connection_segment_filter = match self.connection_segment_filter {
+            None => segments.first().copied(),
+            Some(current) => segments.into_iter().find(|segment| *segment > current),
+        };
+        self.connection_scroll = 0;
+    }
+    
+    // Cycle the Connections tab through the direction and TLS filters, then
+    // each service seen so far
+    pub fn next_connection_filter(&mut self) {
+        let mut services: Vec<&'static str> = match self.connections.try_lock() {
+            Ok(conns) => conns.flows.iter().filter_map(|(id, stats)| stats.protocol(id.protocol).service).collect(),
+            Err(_) => return,
+        };
+        services.sort();
+        services.dedup();
+        
+        self.connection_filter = self.connection_filter.next(&services);
+        self.connection_scroll = 0;
+    }
+    
+    // Cycle the Connections tab through the TCP states
+    pub fn next_state_filter(&mut self) {
+        self.connection_state_filter = TcpState::next_filter(self.connection_state_filter);
+        self.connection_scroll = 0;
+    }
+    
+    // Cycle the Connections tab through the processes seen so far
+    pub fn next_process_filter(&mut self) {
+        let mut pids: Vec<u32> = match self.connections.try_lock() {
+            Ok(conns) => conns.processes.keys().copied().collect(),
+            Err(_) => return,
+        };
+        pids.sort();
+        
+        self.connection_process_filter = match self.connection_process_filter {
+            None => pids.first().copied(),
+            Some(current) => pids.into_iter().find(|pid| *pid > current),
+        };
+        self.connection_scroll = 0;
+    }
+    
+    // Jump from the Processes tab to the selected process's connections
+    pub fn show_process_connections(&mut self) {
+        let pid = match self.connections.try_lock() {
+            Ok(conns) => sorted_processes(&conns).get(self.process_selection).map(|traffic| traffic.info.pid),
+            Err(_) => return,
+        };
+        
+        if let Some(pid) = pid {
+            self.connection_process_filter = Some(pid);
+            self.connection_scroll = 0;
+            self.current_tab = Tab::Connections;
+        }
+    }
+    
+    // Save the packets in the ring buffer to a file
+    pub fn save_ring_buffer(&self) {
+        if let Some(recorder) = &self.recorder {
+            recorder::dump_ring_buffer(recorder);
+        }
+    }
+    
+    // Write out what's still buffered for the recording file before exiting
+    pub fn finish_recording(&self) {
+        if let Some(recorder) = &self.recorder {
+            if let Ok(mut recorder) = recorder.lock() {
+                let _ = recorder.flush();
+            }
+        }
+    }
+    
+    // Open the filter prompt, pre-filled with the current expression
+    pub fn open_filter_prompt(&mut self) {
+        let current = match self.capture_filter.lock() {
+            Ok(filter) => filter.requested.clone(),
+            Err(_) => String::new(),
+        };
+        self.filter_input = Some(current);
+    }
+    
+    // Send the prompt's expression to the capture thread
+    pub fn submit_filter(&mut self) {
+        if let Some(expression) = self.filter_input.take() {
+            if let Ok(mut filter) = self.capture_filter.lock() {
+                filter.request(expression);
+            }
+        }
+    }
+    
     pub fn update(&mut self) -> Result<()> {
         self.networks.refresh_list();
         
-        let interface_name = &self.network_stats.interface_name;
-        
-        if let Some(network) = self.networks.get(interface_name) {
-            let now = Instant::now();
-            let elapsed = now.duration_since(self.network_stats.last_update).as_secs_f64();
-            
-            let rx_bytes = network.received();
-            let tx_bytes = network.transmitted();
-            
-            // Calculate speeds
-            if elapsed > 0.0 {
-                if rx_bytes >= self.network_stats.rx_bytes {
-                    // Normal case - counter increased
-                    self.network_stats.rx_speed = (rx_bytes - self.network_stats.rx_bytes) as f64 / elapsed;
-                } else {
-                    // Counter reset or wrapped around - use the new value as-is
-                    // This assumes the reset value represents the data transferred since reset
-                    self.network_stats.rx_speed = rx_bytes as f64 / elapsed;
-                    // Don't print debug messages to not interfere with TUI
-                }
+        for stats in self.interface_stats.iter_mut() {
+            if let Some(network) = self.networks.get(&stats.interface_name) {
+                let now = Instant::now();
+                let elapsed = now.duration_since(stats.last_update).as_secs_f64();
                 
-                if tx_bytes >= self.network_stats.tx_bytes {
-                    // Normal case - counter increased
-                    self.network_stats.tx_speed = (tx_bytes - self.network_stats.tx_bytes) as f64 / elapsed;
-                } else {
-                    // Counter reset or wrapped around - use the new value as-is
-                    // This assumes the reset value represents the data transferred since reset
-                    self.network_stats.tx_speed = tx_bytes as f64 / elapsed;
-                    // Don't print debug messages to not interfere with TUI
+                let rx_bytes = network.received();
+                let tx_bytes = network.transmitted();
+                
+                // Calculate speeds
+                if elapsed > 0.0 {
+                    if rx_bytes >= stats.rx_bytes {
+                        // Normal case - counter increased
+                        stats.rx_speed = (rx_bytes - stats.rx_bytes) as f64 / elapsed;
+                    } else {
+                        // Counter reset or wrapped around - use the new value as-is
+                        // This assumes the reset value represents the data transferred since reset
+                        stats.rx_speed = rx_bytes as f64 / elapsed;
+                        // Don't print debug messages to not interfere with TUI
+                    }
+                    
+                    if tx_bytes >= stats.tx_bytes {
+                        // Normal case - counter increased
+                        stats.tx_speed = (tx_bytes - stats.tx_bytes) as f64 / elapsed;
+                    } else {
+                        // Counter reset or wrapped around - use the new value as-is
+                        // This assumes the reset value represents the data transferred since reset
+                        stats.tx_speed = tx_bytes as f64 / elapsed;
+                        // Don't print debug messages to not interfere with TUI
+                    }
                 }
+                
+                // Update stats
+                stats.rx_bytes = rx_bytes;
+                stats.tx_bytes = tx_bytes;
+                stats.last_update = now;
             }
-            
-            // Update stats
-            self.network_stats.rx_bytes = rx_bytes;
-            self.network_stats.tx_bytes = tx_bytes;
-            self.network_stats.last_update = now;
         }
         
         // Update packet stats history every second
@@ -145,6 +409,30 @@ impl App {
             }
         }
         
+        // Expire idle and finished flows
+        if let Ok(mut conns) = self.connections.try_lock() {
+            conns.expire(self.capture_time());
+        }
+        
+        // Log DNS queries that went unanswered
+        if let Ok(mut dns) = self.dns_stats.try_lock() {
+            dns.expire(self.capture_time());
+        }
+        
+        // Give up on datagrams still missing fragments
+        if let Ok(mut fragments) = self.fragments.try_lock() {
+            fragments.expire(self.capture_time());
+        }
+        
+        // Keep the recording file readable while it's being written
+        if let Some(recorder) = &self.recorder {
+            if let Ok(mut recorder) = recorder.try_lock() {
+                if let Err(error) = recorder.flush() {
+                    recorder.error = Some(format!("{:#}", error));
+                }
+            }
+        }
+        
         Ok(())
     }
 }
\ No newline at end of file
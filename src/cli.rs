@@ -0,0 +1,221 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Result;
+use clap::{ArgGroup, Parser};
+use pcap::Device;
+
+use crate::network::flow::FlowTimeouts;
+use crate::network::recorder::RecorderOptions;
+use crate::network::types::{CaptureSource, ReplayMode, Tab};
+use crate::utils::IpRange;
+
+// Command-line options
+#[derive(Debug, Parser)]
+#[command(version, about = "Network traffic monitoring dashboard")]
+#[command(group(ArgGroup::new("recording").args(["record", "ring_buffer"]).multiple(true)))]
+pub struct Args {
+    /// Network interface to capture on, repeatable or comma-separated (prompts when omitted)
+    #[arg(short, long = "interface", value_name = "NAME", value_delimiter = ',', conflicts_with = "read")]
+    pub interfaces: Vec<String>,
+
+    /// Analyze a .pcap/.pcapng file instead of a live interface
+    #[arg(short, long, value_name = "FILE", value_parser = parse_file)]
+    pub read: Option<PathBuf>,
+
+    /// Replay the capture file with its original packet timing
+    #[arg(long, requires = "read")]
+    pub realtime: bool,
+
+    /// BPF expression applied to the capture, e.g. "tcp port 443"
+    #[arg(short, long, value_name = "EXPR")]
+    pub filter: Option<String>,
+
+    /// Don't look inside GRE, VXLAN and IP-in-IP tunnels (VLAN tags are always stripped)
+    #[arg(long)]
+    pub no_decap: bool,
+
+    /// UI refresh interval in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 250, value_parser = clap::value_parser!(u64).range(50..=10_000))]
+    pub tick_rate: u64,
+
+    /// Seconds of packet history kept for the graph
+    #[arg(long, value_name = "SECONDS", default_value_t = 60, value_parser = clap::value_parser!(u64).range(2..=86_400))]
+    pub history: u64,
+
+    /// Most flows kept in the connections table; the least recently seen are evicted beyond it
+    #[arg(long, value_name = "COUNT", default_value_t = 100_000, value_parser = clap::value_parser!(u64).range(100..))]
+    pub max_flows: u64,
+
+    /// Seconds an open TCP connection may stay idle before it's expired
+    #[arg(long, value_name = "SECONDS", default_value_t = 600, value_parser = clap::value_parser!(u64).range(1..))]
+    pub tcp_timeout: u64,
+
+    /// Seconds a UDP flow may stay idle before it's expired
+    #[arg(long, value_name = "SECONDS", default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    pub udp_timeout: u64,
+
+    /// Seconds after which a long-running flow's counters are rolled over into the expired totals
+    #[arg(long, value_name = "SECONDS", default_value_t = 3600, value_parser = clap::value_parser!(u64).range(1..))]
+    pub active_timeout: u64,
+
+    /// procfs root used to map connections to processes (Linux only)
+    #[arg(long, value_name = "DIR", default_value = "/proc")]
+    pub proc_root: PathBuf,
+
+    /// GeoIP database for the Geo Map tab, e.g. GeoLite2-City.mmdb or dbip-city-lite.mmdb
+    #[arg(long, value_name = "FILE", value_parser = parse_file)]
+    pub geoip_db: Option<PathBuf>,
+
+    /// ASN database, .mmdb or .csv (GeoLite2-ASN, DB-IP ASN Lite); repeatable, e.g. for the separate IPv4 and IPv6 CSVs
+    #[arg(long, value_name = "FILE", value_parser = parse_file)]
+    pub asn_db: Vec<PathBuf>,
+
+    /// Extra port-to-service names, one per line like /etc/services ("postgres 5432/tcp", "myapp 9000-9009/udp"); they override the built-in table
+    #[arg(long, value_name = "FILE", value_parser = parse_file)]
+    pub services: Option<PathBuf>,
+
+    /// MAC vendor (OUI) registry for the Neighbors tab: Wireshark's manuf, or the IEEE oui.txt / oui.csv
+    #[arg(long, value_name = "FILE", value_parser = parse_file)]
+    pub oui_db: Option<PathBuf>,
+
+    /// Write captured packets to rotating pcapng files in this directory (created if missing)
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+
+    /// BPF expression packets must also match to be recorded or kept in the ring buffer, e.g. "port 53"
+    #[arg(long, value_name = "EXPR", requires = "recording")]
+    pub record_filter: Option<String>,
+
+    /// Start a new recording file after this many megabytes
+    #[arg(long, value_name = "MB", default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..), requires = "record")]
+    pub rotate_size: u64,
+
+    /// Start a new recording file after this many seconds of capture
+    #[arg(long, value_name = "SECONDS", default_value_t = 3600, value_parser = clap::value_parser!(u64).range(1..), requires = "record")]
+    pub rotate_time: u64,
+
+    /// Keep only the newest recording files, deleting older ones
+    #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u64).range(1..), requires = "record")]
+    pub rotate_files: Option<u64>,
+
+    /// Keep the last minutes of packets in memory, saved to a pcapng file with 'w'
+    #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..=1440))]
+    pub ring_buffer: Option<u64>,
+
+    /// Look up host names of connection endpoints with reverse DNS (names seen in DNS traffic are always used)
+    #[arg(long)]
+    pub reverse_dns: bool,
+
+    /// Tab shown at startup
+    #[arg(long, value_name = "TAB", value_enum, default_value_t = Tab::Overview)]
+    pub tab: Tab,
+
+    /// Extra local network in CIDR notation (repeatable), e.g. 100.64.0.0/10 or 2001:db8:1::/64
+    #[arg(short = 'L', long = "local-net", value_name = "CIDR")]
+    pub local_networks: Vec<IpRange>,
+
+    /// Start with the logarithmic graph scale
+    #[arg(long)]
+    pub log_scale: bool,
+
+    /// List capturable interfaces and exit
+    #[arg(long)]
+    pub list_interfaces: bool,
+}
+
+impl Args {
+    // Capture sources from the arguments; empty when none were given
+    pub fn capture_sources(&self) -> Vec<CaptureSource> {
+        if let Some(path) = &self.read {
+            let replay = if self.realtime { ReplayMode::Realtime } else { ReplayMode::Fast };
+            vec![CaptureSource::File { path: path.clone(), replay }]
+        } else {
+            let mut interfaces: Vec<String> = Vec::new();
+            for name in &self.interfaces {
+                if !interfaces.contains(name) {
+                    interfaces.push(name.clone());
+                }
+            }
+            interfaces.into_iter().map(CaptureSource::Interface).collect()
+        }
+    }
+
+    // Flow expiry settings from the timeout options
+    pub fn flow_timeouts(&self) -> FlowTimeouts {
+        FlowTimeouts::new(
+            Duration::from_secs(self.tcp_timeout),
+            Duration::from_secs(self.udp_timeout),
+            Duration::from_secs(self.active_timeout),
+        )
+    }
+
+    // Packet recording settings; None when neither files nor a ring buffer were asked for
+    pub fn recorder_options(&self) -> Option<RecorderOptions> {
+        if self.record.is_none() && self.ring_buffer.is_none() {
+            return None;
+        }
+        Some(RecorderOptions {
+            directory: self.record.clone(),
+            filter: self.record_filter.clone(),
+            rotate_size: self.rotate_size * 1024 * 1024,
+            rotate_time: Duration::from_secs(self.rotate_time),
+            max_files: self.rotate_files.map(|count| count as usize),
+            ring_window: self.ring_buffer.map(|minutes| Duration::from_secs(minutes * 60)),
+        })
+    }
+}
+
+fn parse_file(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("file not found: {}", value))
+    }
+}
+
+// Print the devices pcap can capture on
+pub fn list_interfaces() -> Result<()> {
+    let devices = Device::list()?;
+
+    if devices.is_empty() {
+        println!("No capturable interfaces found (are you running with enough privileges?)");
+        return Ok(());
+    }
+
+    for device in devices {
+        let addresses = device.addresses.iter()
+            .map(|address| address.addr.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let status = if device.flags.is_up() { "up" } else { "down" };
+
+        match &device.desc {
+            Some(desc) => println!("{} ({}) [{}] {}", device.name, status, desc, addresses),
+            None => println!("{} ({}) {}", device.name, status, addresses),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["rnet"].iter().chain(args))
+    }
+
+    #[test]
+    fn record_filter_needs_somewhere_to_record() {
+        let error = parse(&["--record-filter", "port 53"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+
+        let args = parse(&["--record", "/tmp/captures", "--record-filter", "port 53"]).unwrap();
+        assert_eq!(args.record_filter.as_deref(), Some("port 53"));
+        assert!(parse(&["--ring-buffer", "5", "--record-filter", "port 53"]).is_ok());
+        assert!(parse(&["--record", "/tmp/captures", "--ring-buffer", "5"]).is_ok());
+    }
+}
@@ -1,4 +1,5 @@
 mod app;
+mod cli;
 mod network;
 mod ui;
 mod utils;
@@ -11,6 +12,7 @@ use std::{
 };
 
 use anyhow::Result;
+use clap::Parser;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
@@ -23,9 +25,14 @@ use ratatui::{
 use sysinfo::Networks;
 
 use crate::app::App;
-use crate::network::types::Tab;
+use crate::cli::{list_interfaces, Args};
+use crate::network::types::{CaptureSource, Tab};
 use crate::network::capture::start_packet_capture;
+use crate::network::names::start_ptr_resolver;
+use crate::network::process::{start_process_monitor, ProcessResolver};
+use crate::network::recorder::Recorder;
 use crate::ui::*;
+use crate::utils::format_bytes;
 
 fn draw_ui(f: &mut Frame, app: &mut App) {
     // Clear the entire frame first to prevent artifacts
@@ -49,6 +56,10 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
         Tab::PacketGraph.to_string(),
         Tab::PacketDistribution.to_string(),
         Tab::Connections.to_string(),
+        Tab::Processes.to_string(),
+        Tab::Dns.to_string(),
+        Tab::Http.to_string(),
+        Tab::Neighbors.to_string(),
         Tab::GeoMap.to_string(),
     ];
     
@@ -57,11 +68,35 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
         Tab::PacketGraph => 1,
         Tab::PacketDistribution => 2,
         Tab::Connections => 3,
-        Tab::GeoMap => 4,
+        Tab::Processes => 4,
+        Tab::Dns => 5,
+        Tab::Http => 6,
+        Tab::Neighbors => 7,
+        Tab::GeoMap => 8,
     };
     
+    // Show the interface being viewed and the active capture filter, or why
+    // the last one was rejected
+    let mut title = vec![Span::raw("Network Dashboard")];
+    if app.interface_stats.len() > 1 {
+        let view = app.selected_interface().unwrap_or("all interfaces");
+        title.push(Span::styled(format!(" | View: {}", view), Style::default().fg(Color::Yellow)));
+    }
+    if let Ok(filter) = app.capture_filter.try_lock() {
+        if let Some(error) = &filter.error {
+            title.push(Span::styled(format!(" | Filter error: {}", error), Style::default().fg(Color::Red)));
+        } else if !filter.active.is_empty() {
+            title.push(Span::styled(format!(" | Filter: {}", filter.active), Style::default().fg(Color::Cyan)));
+        }
+    }
+    if let Some(recorder) = &app.recorder {
+        if let Ok(recorder) = recorder.try_lock() {
+            recorder_status(&recorder, &mut title);
+        }
+    }
+    
     let tabs = Tabs::new(titles)
-        .block(Block::default().title("Network Dashboard").borders(Borders::ALL))
+        .block(Block::default().title(Line::from(title)).borders(Borders::ALL))
         .select(selected_index)
         .style(Style::default().fg(Color::White))
         .highlight_style(Style::default().fg(Color::Yellow));
@@ -79,6 +114,10 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
         Tab::PacketGraph => draw_packet_graph(f, app, main_chunks[1]),
         Tab::PacketDistribution => draw_packet_bar_chart(f, app, main_chunks[1]),
         Tab::Connections => draw_connections(f, app, main_chunks[1]),
+        Tab::Processes => draw_processes(f, app, main_chunks[1]),
+        Tab::Dns => draw_dns(f, app, main_chunks[1]),
+        Tab::Http => draw_http(f, app, main_chunks[1]),
+        Tab::Neighbors => draw_neighbors(f, app, main_chunks[1]),
         Tab::GeoMap => draw_geo_map(f, app, main_chunks[1]),
     }
     
@@ -86,10 +125,54 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
     if app.show_help {
         draw_help_overlay(f, f.size());
     }
+    
+    // Draw the filter prompt while it is being edited
+    if app.filter_input.is_some() {
+        draw_filter_prompt(f, app, f.size());
+    }
+    if app.tls_search_input.is_some() {
+        draw_tls_search_prompt(f, app, f.size());
+    }
+}
+
+// Title entries for the recording file and the ring buffer
+fn recorder_status(recorder: &Recorder, title: &mut Vec<Span>) {
+    if let Some(error) = &recorder.error {
+        title.push(Span::styled(format!(" | Record error: {}", error), Style::default().fg(Color::Red)));
+    } else if let Some((name, size)) = recorder.current_file() {
+        let files = recorder.files_written();
+        title.push(Span::styled(
+            format!(" | Recording: {} ({}, {} packets, file {})", name, format_bytes(size), recorder.packets, files),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    
+    if !recorder.ring_enabled() {
+        return;
+    }
+    if recorder.dumping {
+        title.push(Span::styled(" | Saving ring buffer...", Style::default().fg(Color::Yellow)));
+        return;
+    }
+    match &recorder.last_dump {
+        Some(Ok(path)) => {
+            title.push(Span::styled(format!(" | Saved {}", path.display()), Style::default().fg(Color::Green)));
+        },
+        Some(Err(error)) => {
+            title.push(Span::styled(format!(" | Save failed: {}", error), Style::default().fg(Color::Red)));
+        },
+        None => {
+            let (packets, bytes) = recorder.ring_usage();
+            title.push(Span::styled(
+                format!(" | Ring: {} packets, {} (w saves)", packets, format_bytes(bytes as u64)),
+                Style::default().fg(Color::Magenta),
+            ));
+        },
+    }
 }
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
-    let tick_rate = Duration::from_millis(250);
+    let tick_rate = app.tick_rate;
     let mut last_tick = Instant::now();
     
     loop {
@@ -104,10 +187,33 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                 // If help is showing, any key closes it
                 if app.show_help {
                     app.show_help = false;
+                } else if let Some(input) = app.tls_search_input.as_mut() {
+                    // So does the TLS search prompt
+                    match key.code {
+                        KeyCode::Enter => app.tls_search = app.tls_search_input.take().unwrap_or_default().trim().to_lowercase(),
+                        KeyCode::Esc => app.tls_search_input = None,
+                        KeyCode::Backspace => {
+                            input.pop();
+                        },
+                        KeyCode::Char(c) => input.push(c),
+                        _ => {}
+                    }
+                } else if let Some(input) = app.filter_input.as_mut() {
+                    // The filter prompt takes all keys while it is open
+                    match key.code {
+                        KeyCode::Enter => app.submit_filter(),
+                        KeyCode::Esc => app.filter_input = None,
+                        KeyCode::Backspace => {
+                            input.pop();
+                        },
+                        KeyCode::Char(c) => input.push(c),
+                        _ => {}
+                    }
                 } else {
                     match key.code {
                         KeyCode::Char('q') => {
                             app.running.store(false, Ordering::Relaxed);
+                            app.finish_recording();
                             return Ok(());
                         },
                         KeyCode::Left => {
@@ -130,28 +236,70 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                                 network::types::ProtocolGrouping::Detailed => network::types::ProtocolGrouping::Basic,
                             };
                         },
+                        // Cycle between all interfaces and each one with 'i'
+                        KeyCode::Char('i') => {
+                            app.next_interface_view();
+                        },
+                        // Edit the BPF capture filter with 'b'
+                        KeyCode::Char('b') => {
+                            app.open_filter_prompt();
+                        },
+                        // Save the ring buffer of recent packets with 'w'
+                        KeyCode::Char('w') => {
+                            app.save_ring_buffer();
+                        },
                         // Display help with 'h'
                         KeyCode::Char('h') => {
                             app.show_help = true;
                         },
                         // Sort connections with 's' (when on Connections tab)
-                        KeyCode::Char('s') => {
-                            if app.current_tab == Tab::Connections {
-                                app.connection_sort = app.connection_sort.next();
-                            }
+                        KeyCode::Char('s') if app.current_tab == Tab::Connections => {
+                            app.connection_sort = app.connection_sort.next();
                         },
                         // Filter connections with 'f' (when on Connections tab)
                         KeyCode::Char('f') => {
                             if app.current_tab == Tab::Connections {
-                                app.connection_filter = app.connection_filter.next();
+                                app.next_connection_filter();
                             } else if app.current_tab == Tab::GeoMap {
                                 app.geo_mode = app.geo_mode.next();
                             }
                         },
+                        // Filter connections by VLAN / tunnel with 'v'
+                        KeyCode::Char('v') if app.current_tab == Tab::Connections => {
+                            app.next_segment_filter();
+                        },
+                        // Filter connections by process with 'p'
+                        KeyCode::Char('p') if app.current_tab == Tab::Connections => {
+                            app.next_process_filter();
+                        },
+                        // Show the selected process's connections with Enter
+                        KeyCode::Enter if app.current_tab == Tab::Processes => {
+                            app.show_process_connections();
+                        },
+                        // Filter connections by TCP state with 't'
+                        KeyCode::Char('t') if app.current_tab == Tab::Connections => {
+                            app.next_state_filter();
+                        },
+                        // Search connections by TLS server name or fingerprint with '/'
+                        KeyCode::Char('/') if app.current_tab == Tab::Connections => {
+                            app.tls_search_input = Some(app.tls_search.clone());
+                        },
+                        // Show host names instead of addresses with 'n'
+                        KeyCode::Char('n') if app.current_tab == Tab::Connections => {
+                            app.show_hostnames = !app.show_hostnames;
+                        },
                         // Scroll through connections list or country list
                         KeyCode::Up => {
                             if app.current_tab == Tab::Connections && app.connection_scroll > 0 {
                                 app.connection_scroll -= 1;
+                            } else if app.current_tab == Tab::Processes && app.process_selection > 0 {
+                                app.process_selection -= 1;
+                            } else if app.current_tab == Tab::Dns && app.dns_scroll > 0 {
+                                app.dns_scroll -= 1;
+                            } else if app.current_tab == Tab::Http && app.http_scroll > 0 {
+                                app.http_scroll -= 1;
+                            } else if app.current_tab == Tab::Neighbors && app.neighbor_scroll > 0 {
+                                app.neighbor_scroll -= 1;
                             } else if app.current_tab == Tab::GeoMap && 
                                      app.geo_mode == network::types::GeoMode::CountryList && 
                                      app.geo_country_selection > 0 {
@@ -161,6 +309,15 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                         KeyCode::Down => {
                             if app.current_tab == Tab::Connections {
                                 app.connection_scroll += 1;
+                            } else if app.current_tab == Tab::Processes {
+                                // Clamped to the list length when drawing
+                                app.process_selection += 1;
+                            } else if app.current_tab == Tab::Dns {
+                                app.dns_scroll += 1;
+                            } else if app.current_tab == Tab::Http {
+                                app.http_scroll += 1;
+                            } else if app.current_tab == Tab::Neighbors {
+                                app.neighbor_scroll += 1;
                             } else if app.current_tab == Tab::GeoMap && 
                                      app.geo_mode == network::types::GeoMode::CountryList {
                                 // We'll limit the max selection in the draw function based on the actual list length
@@ -174,10 +331,8 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                                 app.connection_scroll = 0;
                             }
                         },
-                        KeyCode::PageDown => {
-                            if app.current_tab == Tab::Connections {
-                                app.connection_scroll += 10;
-                            }
+                        KeyCode::PageDown if app.current_tab == Tab::Connections => {
+                            app.connection_scroll += 10;
                         },
                         _ => {}
                     }
@@ -192,8 +347,8 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
     }
 }
 
-// Function to get user-selected network interface
-fn select_network_interface() -> Result<String> {
+// Function to get user-selected network interfaces
+fn select_network_interfaces() -> Result<Vec<String>> {
     let networks = Networks::new_with_refreshed_list();
     
     // Collect available interfaces
@@ -204,6 +359,10 @@ fn select_network_interface() -> Result<String> {
     // Sort by name for easier selection
     interface_names.sort();
     
+    if interface_names.is_empty() {
+        anyhow::bail!("No network interfaces found");
+    }
+    
     // Display the menu of available interfaces
     println!("Available network interfaces:");
     for (idx, name) in interface_names.iter().enumerate() {
@@ -216,31 +375,71 @@ fn select_network_interface() -> Result<String> {
     }
     
     // Prompt for user selection
-    println!("\nEnter the number of the interface to monitor:");
+    println!("\nEnter the number(s) of the interface(s) to monitor, separated by commas:");
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     
     // Parse the input
-    let selected_idx = match input.trim().parse::<usize>() {
-        Ok(idx) if idx < interface_names.len() => idx,
-        _ => {
-            println!("Invalid selection, using first interface");
-            0
+    let mut selected: Vec<String> = Vec::new();
+    for part in input.split(',') {
+        match part.trim().parse::<usize>() {
+            Ok(idx) if idx < interface_names.len() && !selected.contains(&interface_names[idx]) => {
+                selected.push(interface_names[idx].clone());
+            },
+            _ => {}
         }
-    };
+    }
     
-    let interface_name = interface_names[selected_idx].clone();
-    println!("Selected network interface: {}", interface_name);
+    if selected.is_empty() {
+        println!("Invalid selection, using first interface");
+        selected.push(interface_names[0].clone());
+    }
+    
+    println!("Selected network interface(s): {}", selected.join(", "));
     
     // Wait a moment for user to see the selection
     thread::sleep(Duration::from_millis(1000));
     
-    Ok(interface_name)
+    Ok(selected)
 }
 
 fn main() -> Result<()> {
-    // Select the network interface first, before setting up the UI
-    let selected_interface = select_network_interface()?;
+    let args = Args::parse();
+    
+    if args.list_interfaces {
+        return list_interfaces();
+    }
+    
+    // Use the interfaces or file from the command line, falling back to the
+    // interactive picker before setting up the UI
+    let mut sources = args.capture_sources();
+    if sources.is_empty() {
+        sources = select_network_interfaces()?
+            .into_iter()
+            .map(CaptureSource::Interface)
+            .collect();
+    }
+    
+    // Create app for the selected sources
+    let mut app = App::new(sources.clone(), &args)?;
+    
+    // Map live connections to local processes where procfs is available
+    let live = sources.iter().all(|source| matches!(source, CaptureSource::Interface(_)));
+    if let Some(resolver) = ProcessResolver::new(&args.proc_root).filter(|_| live) {
+        start_process_monitor(resolver, app.connections.clone(), app.running.clone());
+        app.process_attribution = true;
+    }
+    
+    // Resolve connection endpoints in the background when asked to
+    if args.reverse_dns {
+        start_ptr_resolver(app.host_names.clone(), app.running.clone());
+    }
+    
+    // Start one capture thread per source
+    for source in sources {
+        let ctx = app.capture_context(&source);
+        start_packet_capture(source, ctx)?;
+    }
     
     // Set up terminal
     enable_raw_mode()?;
@@ -249,30 +448,6 @@ fn main() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     
-    // Create app with the selected interface
-    let mut app = App::new()?;
-    
-    // Set up the network stats with the selected interface
-    let networks = Networks::new_with_refreshed_list();
-    app.network_stats.interface_name = selected_interface;
-    
-    // Update initial stats for the selected interface
-    if let Some(network) = networks.get(&app.network_stats.interface_name) {
-        app.network_stats.rx_bytes = network.received();
-        app.network_stats.tx_bytes = network.transmitted();
-    } else {
-        eprintln!("Warning: Could not get initial stats for selected interface");
-    }
-    
-    // Start packet capture
-    let interface_name = app.network_stats.interface_name.clone();
-    let packet_stats = app.packet_stats.clone();
-    let connections = app.connections.clone();
-    let geo_stats = app.geo_stats.clone();
-    let local_networks = app.local_networks.clone();
-    let running = app.running.clone();
-    start_packet_capture(interface_name, packet_stats, connections, geo_stats, local_networks, running)?;
-    
     // Run the app
     let res = run_app(&mut terminal, app);
     
@@ -290,4 +465,4 @@ fn main() -> Result<()> {
     }
     
     Ok(())
-}
\ No newline at end of file
+}
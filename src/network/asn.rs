@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context, Result};
+use maxminddb::{geoip2, Reader};
+
+use crate::utils::IpRange;
+use super::types::AsnInfo;
+
+// Cached lookups before the cache is cleared, to bound memory on busy links
+const MAX_CACHED_IPS: usize = 100_000;
+
+// A block of addresses announced by one autonomous system, with IPv4
+// addresses mapped into the IPv6 space so both families share one table
+struct AsnRange {
+    first: u128,
+    last: u128,
+    info: Arc<AsnInfo>,
+}
+
+// Autonomous system lookups from GeoLite2-ASN / DB-IP ASN .mmdb files or
+// their CSV exports. Several files can be combined, e.g. the separate IPv4
+// and IPv6 CSVs; the first one that knows an address wins
+pub struct AsnDatabase {
+    readers: Vec<Reader<Vec<u8>>>,
+    ranges: Vec<AsnRange>,      // Sorted by first address
+    files: Vec<String>,
+    cache: Mutex<HashMap<IpAddr, Option<Arc<AsnInfo>>>>,
+}
+
+impl AsnDatabase {
+    pub fn open(paths: &[PathBuf]) -> Result<Self> {
+        let mut readers = Vec::new();
+        let mut ranges = Vec::new();
+        
+        for path in paths {
+            if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+                let loaded = read_csv(path)
+                    .with_context(|| format!("Failed to read ASN database {}", path.display()))?;
+                if loaded.is_empty() {
+                    bail!("No ASN ranges found in {}", path.display());
+                }
+                ranges.extend(loaded);
+            } else {
+                let reader = Reader::open_readfile(path)
+                    .with_context(|| format!("Failed to open ASN database {}", path.display()))?;
+                readers.push(reader);
+            }
+        }
+        ranges.sort_by_key(|range| range.first);
+        
+        let files = paths.iter()
+            .map(|path| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned())
+            .collect();
+        
+        Ok(AsnDatabase {
+            readers,
+            ranges,
+            files,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+    
+    // File names of the loaded databases, for display
+    pub fn description(&self) -> String {
+        self.files.join(", ")
+    }
+    
+    // Network an address belongs to. Results, including misses, are cached per IP
+    pub fn lookup(&self, ip: IpAddr) -> Option<Arc<AsnInfo>> {
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(cached) = cache.get(&ip) {
+                return cached.clone();
+            }
+        }
+        
+        let info = self.lookup_ranges(ip).or_else(|| {
+            self.readers.iter().find_map(|reader| {
+                let record = reader.lookup::<geoip2::Asn>(ip).ok()?;
+                Some(Arc::new(AsnInfo {
+                    number: record.autonomous_system_number?,
+                    organization: record.autonomous_system_organization.unwrap_or_default().to_string(),
+                }))
+            })
+        });
+        
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.len() >= MAX_CACHED_IPS {
+                cache.clear();
+            }
+            cache.insert(ip, info.clone());
+        }
+        
+        info
+    }
+    
+    fn lookup_ranges(&self, ip: IpAddr) -> Option<Arc<AsnInfo>> {
+        let key = range_key(ip);
+        // The last range starting at or before the address
+        let idx = self.ranges.partition_point(|range| range.first <= key).checked_sub(1)?;
+        let range = &self.ranges[idx];
+        (key <= range.last).then(|| range.info.clone())
+    }
+}
+
+fn range_key(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ipv4) => u128::from(ipv4.to_ipv6_mapped()),
+        IpAddr::V6(ipv6) => u128::from(ipv6),
+    }
+}
+
+// Rows are either "network,asn,organization" (GeoLite2-ASN-Blocks) or
+// "first,last,asn,organization" (DB-IP ASN Lite). Header rows and anything
+// else that doesn't parse are skipped
+fn read_csv(path: &Path) -> Result<Vec<AsnRange>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+    
+    // Organizations repeat across many ranges, so share their names
+    let mut networks: HashMap<(u32, String), Arc<AsnInfo>> = HashMap::new();
+    let mut ranges = Vec::new();
+    
+    for record in reader.records() {
+        let record = record?;
+        
+        let parsed = if record.len() >= 4 {
+            let first = record[0].trim().parse::<IpAddr>().ok();
+            let last = record[1].trim().parse::<IpAddr>().ok();
+            // Unquoted names with commas in them spill into extra fields
+            let organization = record.iter().skip(3).collect::<Vec<_>>().join(",");
+            first.zip(last).map(|(first, last)| (first, last, &record[2], organization))
+        } else if record.len() == 3 {
+            record[0].trim().parse::<IpRange>().ok()
+                .map(|network| network.bounds())
+                .map(|(first, last)| (first, last, &record[1], record[2].to_string()))
+        } else {
+            None
+        };
+        
+        let Some((first, last, number, organization)) = parsed else { continue };
+        // AS0 marks unrouted space
+        let Some(number) = number.trim().trim_start_matches("AS").parse::<u32>().ok().filter(|&number| number != 0) else { continue };
+        let organization = organization.trim().to_string();
+        
+        let info = networks.entry((number, organization.clone()))
+            .or_insert_with(|| Arc::new(AsnInfo { number, organization }))
+            .clone();
+        
+        ranges.push(AsnRange {
+            first: range_key(first),
+            last: range_key(last),
+            info,
+        });
+    }
+    
+    Ok(ranges)
+}
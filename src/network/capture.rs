@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     net::IpAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -10,332 +9,689 @@ use std::{
 };
 
 use anyhow::{Context, Result};
-use pcap::{Device, Capture};
+use pcap::{Activated, Capture, Device, Linktype};
 use pnet::packet::{
-    ethernet::{EtherTypes, EthernetPacket},
-    ip::IpNextHeaderProtocols,
-    ipv4::Ipv4Packet,
+    ethernet::{EtherType, EtherTypes},
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::{Ipv4Flags, Ipv4Packet},
     ipv6::Ipv6Packet,
     tcp::TcpPacket,
     udp::UdpPacket,
     Packet,
 };
+use pnet::util::MacAddr;
 
-use crate::utils::is_local_ip;
+use crate::utils::{is_local_ip, IpRange};
+use super::decode::{decode_ip_tunnel, decode_link, strip_vlan_tags};
+use super::flow::{FlowPacket, FlowTable, TcpSegment};
+use super::asn::AsnDatabase;
+use super::dns::{DnsMessage, DnsStats};
+use super::fragment::{parse_ipv6_fragment, skip_ipv6_extensions, Fragment, FragmentKey, FragmentTable};
+use super::geoip::GeoIp;
+use super::http::{HttpEvent, HttpStats};
+use super::icmp::{IcmpMessage, IcmpStats};
+use super::names::HostNames;
+use super::neighbors::{parse_arp, parse_ndp, NeighborProtocol, NeighborTable};
+use super::recorder::Recorder;
+use super::services::ServiceTable;
 use super::types::*;
 
-// Simple IP to geo lookup that returns country code and coordinates
-// In a real application, this would use a GeoIP database
-pub fn lookup_ip_location(ip: IpAddr) -> Option<GeoLocation> {
-    // If it's a local IP, don't attempt geolocation
-    if crate::utils::is_loopback_ip(ip) || crate::utils::is_private_ip(ip) {
-        return None;
-    }
-    
-    // For demonstration, we'll use a simplistic approach:
-    // Assign locations based on IP range
-    // This is for simulation only!
-    let octets = match ip {
-        IpAddr::V4(ipv4) => ipv4.octets(),
-        IpAddr::V6(_) => return None, // Skip IPv6 for simplicity
+// IPv6 networks (typically the on-link /64s) configured on an interface, so
+// global addresses of the local segment count as local
+pub fn interface_ipv6_networks(interface_name: &str) -> Vec<IpRange> {
+    let devices = match Device::list() {
+        Ok(devices) => devices,
+        Err(_) => return Vec::new(),
     };
     
-    // Extremely simplified classification based on first octet
-    // This is NOT accurate, just for demonstration!
-    match octets[0] {
-        0..=49 => Some(GeoLocation {
-            country: "US".to_string(),
-            region: "North America".to_string(),
-            latitude: 37.0902,
-            longitude: -95.7129,
-        }),
-        50..=99 => Some(GeoLocation {
-            country: "EU".to_string(),
-            region: "Europe".to_string(),
-            latitude: 54.5260,
-            longitude: 15.2551,
-        }),
-        100..=149 => Some(GeoLocation {
-            country: "CN".to_string(),
-            region: "Asia".to_string(),
-            latitude: 35.8617,
-            longitude: 104.1954,
-        }),
-        150..=199 => Some(GeoLocation {
-            country: "AU".to_string(),
-            region: "Oceania".to_string(),
-            latitude: -25.2744,
-            longitude: 133.7751,
-        }),
-        _ => Some(GeoLocation {
-            country: "BR".to_string(),
-            region: "South America".to_string(),
-            latitude: -14.2350,
-            longitude: -51.9253,
-        }),
-    }
+    devices.into_iter()
+        .filter(|device| device.name == interface_name)
+        .flat_map(|device| device.addresses)
+        .filter(|address| address.addr.is_ipv6())
+        .filter_map(|address| IpRange::from_netmask(address.addr, address.netmask?))
+        .collect()
 }
 
-// Helper function to determine the direction of a connection
-pub fn get_connection_direction(src_ip: IpAddr, dst_ip: IpAddr, local_networks: &[crate::utils::IpRange]) -> ConnectionDirection {
-    let src_is_local = is_local_ip(src_ip, local_networks);
-    let dst_is_local = is_local_ip(dst_ip, local_networks);
-    
-    // If source is local and destination is not, it's outbound
-    if src_is_local && !dst_is_local {
+// Direction of a connection, from the side that opened it: flows started by
+// a local host are outbound, everything else is inbound
+pub fn get_connection_direction(client_ip: IpAddr, local_networks: &[IpRange]) -> ConnectionDirection {
+    if is_local_ip(client_ip, local_networks) {
         ConnectionDirection::Outbound
     } else {
-        // Otherwise consider it inbound (includes local to local)
         ConnectionDirection::Inbound
     }
 }
 
-pub fn start_packet_capture(
-    interface_name: String, 
-    packet_stats: Arc<Mutex<PacketStats>>,
-    connections: Arc<Mutex<HashMap<ConnectionId, ConnectionStats>>>,
-    geo_stats: Arc<Mutex<GeoStats>>,
-    local_networks: Vec<crate::utils::IpRange>,
-    running: Arc<AtomicBool>
-) -> Result<()> {
-    // Find the device with the matching name
-    let devices = Device::list()?;
-    let device = devices.into_iter()
-        .find(|d| d.name == interface_name)
-        .context(format!("Failed to find device {}", interface_name))?;
+// Shared state a capture thread writes into
+#[derive(Clone)]
+pub struct CaptureContext {
+    pub interface: Arc<str>,    // Name every packet and connection is tagged with
+    pub packet_stats: Arc<Mutex<PacketStats>>,
+    pub connections: Arc<Mutex<FlowTable>>,
+    pub geo_stats: Arc<Mutex<GeoStats>>,
+    pub geoip: Option<Arc<GeoIp>>,  // Location lookups, when a database was given
+    pub asn_db: Option<Arc<AsnDatabase>>,
+    pub host_names: Arc<Mutex<HostNames>>,
+    pub dns_stats: Arc<Mutex<DnsStats>>,
+    pub http_stats: Arc<Mutex<HttpStats>>,
+    pub icmp_stats: Arc<Mutex<IcmpStats>>,
+    pub fragments: Arc<Mutex<FragmentTable>>,
+    pub neighbors: Arc<Mutex<NeighborTable>>,
+    pub recorder: Option<Arc<Mutex<Recorder>>>,    // Writes packets to pcapng files, when enabled
+    pub services: Arc<ServiceTable>,   // Port to service names
+    pub local_networks: Vec<IpRange>,
+    pub running: Arc<AtomicBool>,
+    pub capture_finished: Arc<AtomicBool>,
+    pub clock: Arc<CaptureClock>,   // Newest packet timestamp, shared by all sources
+    pub filter: Arc<Mutex<CaptureFilter>>,
+    pub decapsulate: bool,      // Look inside GRE, VXLAN and IP-in-IP tunnels
+}
+
+pub fn start_packet_capture(source: CaptureSource, ctx: CaptureContext) -> Result<()> {
+    match source {
+        CaptureSource::Interface(interface_name) => {
+            // Find the device with the matching name
+            let devices = Device::list()?;
+            let device = devices.into_iter()
+                .find(|d| d.name == interface_name)
+                .context(format!("Failed to find device {}", interface_name))?;
+            
+            // Create a new capture instance
+            let capture_device = Capture::from_device(device)?;
+            let capture_device = capture_device.immediate_mode(true);
+            let capture_device = capture_device.snaplen(65535);
+            // Wake up regularly so filter changes and quitting are noticed on idle links
+            let capture_device = capture_device.timeout(250);
+            
+            let cap = match capture_device.open() {
+                Ok(cap) => cap,
+                Err(e) => {
+                    // Log error but don't stop the app - we'll just run with no packet capture
+                    eprintln!("Error opening capture device: {}", e);
+                    return Ok(());
+                }
+            };
+            
+            thread::spawn(move || run_capture_loop(cap, None, ctx));
+        },
+        CaptureSource::File { path, replay } => {
+            // Unlike a live device, a file that can't be opened is a hard error
+            let cap = Capture::from_file(&path)
+                .context(format!("Failed to open capture file {}", path.display()))?;
+            
+            thread::spawn(move || run_capture_loop(cap, Some(replay), ctx));
+        },
+    }
     
-    // Create a new capture instance
-    let capture_device = Capture::from_device(device)?;
-    let capture_device = capture_device.immediate_mode(true);
-    let capture_device = capture_device.snaplen(65535);
+    Ok(())
+}
+
+// Read packets until the app stops or, for capture files, the file runs out
+fn run_capture_loop<T: Activated + ?Sized>(mut cap: Capture<T>, replay: Option<ReplayMode>, ctx: CaptureContext) {
+    // Track errors so we don't spam the console
+    let mut consecutive_errors = 0;
+    // Wall clock and capture timestamp of the first replayed packet
+    let mut replay_origin: Option<(Instant, Duration)> = None;
+    // Filter generation last compiled on this handle
+    let mut filter_generation = 0;
+    // Link-layer header type, used to find the IP layer in every frame
+    let linktype = cap.get_datalink();
+    // This source's index in the recorder
+    let record_index = ctx.recorder.as_ref()
+        .and_then(|recorder| recorder.lock().ok())
+        .map(|mut recorder| recorder.add_interface(&ctx.interface, linktype));
     
-    let mut cap = match capture_device.open() {
-        Ok(cap) => cap,
+    while ctx.running.load(Ordering::Relaxed) {
+        apply_filter_changes(&mut cap, &ctx.filter, &mut filter_generation);
+        
+        match cap.next_packet() {
+            Ok(packet) => {
+                // Reset error counter on success
+                consecutive_errors = 0;
+                
+                if replay == Some(ReplayMode::Realtime) {
+                    let timestamp = packet_timestamp(packet.header);
+                    let (started, first_timestamp) = *replay_origin.get_or_insert((Instant::now(), timestamp));
+                    let due = started + timestamp.saturating_sub(first_timestamp);
+                    
+                    // Sleep in short steps so quitting stays responsive during long gaps
+                    loop {
+                        let now = Instant::now();
+                        if now >= due || !ctx.running.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        thread::sleep((due - now).min(Duration::from_millis(100)));
+                    }
+                }
+                
+                process_packet(&ctx, linktype, packet.data, packet.header.len, packet_timestamp(packet.header));
+                
+                if let (Some(recorder), Some(index)) = (&ctx.recorder, record_index) {
+                    if let Ok(mut recorder) = recorder.lock() {
+                        recorder.record(index, packet_timestamp(packet.header), packet.header.len, packet.data);
+                    }
+                }
+            },
+            Err(pcap::Error::TimeoutExpired) => {
+                // No traffic within the read timeout - just check the filter and running flag again
+            },
+            Err(pcap::Error::NoMorePackets) => {
+                // End of the capture file - keep the collected stats on screen
+                ctx.capture_finished.store(true, Ordering::Relaxed);
+                break;
+            },
+            Err(_) => {
+                consecutive_errors += 1;
+                
+                // Only log every 100th error to avoid flooding
+                if consecutive_errors == 1 || consecutive_errors % 100 == 0 {
+                    // Don't use println in a TUI app - errors will mess up the display
+                    // Just sleep and continue
+                }
+                
+                // Sleep to avoid spinning CPU on repeated errors
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+// Recompile the BPF filter when the UI requested a new expression. On a
+// syntax error the previous filter stays in place and the error is reported
+fn apply_filter_changes<T: Activated + ?Sized>(cap: &mut Capture<T>, filter: &Mutex<CaptureFilter>, applied_generation: &mut u64) {
+    let mut filter = match filter.try_lock() {
+        Ok(filter) => filter,
+        Err(_) => return,
+    };
+    
+    if filter.generation == *applied_generation {
+        return;
+    }
+    *applied_generation = filter.generation;
+    
+    let expression = filter.requested.clone();
+    match cap.filter(&expression, true) {
+        Ok(()) => {
+            filter.active = expression;
+            filter.error = None;
+        },
         Err(e) => {
-            // Log error but don't stop the app - we'll just run with no packet capture
-            eprintln!("Error opening capture device: {}", e);
-            return Ok(());
+            filter.error = Some(format!("'{}': {}", expression, e));
+        },
+    }
+}
+
+// Capture timestamp of a packet as a duration since the Unix epoch
+fn packet_timestamp(header: &pcap::PacketHeader) -> Timestamp {
+    Duration::new(header.ts.tv_sec.max(0) as u64, 0)
+        + Duration::from_micros(header.ts.tv_usec.max(0) as u64)
+}
+
+// Maximum number of nested tunnels to decapsulate
+const MAX_TUNNEL_DEPTH: u8 = 4;
+
+// Details about the packet being decoded that the inner layers need
+#[derive(Debug, Clone, Copy)]
+struct PacketMeta {
+    wire_len: u32,          // Original length of the whole frame on the wire
+    timestamp: Timestamp,   // When the frame was captured
+    segment: Segment,       // VLAN tags and tunnel seen so far
+    tunnel_depth: u8,       // Number of tunnels already stripped
+}
+
+// Decode a single frame and update the shared statistics
+pub fn process_packet(ctx: &CaptureContext, linktype: Linktype, data: &[u8], wire_len: u32, timestamp: Timestamp) {
+    ctx.clock.advance(timestamp);
+    let meta = PacketMeta {
+        wire_len,
+        timestamp,
+        segment: Segment::default(),
+        tunnel_depth: 0,
+    };
+    
+    // Unsupported link types and truncated headers still count as packets
+    let packet_type = match decode_link(linktype, data) {
+        Some((ethertype, payload)) => process_network(ctx, ethertype, payload, meta),
+        None => PacketType::OTHER,
+    };
+    
+    // The capture thread may block briefly here; the UI only ever uses try_lock
+    if let Ok(mut stats) = ctx.packet_stats.lock() {
+        stats.record(&ctx.interface, packet_type);
+    }
+}
+
+// Handle the network layer, after peeling any VLAN tags
+fn process_network(ctx: &CaptureContext, ethertype: EtherType, payload: &[u8], mut meta: PacketMeta) -> PacketType {
+    let (ethertype, payload) = strip_vlan_tags(ethertype, payload, &mut meta.segment);
+    
+    match ethertype {
+        EtherTypes::Ipv4 => match Ipv4Packet::new(payload) {
+            Some(ipv4) => {
+                let src_ip = IpAddr::V4(ipv4.get_source());
+                let dst_ip = IpAddr::V4(ipv4.get_destination());
+                let protocol = ipv4.get_next_level_protocol();
+                
+                let fragment = Fragment {
+                    id: ipv4.get_identification() as u32,
+                    offset: ipv4.get_fragment_offset() as usize * 8,
+                    more: ipv4.get_flags() & Ipv4Flags::MoreFragments != 0,
+                };
+                if !fragment.is_whole() {
+                    return process_fragment(ctx, src_ip, dst_ip, protocol, fragment, ipv4.payload(), meta);
+                }
+                process_ip_payload(ctx, src_ip, dst_ip, protocol, ipv4.payload(), meta)
+            },
+            None => PacketType::OTHER,
+        },
+        EtherTypes::Ipv6 => match Ipv6Packet::new(payload) {
+            Some(ipv6) => {
+                let src_ip = IpAddr::V6(ipv6.get_source());
+                let dst_ip = IpAddr::V6(ipv6.get_destination());
+                
+                // The Fragment header can follow Hop-by-Hop, Routing and Destination Options
+                let Some((next_header, payload)) = skip_ipv6_extensions(ipv6.get_next_header().0, ipv6.payload()) else {
+                    return PacketType::OTHER;
+                };
+                let next_header = IpNextHeaderProtocol(next_header);
+                
+                if next_header == IpNextHeaderProtocols::Ipv6Frag {
+                    let Some((next_header, fragment, data)) = parse_ipv6_fragment(payload) else {
+                        return PacketType::OTHER;
+                    };
+                    let protocol = IpNextHeaderProtocol(next_header);
+                    if !fragment.is_whole() {
+                        return process_fragment(ctx, src_ip, dst_ip, protocol, fragment, data, meta);
+                    }
+                    return process_ip_payload(ctx, src_ip, dst_ip, protocol, data, meta);
+                }
+                process_ip_payload(ctx, src_ip, dst_ip, next_header, payload, meta)
+            },
+            None => PacketType::OTHER,
+        },
+        EtherTypes::Arp => {
+            if let Some((ip, mac)) = parse_arp(payload) {
+                track_neighbor(ctx, ip, mac, NeighborProtocol::Arp, meta.timestamp);
+            }
+            PacketType::ARP
+        },
+        _ => PacketType::OTHER,
+    }
+}
+
+// Hold a fragment until its datagram is complete, then carry on with the
+// whole payload. Until then fragments only count towards their transport,
+// as their ports are in the first one
+fn process_fragment(
+    ctx: &CaptureContext,
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    protocol: IpNextHeaderProtocol,
+    fragment: Fragment,
+    payload: &[u8],
+    mut meta: PacketMeta,
+) -> PacketType {
+    let key = FragmentKey {
+        interface: ctx.interface.clone(),
+        src: src_ip,
+        dst: dst_ip,
+        protocol: protocol.0,
+        id: fragment.id,
+    };
+    let datagram = match ctx.fragments.lock() {
+        Ok(mut fragments) => fragments.add(key, fragment, payload, meta.wire_len, meta.timestamp),
+        Err(_) => None,
+    };
+    
+    match datagram {
+        Some((payload, wire_len)) => {
+            // The flow is charged for every fragment's bytes
+            meta.wire_len = wire_len;
+            process_ip_payload(ctx, src_ip, dst_ip, protocol, &payload, meta)
+        },
+        None => match protocol {
+            IpNextHeaderProtocols::Tcp => PacketType { basic: BasicProtocolType::TCP, service: None },
+            IpNextHeaderProtocols::Udp => PacketType { basic: BasicProtocolType::UDP, service: None },
+            IpNextHeaderProtocols::Icmp | IpNextHeaderProtocols::Icmpv6 => PacketType::ICMP,
+            _ => PacketType::OTHER,
+        },
+    }
+}
+
+// Decapsulate tunnels so the inner flow is what gets counted, otherwise
+// handle the transport layer
+fn process_ip_payload(
+    ctx: &CaptureContext,
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    protocol: IpNextHeaderProtocol,
+    payload: &[u8],
+    mut meta: PacketMeta,
+) -> PacketType {
+    if ctx.decapsulate && meta.tunnel_depth < MAX_TUNNEL_DEPTH {
+        if let Some((tunnel, ethertype, inner)) = decode_ip_tunnel(protocol, payload) {
+            meta.segment.tunnel = Some(tunnel);
+            meta.tunnel_depth += 1;
+            return process_network(ctx, ethertype, inner, meta);
         }
+    }
+    
+    process_transport(ctx, src_ip, dst_ip, protocol, payload, meta)
+}
+
+// Classify the transport layer and record the flow and its location
+fn process_transport(
+    ctx: &CaptureContext,
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    protocol: IpNextHeaderProtocol,
+    payload: &[u8],
+    meta: PacketMeta,
+) -> PacketType {
+    let packet_type = match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            if let Some(tcp) = TcpPacket::new(payload) {
+                // Classify TCP traffic by port, then by payload
+                let src_port = tcp.get_source();
+                let dst_port = tcp.get_destination();
+                let port_type = ctx.services.classify(BasicProtocolType::TCP, src_port, dst_port);
+                
+                let packet_type = track_connection(ctx, meta.segment, FlowPacket {
+                    src_ip,
+                    dst_ip,
+                    src_port,
+                    dst_port,
+                    protocol: port_type,
+                    wire_len: meta.wire_len,
+                    timestamp: meta.timestamp,
+                    icmp: None,
+                    tcp: Some(TcpSegment {
+                        flags: tcp.get_flags(),
+                        seq: tcp.get_sequence(),
+                        ack: tcp.get_acknowledgement(),
+                        window: tcp.get_window(),
+                        payload_len: tcp.payload().len() as u32,
+                    }),
+                }, tcp.payload());
+                
+                // DNS over TCP, when a message starts at the segment
+                if (src_port == 53 || dst_port == 53) && !tcp.payload().is_empty() {
+                    if let Some(message) = DnsMessage::parse_tcp(tcp.payload()) {
+                        track_dns(ctx, &message, (src_ip, src_port), (dst_ip, dst_port), meta.timestamp);
+                    }
+                }
+                
+                packet_type
+            } else {
+                PacketType::OTHER
+            }
+        },
+        IpNextHeaderProtocols::Udp => {
+            if let Some(udp) = UdpPacket::new(payload) {
+                // Classify UDP traffic by port, then by payload
+                let src_port = udp.get_source();
+                let dst_port = udp.get_destination();
+                let port_type = ctx.services.classify(BasicProtocolType::UDP, src_port, dst_port);
+                
+                let packet_type = track_connection(ctx, meta.segment, FlowPacket {
+                    src_ip,
+                    dst_ip,
+                    src_port,
+                    dst_port,
+                    protocol: port_type,
+                    wire_len: meta.wire_len,
+                    timestamp: meta.timestamp,
+                    tcp: None,
+                    icmp: None,
+                }, udp.payload());
+                
+                // DNS, plus mDNS responses for the names of local hosts
+                if src_port == 53 || dst_port == 53 || src_port == 5353 {
+                    if let Some(message) = DnsMessage::parse(udp.payload()) {
+                        track_dns(ctx, &message, (src_ip, src_port), (dst_ip, dst_port), meta.timestamp);
+                    }
+                }
+                
+                packet_type
+            } else {
+                PacketType::OTHER
+            }
+        },
+        IpNextHeaderProtocols::Icmp => {
+            if let Some(message) = IcmpMessage::parse(payload, false) {
+                track_icmp(ctx, meta, src_ip, dst_ip, &message);
+            }
+            PacketType::ICMP
+        },
+        IpNextHeaderProtocols::Icmpv6 => {
+            if let Some(message) = IcmpMessage::parse(payload, true) {
+                track_icmp(ctx, meta, src_ip, dst_ip, &message);
+            }
+            // Neighbor Discovery is IPv6's ARP
+            if let Some((ip, mac)) = parse_ndp(src_ip, payload) {
+                track_neighbor(ctx, ip, mac, NeighborProtocol::Ndp, meta.timestamp);
+            }
+            PacketType::ICMP
+        },
+        _ => PacketType::OTHER,
     };
     
-    // Start capture thread
-    thread::spawn(move || {
-        // Track errors so we don't spam the console
-        let mut consecutive_errors = 0;
+    track_location(ctx, src_ip, dst_ip);
+    packet_type
+}
+
+// Record the packet on its flow. Returns the packet's type, refined by what
+// the flow's payload revealed
+fn track_connection(ctx: &CaptureContext, segment: Segment, packet: FlowPacket, payload: &[u8]) -> PacketType {
+    let mut http_event: Option<(HttpEvent, bool)> = None;
+    let mut packet_type = packet.protocol;
+    
+    if let Ok(mut conns) = ctx.connections.lock() {
+        let (stats, from_client) = conns.record(&ctx.interface, segment, &packet);
         
-        while running.load(Ordering::Relaxed) {
-            match cap.next_packet() {
-                Ok(packet) => {
-                    // Reset error counter on success
-                    consecutive_errors = 0;
-                    
-                    if let Some(ethernet) = EthernetPacket::new(packet.data) {
-                        let mut packet_type = PacketType::Other;
-                        
-                        match ethernet.get_ethertype() {
-                            EtherTypes::Ipv4 => {
-                                if let Some(ipv4) = Ipv4Packet::new(ethernet.payload()) {
-                                    match ipv4.get_next_level_protocol() {
-                                        IpNextHeaderProtocols::Tcp => {
-                                            if let Some(tcp) = TcpPacket::new(ipv4.payload()) {
-                                                // Classify TCP traffic by port
-                                                let src_port = tcp.get_source();
-                                                let dst_port = tcp.get_destination();
-                                                let src_ip = IpAddr::V4(ipv4.get_source());
-                                                let dst_ip = IpAddr::V4(ipv4.get_destination());
-                                                
-                                                // Check for common services on either source or destination port
-                                                if src_port == 80 || dst_port == 80 {
-                                                    packet_type = PacketType::TCP_HTTP;
-                                                } else if src_port == 443 || dst_port == 443 {
-                                                    packet_type = PacketType::TCP_HTTPS;
-                                                } else if src_port == 22 || dst_port == 22 {
-                                                    packet_type = PacketType::TCP_SSH;
-                                                } else if src_port == 53 || dst_port == 53 {
-                                                    packet_type = PacketType::TCP_DNS;
-                                                } else {
-                                                    packet_type = PacketType::TCP_Other;
-                                                }
-                                                
-                                                // Track this connection
-                                                if let Ok(mut conns) = connections.try_lock() {
-                                                    let now = Instant::now();
-                                                    let packet_len = packet.header.len + packet.header.caplen;
-                                                    
-                                                    // Create connection identifier
-                                                    let conn_id = ConnectionId {
-                                                        src_ip,
-                                                        dst_ip,
-                                                        src_port,
-                                                        dst_port,
-                                                        protocol: packet_type,
-                                                    };
-                                                    
-                                                    // Update or create connection stats
-                                                    conns.entry(conn_id)
-                                                        .and_modify(|stats| {
-                                                            stats.last_seen = now;
-                                                            stats.packet_count += 1;
-                                                            stats.byte_count += packet_len as u64;
-                                                        })
-                                                        .or_insert_with(|| ConnectionStats {
-                                                            first_seen: now,
-                                                            last_seen: now,
-                                                            packet_count: 1,
-                                                            byte_count: packet_len as u64,
-                                                        });
-                                                }
-                                                
-                                                // Track geographic location
-                                                if let Ok(mut geo) = geo_stats.try_lock() {
-                                                    // For outbound connections, track the destination
-                                                    let target_ip = if is_local_ip(src_ip, &local_networks) {
-                                                        dst_ip
-                                                    } else {
-                                                        src_ip
-                                                    };
-                                                    
-                                                    // Try to get location
-                                                    if let Some(location) = lookup_ip_location(target_ip) {
-                                                        // Update country stats
-                                                        geo.locations.entry(location.country.clone())
-                                                            .and_modify(|(_, count)| {
-                                                                *count += 1;
-                                                            })
-                                                            .or_insert_with(|| (location, 1));
-                                                            
-                                                        // Update top country
-                                                        geo.update_top_country();
-                                                    }
-                                                }
-                                            }
-                                        },
-                                        IpNextHeaderProtocols::Udp => {
-                                            if let Some(udp) = UdpPacket::new(ipv4.payload()) {
-                                                // Classify UDP traffic by port
-                                                let src_port = udp.get_source();
-                                                let dst_port = udp.get_destination();
-                                                let src_ip = IpAddr::V4(ipv4.get_source());
-                                                let dst_ip = IpAddr::V4(ipv4.get_destination());
-                                                
-                                                // Check for common services
-                                                if src_port == 53 || dst_port == 53 {
-                                                    packet_type = PacketType::UDP_DNS;
-                                                } else if src_port == 67 || dst_port == 67 || 
-                                                          src_port == 68 || dst_port == 68 {
-                                                    packet_type = PacketType::UDP_DHCP;
-                                                } else {
-                                                    packet_type = PacketType::UDP_Other;
-                                                }
-                                                
-                                                // Track this connection
-                                                if let Ok(mut conns) = connections.try_lock() {
-                                                    let now = Instant::now();
-                                                    let packet_len = packet.header.len + packet.header.caplen;
-                                                    
-                                                    // Create connection identifier
-                                                    let conn_id = ConnectionId {
-                                                        src_ip,
-                                                        dst_ip,
-                                                        src_port,
-                                                        dst_port,
-                                                        protocol: packet_type,
-                                                    };
-                                                    
-                                                    // Update or create connection stats
-                                                    conns.entry(conn_id)
-                                                        .and_modify(|stats| {
-                                                            stats.last_seen = now;
-                                                            stats.packet_count += 1;
-                                                            stats.byte_count += packet_len as u64;
-                                                        })
-                                                        .or_insert_with(|| ConnectionStats {
-                                                            first_seen: now,
-                                                            last_seen: now,
-                                                            packet_count: 1,
-                                                            byte_count: packet_len as u64,
-                                                        });
-                                                }
-                                            }
-                                        },
-                                        IpNextHeaderProtocols::Icmp => {
-                                            packet_type = PacketType::ICMP;
-                                        },
-                                        _ => {},
-                                    }
-                                }
-                            },
-                            EtherTypes::Ipv6 => {
-                                if let Some(ipv6) = Ipv6Packet::new(ethernet.payload()) {
-                                    match ipv6.get_next_header() {
-                                        IpNextHeaderProtocols::Tcp => {
-                                            if let Some(tcp) = TcpPacket::new(ipv6.payload()) {
-                                                // Classify TCP traffic by port
-                                                let src_port = tcp.get_source();
-                                                let dst_port = tcp.get_destination();
-                                                
-                                                // Check for common services on either source or destination port
-                                                if src_port == 80 || dst_port == 80 {
-                                                    packet_type = PacketType::TCP_HTTP;
-                                                } else if src_port == 443 || dst_port == 443 {
-                                                    packet_type = PacketType::TCP_HTTPS;
-                                                } else if src_port == 22 || dst_port == 22 {
-                                                    packet_type = PacketType::TCP_SSH;
-                                                } else if src_port == 53 || dst_port == 53 {
-                                                    packet_type = PacketType::TCP_DNS;
-                                                } else {
-                                                    packet_type = PacketType::TCP_Other;
-                                                }
-                                            }
-                                        },
-                                        IpNextHeaderProtocols::Udp => {
-                                            if let Some(udp) = UdpPacket::new(ipv6.payload()) {
-                                                // Classify UDP traffic by port
-                                                let src_port = udp.get_source();
-                                                let dst_port = udp.get_destination();
-                                                
-                                                // Check for common services
-                                                if src_port == 53 || dst_port == 53 {
-                                                    packet_type = PacketType::UDP_DNS;
-                                                } else if src_port == 67 || dst_port == 67 || 
-                                                          src_port == 68 || dst_port == 68 {
-                                                    packet_type = PacketType::UDP_DHCP;
-                                                } else {
-                                                    packet_type = PacketType::UDP_Other;
-                                                }
-                                            }
-                                        },
-                                        IpNextHeaderProtocols::Icmpv6 => {
-                                            packet_type = PacketType::ICMP;
-                                        },
-                                        _ => {},
-                                    }
-                                }
-                            },
-                            _ => {},
-                        }
-                        
-                        // Update packet counts - use try_lock to avoid blocking UI
-                        if let Ok(mut stats) = packet_stats.try_lock() {
-                            *stats.counts.entry(packet_type).or_insert(0) += 1;
-                        }
-                    }
-                },
-                Err(_) => {
-                    consecutive_errors += 1;
-                    
-                    // Only log every 100th error to avoid flooding
-                    if consecutive_errors == 1 || consecutive_errors % 100 == 0 {
-                        // Don't use println in a TUI app - errors will mess up the display
-                        // Just sleep and continue
+        stats.inspect_payload(packet.protocol.basic, payload);
+        packet_type = stats.protocol(packet.protocol);
+        
+        lookup_asn(ctx, stats, &packet);
+        
+        if let Some(tcp) = &packet.tcp {
+            if !payload.is_empty() {
+                // The client's first bytes tell whether this is TLS, and to where
+                if from_client {
+                    if let Some(info) = stats.hello.feed(tcp.seq, payload) {
+                        stats.tls = Some(Arc::new(info));
                     }
-                    
-                    // Sleep to avoid spinning CPU on repeated errors
-                    thread::sleep(Duration::from_millis(10));
                 }
+                
+                // ...or plain HTTP, on whatever port
+                http_event = stats.http.feed(from_client, payload, packet.timestamp).map(|event| (event, from_client));
+            }
+        } else if !payload.is_empty() {
+            // QUIC carries its ClientHello in Initial packets anyone can decrypt
+            if let Some(info) = stats.quic.feed(from_client, payload) {
+                stats.tls = Some(Arc::new(info));
             }
         }
-    });
+    }
     
-    Ok(())
-}
\ No newline at end of file
+    // Recorded once the flow table is released
+    if let Some((event, from_client)) = http_event {
+        let (client, server) = if from_client {
+            ((packet.src_ip, packet.src_port), (packet.dst_ip, packet.dst_port))
+        } else {
+            ((packet.dst_ip, packet.dst_port), (packet.src_ip, packet.src_port))
+        };
+        if let Ok(mut http) = ctx.http_stats.lock() {
+            http.record(event, client, server, packet.timestamp);
+        }
+    }
+    
+    packet_type
+}
+
+// Count the message by type. Echoes and errors also go on a pseudo-flow
+// between the two hosts, where replies are paired with their requests
+fn track_icmp(ctx: &CaptureContext, meta: PacketMeta, src_ip: IpAddr, dst_ip: IpAddr, message: &IcmpMessage) {
+    let mut rtt = None;
+    
+    if message.has_flow() {
+        // The echo identifier stands in for ports, so separate pings get separate flows
+        let id = message.echo.map_or(0, |(id, _)| id);
+        let packet = FlowPacket {
+            src_ip,
+            dst_ip,
+            src_port: id,
+            dst_port: id,
+            protocol: PacketType::ICMP,
+            wire_len: meta.wire_len,
+            timestamp: meta.timestamp,
+            tcp: None,
+            icmp: Some(message.icmp_type),
+        };
+        
+        if let Ok(mut conns) = ctx.connections.lock() {
+            let (stats, _) = conns.record(&ctx.interface, meta.segment, &packet);
+            lookup_asn(ctx, stats, &packet);
+            let (echo_rtt, timed_out) = stats.icmp.get_or_insert_with(IcmpSession::default).record(message, meta.timestamp);
+            rtt = echo_rtt;
+            conns.unanswered_echoes += timed_out;
+        }
+    }
+    
+    if let Ok(mut icmp) = ctx.icmp_stats.lock() {
+        icmp.record(message, rtt);
+    }
+}
+
+// Look up the remote network once, when the flow is new
+fn lookup_asn(ctx: &CaptureContext, stats: &mut ConnectionStats, packet: &FlowPacket) {
+    if let Some(asn_db) = &ctx.asn_db {
+        if stats.packet_count == 1 {
+            stats.asn = remote_ip(ctx, packet.src_ip, packet.dst_ip)
+                .and_then(|ip| asn_db.lookup(ip));
+        }
+    }
+}
+
+fn track_neighbor(ctx: &CaptureContext, ip: IpAddr, mac: MacAddr, protocol: NeighborProtocol, timestamp: Timestamp) {
+    if let Ok(mut neighbors) = ctx.neighbors.lock() {
+        neighbors.record(&ctx.interface, ip, mac, protocol, timestamp);
+    }
+}
+
+fn track_dns(ctx: &CaptureContext, message: &DnsMessage, src: (IpAddr, u16), dst: (IpAddr, u16), timestamp: Timestamp) {
+    // Responses teach us the names behind addresses
+    if message.response {
+        if let Ok(mut names) = ctx.host_names.lock() {
+            names.record_answers(message);
+        }
+    }
+    
+    if src.1 == 53 || dst.1 == 53 {
+        if let Ok(mut dns) = ctx.dns_stats.lock() {
+            dns.record(message, src, dst, timestamp);
+        }
+    }
+}
+
+// The endpoint worth looking up: the destination of outbound traffic, the
+// source of inbound. None for addresses no database can place
+fn remote_ip(ctx: &CaptureContext, src_ip: IpAddr, dst_ip: IpAddr) -> Option<IpAddr> {
+    let target_ip = if is_local_ip(src_ip, &ctx.local_networks) {
+        dst_ip
+    } else {
+        src_ip
+    };
+    
+    // Local, multicast and unspecified addresses have no location
+    if crate::utils::is_loopback_ip(target_ip) || crate::utils::is_private_ip(target_ip) ||
+        target_ip.is_multicast() || target_ip.is_unspecified() {
+        return None;
+    }
+    
+    Some(target_ip)
+}
+
+fn track_location(ctx: &CaptureContext, src_ip: IpAddr, dst_ip: IpAddr) {
+    if ctx.geoip.is_none() && ctx.asn_db.is_none() {
+        return;
+    }
+    let Some(target_ip) = remote_ip(ctx, src_ip, dst_ip) else { return };
+    
+    let location = ctx.geoip.as_ref().and_then(|geoip| geoip.lookup(target_ip));
+    let network = ctx.asn_db.as_ref().and_then(|asn_db| asn_db.lookup(target_ip));
+    
+    if let Ok(mut geo) = ctx.geo_stats.lock() {
+        if let Some(location) = &location {
+            geo.record_location(location);
+        }
+        if let Some(network) = &network {
+            geo.record_network(network, target_ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::network::oui::OuiTable;
+    use crate::network::test_util::{at, flow_table, interface, ip, CLIENT};
+    
+    fn context(asn_db: Option<AsnDatabase>) -> CaptureContext {
+        CaptureContext {
+            interface: interface(),
+            packet_stats: Arc::new(Mutex::new(PacketStats::new(10, &["eth0".to_string()]))),
+            connections: Arc::new(Mutex::new(flow_table())),
+            geo_stats: Arc::new(Mutex::new(GeoStats::new())),
+            geoip: None,
+            asn_db: asn_db.map(Arc::new),
+            host_names: Arc::new(Mutex::new(HostNames::new())),
+            dns_stats: Arc::new(Mutex::new(DnsStats::new())),
+            http_stats: Arc::new(Mutex::new(HttpStats::new())),
+            icmp_stats: Arc::new(Mutex::new(IcmpStats::new())),
+            fragments: Arc::new(Mutex::new(FragmentTable::new())),
+            neighbors: Arc::new(Mutex::new(NeighborTable::new(OuiTable::load(None).unwrap()))),
+            recorder: None,
+            services: Arc::new(ServiceTable::load(None).unwrap()),
+            local_networks: vec![IpRange::new([10, 0, 0, 0], 8)],
+            running: Arc::new(AtomicBool::new(true)),
+            capture_finished: Arc::new(AtomicBool::new(false)),
+            clock: Arc::new(CaptureClock::default()),
+            filter: Arc::new(Mutex::new(CaptureFilter::new(None))),
+            decapsulate: true,
+        }
+    }
+    
+    // IPv4 packet without options; the checksum isn't checked
+    fn ipv4(protocol: u8, src: [u8; 4], dst: [u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0];
+        packet.extend((20 + payload.len() as u16).to_be_bytes());
+        packet.extend([0, 0, 0, 0, 64, protocol, 0, 0]);
+        packet.extend(src);
+        packet.extend(dst);
+        packet.extend_from_slice(payload);
+        packet
+    }
+    
+    #[test]
+    fn udp_and_icmp_count_towards_networks() {
+        let path = std::env::temp_dir().join(format!("rnet-asn-{}.csv", std::process::id()));
+        fs::write(&path, "8.8.8.0/24,15169,Google LLC\n").unwrap();
+        let asn_db = AsnDatabase::open(std::slice::from_ref(&path));
+        let _ = fs::remove_file(&path);
+        let ctx = context(Some(asn_db.unwrap()));
+        
+        let IpAddr::V4(client) = CLIENT else { unreachable!() };
+        let dns_query = [0xc3, 0x50, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00];
+        let udp = ipv4(17, client.octets(), [8, 8, 8, 8], &dns_query);
+        process_packet(&ctx, Linktype::RAW, &udp, udp.len() as u32, at(0));
+        let echo = ipv4(1, [8, 8, 8, 8], client.octets(), &[0, 0, 0, 0, 0, 1, 0, 1]);
+        process_packet(&ctx, Linktype::RAW, &echo, echo.len() as u32, at(10));
+        
+        let geo = ctx.geo_stats.lock().unwrap();
+        let google = &geo.networks[&15169];
+        assert_eq!(google.packets, 2);
+        assert_eq!(google.addresses.iter().copied().collect::<Vec<_>>(), vec![ip("8.8.8.8")]);
+    }
+}
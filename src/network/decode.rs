@@ -0,0 +1,497 @@
+use pcap::Linktype;
+use pnet::packet::{
+    ethernet::{EtherType, EtherTypes, EthernetPacket},
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    Packet,
+};
+
+use super::types::{Segment, Tunnel};
+
+// DLT_RAW as reported by live captures (12 on most platforms, 14 on OpenBSD);
+// capture files use LINKTYPE_RAW (101) instead
+const DLT_RAW: i32 = 12;
+const DLT_RAW_OPENBSD: i32 = 14;
+
+// Address family values used by BSD loopback (NULL/LOOP) headers
+const AF_INET: u32 = 2;
+const AF_INET6_VALUES: [u32; 4] = [10, 24, 28, 30]; // Linux, Net/OpenBSD, FreeBSD, macOS
+
+// PPP protocol numbers for IP
+const PPP_IPV4: u16 = 0x0021;
+const PPP_IPV6: u16 = 0x0057;
+
+// VLAN tag EtherTypes: 802.1Q, 802.1ad (QinQ) and the legacy QinQ value
+const ETHERTYPE_VLAN: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+// Payload types carried by GRE besides plain IP
+const GRE_TRANSPARENT_ETHERNET: u16 = 0x6558;
+const GRE_ERSPAN_TYPE2: u16 = 0x88be;
+const GRE_ERSPAN_TYPE3: u16 = 0x22eb;
+
+// IANA-assigned VXLAN UDP port
+const VXLAN_PORT: u16 = 4789;
+
+// Strip the link-layer header of a frame, returning the EtherType of the
+// network-layer payload. None means the link type or frame isn't supported
+pub fn decode_link(linktype: Linktype, data: &[u8]) -> Option<(EtherType, &[u8])> {
+    match linktype {
+        Linktype::ETHERNET => decode_ethernet(data),
+        Linktype::LINUX_SLL => decode_linux_sll(data),
+        Linktype::LINUX_SLL2 => decode_linux_sll2(data),
+        Linktype::NULL => decode_null(data, false),
+        Linktype::LOOP => decode_null(data, true),
+        Linktype::RAW | Linktype::IPV4 | Linktype::IPV6 => decode_raw_ip(data),
+        Linktype(DLT_RAW) | Linktype(DLT_RAW_OPENBSD) => decode_raw_ip(data),
+        Linktype::PPP => decode_ppp(data),
+        Linktype::IEEE802_11_RADIOTAP => decode_radiotap(data),
+        _ => None,
+    }
+}
+
+fn decode_ethernet(data: &[u8]) -> Option<(EtherType, &[u8])> {
+    let ethernet = EthernetPacket::new(data)?;
+    let header_len = data.len() - ethernet.payload().len();
+    Some((ethernet.get_ethertype(), &data[header_len..]))
+}
+
+// Linux "cooked" capture v1, used for the "any" device: 16-byte header
+// with the protocol in the last two bytes
+fn decode_linux_sll(data: &[u8]) -> Option<(EtherType, &[u8])> {
+    if data.len() < 16 {
+        return None;
+    }
+    let protocol = u16::from_be_bytes([data[14], data[15]]);
+    Some((EtherType(protocol), &data[16..]))
+}
+
+// Linux cooked capture v2: 20-byte header with the protocol first
+fn decode_linux_sll2(data: &[u8]) -> Option<(EtherType, &[u8])> {
+    if data.len() < 20 {
+        return None;
+    }
+    let protocol = u16::from_be_bytes([data[0], data[1]]);
+    Some((EtherType(protocol), &data[20..]))
+}
+
+// BSD loopback: 4-byte address family, in host byte order of the capturing
+// machine for NULL and network byte order for LOOP
+fn decode_null(data: &[u8], network_order: bool) -> Option<(EtherType, &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+    let bytes = [data[0], data[1], data[2], data[3]];
+    
+    // NULL files may come from a machine of either endianness, so accept
+    // whichever reading gives a known family
+    let family = if network_order {
+        u32::from_be_bytes(bytes)
+    } else {
+        let little = u32::from_le_bytes(bytes);
+        if little == AF_INET || AF_INET6_VALUES.contains(&little) {
+            little
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    };
+    
+    let ethertype = if family == AF_INET {
+        EtherTypes::Ipv4
+    } else if AF_INET6_VALUES.contains(&family) {
+        EtherTypes::Ipv6
+    } else {
+        return None;
+    };
+    
+    Some((ethertype, &data[4..]))
+}
+
+// Raw IP (tun devices, WireGuard): no link header, the version nibble tells
+// IPv4 from IPv6
+fn decode_raw_ip(data: &[u8]) -> Option<(EtherType, &[u8])> {
+    match data.first()? >> 4 {
+        4 => Some((EtherTypes::Ipv4, data)),
+        6 => Some((EtherTypes::Ipv6, data)),
+        _ => None,
+    }
+}
+
+// PPP: optional address/control bytes (0xff 0x03), then a 1 or 2 byte protocol
+fn decode_ppp(data: &[u8]) -> Option<(EtherType, &[u8])> {
+    let data = match data {
+        [0xff, 0x03, rest @ ..] => rest,
+        _ => data,
+    };
+    
+    // Protocol field compression leaves a single odd byte
+    let (protocol, payload) = match data {
+        [first, rest @ ..] if first & 0x01 == 1 => (*first as u16, rest),
+        [high, low, rest @ ..] => (u16::from_be_bytes([*high, *low]), rest),
+        _ => return None,
+    };
+    
+    match protocol {
+        PPP_IPV4 => Some((EtherTypes::Ipv4, payload)),
+        PPP_IPV6 => Some((EtherTypes::Ipv6, payload)),
+        _ => None,
+    }
+}
+
+// 802.11 with a radiotap header (monitor mode). Only unencrypted data frames
+// carry IP we can read; they are followed by an LLC/SNAP header
+fn decode_radiotap(data: &[u8]) -> Option<(EtherType, &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+    let radiotap_len = u16::from_le_bytes([data[2], data[3]]) as usize;
+    let frame = data.get(radiotap_len..)?;
+    
+    if frame.len() < 24 {
+        return None;
+    }
+    let frame_type = (frame[0] >> 2) & 0x03;
+    let subtype = frame[0] >> 4;
+    let flags = frame[1];
+    
+    // Data frames only, skipping the null-data subtypes and protected frames
+    let is_data = frame_type == 2;
+    let has_payload = subtype & 0x04 == 0;
+    let protected = flags & 0x40 != 0;
+    if !is_data || !has_payload || protected {
+        return None;
+    }
+    
+    let mut header_len = 24;
+    // Four addresses when the frame goes between two access points
+    if flags & 0x03 == 0x03 {
+        header_len += 6;
+    }
+    // QoS data frames add a QoS control field, plus HT control when ordered
+    if subtype & 0x08 != 0 {
+        header_len += 2;
+        if flags & 0x80 != 0 {
+            header_len += 4;
+        }
+    }
+    
+    // LLC/SNAP: AA AA 03 00 00 00 followed by the EtherType
+    let llc = frame.get(header_len..header_len + 8)?;
+    if llc[..6] != [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00] {
+        return None;
+    }
+    let protocol = u16::from_be_bytes([llc[6], llc[7]]);
+    
+    Some((EtherType(protocol), &frame[header_len + 8..]))
+}
+
+// Peel 802.1Q / QinQ tags, recording the outer and inner VLAN IDs
+pub fn strip_vlan_tags<'a>(mut ethertype: EtherType, mut payload: &'a [u8], segment: &mut Segment) -> (EtherType, &'a [u8]) {
+    while ETHERTYPE_VLAN.contains(&ethertype.0) && payload.len() >= 4 {
+        let vlan_id = u16::from_be_bytes([payload[0], payload[1]]) & 0x0fff;
+        
+        // Only the first two tags are kept, including across tunnels
+        if segment.vlan.is_none() {
+            segment.vlan = Some(vlan_id);
+        } else if segment.inner_vlan.is_none() {
+            segment.inner_vlan = Some(vlan_id);
+        }
+        
+        ethertype = EtherType(u16::from_be_bytes([payload[2], payload[3]]));
+        payload = &payload[4..];
+    }
+    (ethertype, payload)
+}
+
+// Recognize GRE, VXLAN and IP-in-IP encapsulations inside an IP payload,
+// returning the tunnel and the inner frame's EtherType and payload
+pub fn decode_ip_tunnel(protocol: IpNextHeaderProtocol, payload: &[u8]) -> Option<(Tunnel, EtherType, &[u8])> {
+    match protocol {
+        IpNextHeaderProtocols::Ipv4 => Some((Tunnel::IpInIp, EtherTypes::Ipv4, payload)),
+        IpNextHeaderProtocols::Ipv6 => Some((Tunnel::IpInIp, EtherTypes::Ipv6, payload)),
+        IpNextHeaderProtocols::Gre => decode_gre(payload),
+        IpNextHeaderProtocols::Udp => decode_vxlan(payload),
+        _ => None,
+    }
+}
+
+// GRE (RFC 2784/2890): flags and version, protocol type, then optional
+// checksum, key and sequence number fields
+fn decode_gre(data: &[u8]) -> Option<(Tunnel, EtherType, &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+    let flags = u16::from_be_bytes([data[0], data[1]]);
+    let protocol = u16::from_be_bytes([data[2], data[3]]);
+    
+    // Version 1 is PPTP's enhanced GRE, which carries PPP rather than IP
+    if flags & 0x0007 != 0 {
+        return None;
+    }
+    
+    let mut offset = 4;
+    if flags & 0x8000 != 0 {
+        offset += 4; // Checksum and reserved
+    }
+    let key = if flags & 0x2000 != 0 {
+        let key = data.get(offset..offset + 4)?;
+        offset += 4;
+        Some(u32::from_be_bytes([key[0], key[1], key[2], key[3]]))
+    } else {
+        None
+    };
+    if flags & 0x1000 != 0 {
+        offset += 4; // Sequence number
+    }
+    let inner = data.get(offset..)?;
+    let tunnel = Tunnel::Gre(key);
+    
+    match protocol {
+        GRE_TRANSPARENT_ETHERNET => {
+            let (ethertype, payload) = decode_ethernet(inner)?;
+            Some((tunnel, ethertype, payload))
+        },
+        GRE_ERSPAN_TYPE2 => {
+            // 8-byte ERSPAN header in front of the mirrored Ethernet frame
+            let (ethertype, payload) = decode_ethernet(inner.get(8..)?)?;
+            Some((tunnel, ethertype, payload))
+        },
+        GRE_ERSPAN_TYPE3 => {
+            // 12-byte header, plus an 8-byte platform subheader when the O bit is set
+            let header_len = if inner.get(11)? & 0x01 != 0 { 20 } else { 12 };
+            let (ethertype, payload) = decode_ethernet(inner.get(header_len..)?)?;
+            Some((tunnel, ethertype, payload))
+        },
+        protocol if protocol == EtherTypes::Ipv4.0 || protocol == EtherTypes::Ipv6.0 => {
+            Some((tunnel, EtherType(protocol), inner))
+        },
+        // Anything else (PPP, WCCP, keepalives) stays part of the outer flow
+        _ => None,
+    }
+}
+
+// VXLAN (RFC 7348): UDP to port 4789 with an 8-byte header holding the VNI,
+// followed by an Ethernet frame
+fn decode_vxlan(udp: &[u8]) -> Option<(Tunnel, EtherType, &[u8])> {
+    if udp.len() < 16 || u16::from_be_bytes([udp[2], udp[3]]) != VXLAN_PORT {
+        return None;
+    }
+    let header = &udp[8..16];
+    
+    // The I flag must be set for the VNI to be valid
+    if header[0] & 0x08 == 0 {
+        return None;
+    }
+    let vni = u32::from_be_bytes([0, header[4], header[5], header[6]]);
+    
+    let (ethertype, payload) = decode_ethernet(&udp[16..])?;
+    Some((Tunnel::Vxlan(vni), ethertype, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Ethernet frame from 00:11:22:33:44:55 to 00:66:77:88:99:aa
+    fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x00, 0x66, 0x77, 0x88, 0x99, 0xaa, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        frame.extend(ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+    
+    fn gre(flags: u16, protocol: u16, fields: &[u8], inner: &[u8]) -> Vec<u8> {
+        let mut packet = flags.to_be_bytes().to_vec();
+        packet.extend(protocol.to_be_bytes());
+        packet.extend_from_slice(fields);
+        packet.extend_from_slice(inner);
+        packet
+    }
+    
+    const IPV4: &[u8] = &[0x45, 0, 0, 20];
+    
+    fn with_header(header: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut frame = header.to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+    
+    #[test]
+    fn linux_cooked_captures() {
+        let mut sll = vec![0; 14];
+        sll.extend([0x86, 0xdd]);
+        assert_eq!(decode_link(Linktype::LINUX_SLL, &with_header(&sll, &[0x60])), Some((EtherTypes::Ipv6, &[0x60][..])));
+        
+        let mut sll2 = vec![0x08, 0x00];
+        sll2.extend([0; 18]);
+        assert_eq!(decode_link(Linktype::LINUX_SLL2, &with_header(&sll2, IPV4)), Some((EtherTypes::Ipv4, IPV4)));
+    }
+    
+    #[test]
+    fn null_family_in_either_byte_order() {
+        for family in [2u32, 10, 24, 28, 30] {
+            let expected = if family == AF_INET { EtherTypes::Ipv4 } else { EtherTypes::Ipv6 };
+            let little = with_header(&family.to_le_bytes(), IPV4);
+            let big = with_header(&family.to_be_bytes(), IPV4);
+            assert_eq!(decode_link(Linktype::NULL, &little), Some((expected, IPV4)), "little-endian {}", family);
+            assert_eq!(decode_link(Linktype::NULL, &big), Some((expected, IPV4)), "big-endian {}", family);
+        }
+        assert_eq!(decode_link(Linktype::NULL, &with_header(&7u32.to_le_bytes(), IPV4)), None);
+        
+        // LOOP is always network byte order
+        assert_eq!(decode_link(Linktype::LOOP, &with_header(&24u32.to_be_bytes(), IPV4)), Some((EtherTypes::Ipv6, IPV4)));
+        assert_eq!(decode_link(Linktype::LOOP, &with_header(&24u32.to_le_bytes(), IPV4)), None);
+    }
+    
+    #[test]
+    fn ppp_protocol_field() {
+        assert_eq!(decode_link(Linktype::PPP, &with_header(&[0xff, 0x03, 0x00, 0x21], IPV4)), Some((EtherTypes::Ipv4, IPV4)));
+        assert_eq!(decode_link(Linktype::PPP, &with_header(&[0x00, 0x57], IPV4)), Some((EtherTypes::Ipv6, IPV4)));
+        
+        // Compressed to one byte, with and without address and control
+        assert_eq!(decode_link(Linktype::PPP, &with_header(&[0x21], IPV4)), Some((EtherTypes::Ipv4, IPV4)));
+        assert_eq!(decode_link(Linktype::PPP, &with_header(&[0xff, 0x03, 0x57], IPV4)), Some((EtherTypes::Ipv6, IPV4)));
+        
+        // LCP isn't IP
+        assert_eq!(decode_link(Linktype::PPP, &with_header(&[0xc0, 0x21], IPV4)), None);
+    }
+    
+    // Radiotap header of `len` bytes followed by an 802.11 data frame
+    fn radiotap(len: u16, frame_control: [u8; 2], extra: usize, llc: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0, 0];
+        frame.extend(len.to_le_bytes());
+        frame.resize(len as usize, 0);
+        frame.extend(frame_control);
+        frame.resize(frame.len() + 22 + extra, 0);
+        frame.extend_from_slice(llc);
+        frame.extend_from_slice(IPV4);
+        frame
+    }
+    
+    const SNAP_IPV4: [u8; 8] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00];
+    
+    #[test]
+    fn radiotap_data_frames() {
+        // The radiotap length is little-endian; 0x0c00 read the other way would run off the end
+        let data = radiotap(12, [0x08, 0x00], 0, &SNAP_IPV4);
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &data), Some((EtherTypes::Ipv4, IPV4)));
+        
+        // QoS data carries two more header bytes
+        let qos = radiotap(8, [0x88, 0x00], 2, &SNAP_IPV4);
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &qos), Some((EtherTypes::Ipv4, IPV4)));
+        
+        // Protected frames and frames without LLC/SNAP are skipped
+        let protected = radiotap(8, [0x08, 0x40], 0, &SNAP_IPV4);
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &protected), None);
+        let no_snap = radiotap(8, [0x08, 0x00], 0, &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0xf8, 0x08, 0x00]);
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &no_snap), None);
+        
+        // Beacons are management frames
+        let beacon = radiotap(8, [0x80, 0x00], 0, &SNAP_IPV4);
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &beacon), None);
+    }
+    
+    #[test]
+    fn truncated_frames() {
+        let cases = [
+            (Linktype::ETHERNET, 13),
+            (Linktype::LINUX_SLL, 15),
+            (Linktype::LINUX_SLL2, 19),
+            (Linktype::NULL, 3),
+            (Linktype::LOOP, 3),
+            (Linktype::RAW, 0),
+            (Linktype::PPP, 1),
+            (Linktype::IEEE802_11_RADIOTAP, 3),
+        ];
+        for (linktype, len) in cases {
+            assert_eq!(decode_link(linktype, &[0x00; 32][..len]), None, "{:?}", linktype);
+        }
+        // Radiotap header claiming more than is there
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &[0, 0, 0xff, 0x00, 0, 0, 0, 0]), None);
+        let cut_llc = radiotap(8, [0x08, 0x00], 0, &SNAP_IPV4[..4]);
+        assert_eq!(decode_link(Linktype::IEEE802_11_RADIOTAP, &cut_llc[..cut_llc.len() - IPV4.len()]), None);
+    }
+    
+    #[test]
+    fn single_vlan_tag() {
+        let mut segment = Segment::default();
+        let tagged = [0x20, 0x64, 0x08, 0x00, 0x45];
+        let (ethertype, payload) = strip_vlan_tags(EtherType(0x8100), &tagged, &mut segment);
+        assert_eq!((ethertype, payload), (EtherTypes::Ipv4, &[0x45][..]));
+        // Priority bits aren't part of the ID
+        assert_eq!((segment.vlan, segment.inner_vlan), (Some(100), None));
+    }
+    
+    #[test]
+    fn qinq_keeps_outer_and_inner_vlan() {
+        let mut segment = Segment::default();
+        let tagged = [0x00, 0x64, 0x81, 0x00, 0x00, 0x14, 0x86, 0xdd, 0x60];
+        let (ethertype, payload) = strip_vlan_tags(EtherType(0x88a8), &tagged, &mut segment);
+        assert_eq!((ethertype, payload), (EtherTypes::Ipv6, &[0x60][..]));
+        assert_eq!((segment.vlan, segment.inner_vlan), (Some(100), Some(20)));
+        assert_eq!(segment.label(), "vlan 100.20");
+    }
+    
+    #[test]
+    fn gre_optional_fields_set_the_header_length() {
+        let packet = gre(0x0000, 0x0800, &[], IPV4);
+        assert_eq!(decode_gre(&packet), Some((Tunnel::Gre(None), EtherTypes::Ipv4, IPV4)));
+        
+        // Checksum, key and sequence number: 12 bytes after the base header
+        let fields = [0xbe, 0xef, 0, 0, 0, 0, 0x01, 0x2c, 0, 0, 0, 9];
+        let packet = gre(0xb000, 0x86dd, &fields, &[0x60]);
+        assert_eq!(decode_gre(&packet), Some((Tunnel::Gre(Some(300)), EtherTypes::Ipv6, &[0x60][..])));
+        
+        // Key flag set but the key cut off
+        assert_eq!(decode_gre(&gre(0x2000, 0x0800, &[0, 0], &[])), None);
+    }
+    
+    #[test]
+    fn gre_without_ip_or_ethernet_is_not_a_tunnel() {
+        // PPTP's enhanced GRE, and PPP or keepalives over plain GRE
+        assert_eq!(decode_gre(&gre(0x3001, 0x880b, &[0; 8], IPV4)), None);
+        assert_eq!(decode_gre(&gre(0x0000, 0x880b, &[], IPV4)), None);
+        assert_eq!(decode_gre(&gre(0x0000, 0x0000, &[], &[])), None);
+    }
+    
+    #[test]
+    fn gre_bridged_ethernet() {
+        let packet = gre(0x0000, GRE_TRANSPARENT_ETHERNET, &[], &ethernet(0x0800, IPV4));
+        assert_eq!(decode_gre(&packet), Some((Tunnel::Gre(None), EtherTypes::Ipv4, IPV4)));
+    }
+    
+    #[test]
+    fn erspan_type2() {
+        // Sequence number, then the 8-byte ERSPAN header
+        let mut inner = vec![0x10, 0x00, 0x00, 0x2a, 0, 0, 0, 0];
+        inner.extend(ethernet(0x0800, IPV4));
+        let packet = gre(0x1000, GRE_ERSPAN_TYPE2, &[0, 0, 0, 1], &inner);
+        assert_eq!(decode_gre(&packet), Some((Tunnel::Gre(None), EtherTypes::Ipv4, IPV4)));
+    }
+    
+    #[test]
+    fn erspan_type3_with_platform_subheader() {
+        let mut inner = vec![0x20, 0x00, 0x00, 0x2a, 0, 0, 0, 0, 0, 0, 0, 0x01];
+        inner.extend([0; 8]);
+        inner.extend(ethernet(0x86dd, &[0x60]));
+        let packet = gre(0x1000, GRE_ERSPAN_TYPE3, &[0, 0, 0, 1], &inner);
+        assert_eq!(decode_gre(&packet), Some((Tunnel::Gre(None), EtherTypes::Ipv6, &[0x60][..])));
+    }
+    
+    #[test]
+    fn vxlan_needs_the_i_flag() {
+        let mut udp = vec![0xc0, 0x00, 0x12, 0xb5, 0, 0, 0, 0];
+        udp.extend([0x08, 0, 0, 0, 0x00, 0x13, 0x88, 0]);
+        udp.extend(ethernet(0x0800, IPV4));
+        assert_eq!(
+            decode_ip_tunnel(IpNextHeaderProtocols::Udp, &udp),
+            Some((Tunnel::Vxlan(5000), EtherTypes::Ipv4, IPV4)),
+        );
+        
+        udp[8] = 0x00;
+        assert_eq!(decode_ip_tunnel(IpNextHeaderProtocols::Udp, &udp), None);
+        
+        // Other ports aren't VXLAN at all
+        udp[8] = 0x08;
+        udp[3] = 0x35;
+        assert_eq!(decode_ip_tunnel(IpNextHeaderProtocols::Udp, &udp), None);
+    }
+}
@@ -0,0 +1,302 @@
+use super::dns::DnsMessage;
+use super::http::is_request_start;
+use super::quic::long_header_version;
+use super::types::{BasicProtocolType, ConnectionStats, Detection, QUIC_SERVICE};
+
+// Payload packets looked at per flow before giving up on a signature
+const MAX_INSPECTED: u8 = 4;
+
+const BITTORRENT_HANDSHAKE: &[u8] = b"\x13BitTorrent protocol";
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// PostgreSQL startup codes: protocol 3.0, SSLRequest and GSSENCRequest
+const POSTGRES_STARTUP: u32 = 0x0003_0000;
+const POSTGRES_SSL_REQUEST: u32 = 80877103;
+const POSTGRES_GSSENC_REQUEST: u32 = 80877104;
+
+impl ConnectionStats {
+    // Look for a known signature in the first few payloads of the flow, from
+    // either side, as servers like MySQL and SSH speak first
+    pub fn inspect_payload(&mut self, transport: BasicProtocolType, payload: &[u8]) {
+        if self.detection.is_some() || self.inspected >= MAX_INSPECTED || payload.is_empty() {
+            return;
+        }
+        self.inspected += 1;
+        self.detection = match transport {
+            BasicProtocolType::TCP => detect_tcp(payload),
+            BasicProtocolType::UDP => detect_udp(payload),
+            _ => None,
+        };
+    }
+}
+
+fn detection(service: &'static str, method: &'static str, confident: bool) -> Option<Detection> {
+    Some(Detection { service, method, confident })
+}
+
+fn detect_tcp(payload: &[u8]) -> Option<Detection> {
+    // TLS names no application, so the port still says which one it is
+    if is_tls_handshake(payload) {
+        return detection("tls", "TLS handshake", false);
+    }
+    if is_request_start(payload) {
+        return detection("http", "HTTP request line", true);
+    }
+    if payload.starts_with(b"HTTP/1.") {
+        return detection("http", "HTTP status line", true);
+    }
+    if payload.starts_with(HTTP2_PREFACE) {
+        return detection("http", "HTTP/2 preface", true);
+    }
+    if payload.starts_with(b"SSH-2.0-") || payload.starts_with(b"SSH-1.") {
+        return detection("ssh", "SSH banner", true);
+    }
+    if payload.starts_with(BITTORRENT_HANDSHAKE) {
+        return detection("bittorrent", "BitTorrent handshake", true);
+    }
+    if is_smb(payload) {
+        return detection("smb", "SMB header", true);
+    }
+    if is_rdp_connection_request(payload) {
+        return detection("rdp", "X.224 connection request", true);
+    }
+    if is_mysql_greeting(payload) {
+        return detection("mysql", "MySQL greeting", true);
+    }
+    if let Some(method) = postgres_startup(payload) {
+        return detection("postgres", method, true);
+    }
+    None
+}
+
+fn detect_udp(payload: &[u8]) -> Option<Detection> {
+    if long_header_version(payload).is_some() {
+        return detection(QUIC_SERVICE, "QUIC long header", true);
+    }
+    if payload.starts_with(b"d1:") && contains(payload, b"1:y1:") {
+        return detection("bittorrent", "BitTorrent DHT message", true);
+    }
+    // Plenty of binary payloads happen to parse, so this only names unknown ports
+    if DnsMessage::parse(payload).is_some_and(|message| message.questions.len() == 1) {
+        return detection("dns", "DNS message", false);
+    }
+    None
+}
+
+// A ClientHello or ServerHello record
+fn is_tls_handshake(payload: &[u8]) -> bool {
+    payload.len() >= 6 && payload[0] == 0x16 && payload[1] == 0x03 && payload[2] <= 0x04 &&
+        matches!(payload[5], 1 | 2)
+}
+
+// SMB1 or SMB2/3 header behind a NetBIOS session message
+fn is_smb(payload: &[u8]) -> bool {
+    payload.len() >= 8 && payload[0] == 0x00 &&
+        matches!(&payload[4..8], b"\xffSMB" | b"\xfeSMB")
+}
+
+// TPKT header whose length matches, carrying an X.224 connection request
+fn is_rdp_connection_request(payload: &[u8]) -> bool {
+    payload.len() >= 11 && payload[0] == 3 && payload[1] == 0 &&
+        u16::from_be_bytes([payload[2], payload[3]]) as usize == payload.len() &&
+        payload[4] as usize + 5 == payload.len() &&
+        payload[5] == 0xe0
+}
+
+// Protocol 10 handshake: the packet length, sequence 0, then the server
+// version as a NUL-terminated string such as "8.0.36"
+fn is_mysql_greeting(payload: &[u8]) -> bool {
+    if payload.len() < 10 || payload[3] != 0 || payload[4] != 0x0a {
+        return false;
+    }
+    let length = u32::from_le_bytes([payload[0], payload[1], payload[2], 0]) as usize;
+    if length + 4 != payload.len() {
+        return false;
+    }
+    let version = &payload[5..];
+    let Some(end) = version.iter().position(|byte| *byte == 0) else { return false };
+    end > 0 && version[0].is_ascii_digit() && version[..end].iter().all(|byte| byte.is_ascii_graphic())
+}
+
+// The client's first message: its length, then a startup code
+fn postgres_startup(payload: &[u8]) -> Option<&'static str> {
+    if payload.len() < 8 {
+        return None;
+    }
+    let length = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    let code = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    if length != payload.len() {
+        return None;
+    }
+    match code {
+        POSTGRES_STARTUP => Some("PostgreSQL startup"),
+        POSTGRES_SSL_REQUEST if length == 8 => Some("PostgreSQL SSL request"),
+        POSTGRES_GSSENC_REQUEST if length == 8 => Some("PostgreSQL GSS request"),
+        _ => None,
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::tcp::TcpFlags;
+    use crate::network::flow::FlowPacket;
+    use crate::network::test_util::{flow_table, interface, tcp_packet};
+    use crate::network::types::{PacketType, Segment};
+    
+    fn tcp(payload: &[u8]) -> Option<(&'static str, &'static str)> {
+        detect_tcp(payload).map(|detection| (detection.service, detection.method))
+    }
+    
+    fn udp(payload: &[u8]) -> Option<(&'static str, &'static str)> {
+        detect_udp(payload).map(|detection| (detection.service, detection.method))
+    }
+    
+    fn with_length(body: &[u8]) -> Vec<u8> {
+        let mut payload = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(body);
+        payload
+    }
+    
+    #[test]
+    fn tls_handshake() {
+        assert_eq!(tcp(&[0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00]), Some(("tls", "TLS handshake")));
+        assert!(!detect_tcp(&[0x16, 0x03, 0x03, 0x00, 0x7a, 0x02]).unwrap().confident);
+        // Application data records aren't
+        assert_eq!(tcp(&[0x17, 0x03, 0x03, 0x00, 0x20, 0x01]), None);
+    }
+    
+    #[test]
+    fn http_request_and_status_lines() {
+        assert_eq!(tcp(b"GET /index.html HTTP/1.1\r\n"), Some(("http", "HTTP request line")));
+        assert_eq!(tcp(b"HTTP/1.1 200 OK\r\n"), Some(("http", "HTTP status line")));
+        assert_eq!(tcp(b"GETTING /"), None);
+        assert_eq!(tcp(b"HTTP/2 200\r\n"), None);
+    }
+    
+    #[test]
+    fn http2_preface() {
+        assert_eq!(tcp(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x12\x04"), Some(("http", "HTTP/2 preface")));
+        assert_eq!(tcp(b"PRI * HTTP/2.0\r\n\r\n"), None);
+    }
+    
+    #[test]
+    fn ssh_banner() {
+        assert_eq!(tcp(b"SSH-2.0-OpenSSH_9.6\r\n"), Some(("ssh", "SSH banner")));
+        assert_eq!(tcp(b"SSH-1.99-Cisco-1.25\r\n"), Some(("ssh", "SSH banner")));
+        assert_eq!(tcp(b"SSH-3.0-future\r\n"), None);
+    }
+    
+    #[test]
+    fn bittorrent_handshake() {
+        assert_eq!(tcp(b"\x13BitTorrent protocol\x00\x00\x00\x00"), Some(("bittorrent", "BitTorrent handshake")));
+        // The length byte is part of the signature
+        assert_eq!(tcp(b"\x14BitTorrent protocol\x00\x00\x00"), None);
+    }
+    
+    #[test]
+    fn smb_header() {
+        assert_eq!(tcp(b"\x00\x00\x00\x44\xfeSMB\x40\x00"), Some(("smb", "SMB header")));
+        assert_eq!(tcp(b"\x00\x00\x00\x2f\xffSMB\x72\x00"), Some(("smb", "SMB header")));
+        // A NetBIOS session request rather than a message
+        assert_eq!(tcp(b"\x81\x00\x00\x44\xfeSMB"), None);
+    }
+    
+    #[test]
+    fn rdp_connection_request() {
+        let request = [0x03, 0x00, 0x00, 0x0b, 0x06, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(tcp(&request), Some(("rdp", "X.224 connection request")));
+        // TPKT length disagreeing with the packet
+        let mut wrong_length = request;
+        wrong_length[3] = 0x0c;
+        assert_eq!(tcp(&wrong_length), None);
+    }
+    
+    #[test]
+    fn mysql_greeting() {
+        let mut body = vec![0x0a];
+        body.extend(b"8.0.36\x00");
+        body.extend([0x08, 0x00, 0x00, 0x00]);
+        let mut greeting = (body.len() as u32).to_le_bytes()[..3].to_vec();
+        greeting.push(0);
+        greeting.extend(&body);
+        assert_eq!(tcp(&greeting), Some(("mysql", "MySQL greeting")));
+        
+        // The version has to look like one
+        greeting[5] = b'x';
+        assert_eq!(tcp(&greeting), None);
+    }
+    
+    #[test]
+    fn postgres_startup_messages() {
+        let startup = with_length(b"\x00\x03\x00\x00user\x00alice\x00\x00");
+        assert_eq!(tcp(&startup), Some(("postgres", "PostgreSQL startup")));
+        let ssl_request = with_length(&POSTGRES_SSL_REQUEST.to_be_bytes());
+        assert_eq!(tcp(&ssl_request), Some(("postgres", "PostgreSQL SSL request")));
+        
+        // An SSL request with anything after it
+        let padded = with_length(&[0x04, 0xd2, 0x16, 0x2f, 0x00]);
+        assert_eq!(tcp(&padded), None);
+        // A length that doesn't match the packet
+        let mut truncated = startup;
+        truncated.truncate(12);
+        assert_eq!(tcp(&truncated), None);
+    }
+    
+    #[test]
+    fn quic_long_header() {
+        let initial = [0xc3, 0x00, 0x00, 0x00, 0x01, 0x08, 0x83, 0x94, 0xc8, 0xf0];
+        assert_eq!(udp(&initial), Some((QUIC_SERVICE, "QUIC long header")));
+        // Unknown version
+        assert_eq!(udp(&[0xc3, 0x12, 0x34, 0x56, 0x78, 0x08, 0x83, 0x94, 0xc8, 0xf0]), None);
+    }
+    
+    #[test]
+    fn dht_bencode() {
+        assert_eq!(udp(b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe"), Some(("bittorrent", "BitTorrent DHT message")));
+        assert_eq!(udp(b"d1:ad2:id20:abcdefghij0123456789ee"), None);
+    }
+    
+    #[test]
+    fn dns_only_as_a_guess() {
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        query.extend(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        assert_eq!(udp(&query), Some(("dns", "DNS message")));
+        assert!(!detect_udp(&query).unwrap().confident);
+        
+        // No question
+        query[5] = 0;
+        assert_eq!(udp(&query[..12]), None);
+    }
+    
+    #[test]
+    fn ssh_on_the_https_port_is_ssh() {
+        let mut table = flow_table();
+        let https = PacketType { basic: BasicProtocolType::TCP, service: Some("https") };
+        let banner = FlowPacket { protocol: https, ..tcp_packet(false, TcpFlags::ACK | TcpFlags::PSH, 500, 101, 21, 0) };
+        let (stats, _) = table.record(&interface(), Segment::default(), &banner);
+        
+        assert_eq!(stats.protocol(https), https);
+        stats.inspect_payload(BasicProtocolType::TCP, b"SSH-2.0-OpenSSH_9.6\r\n");
+        assert_eq!(stats.protocol(https).service, Some("ssh"));
+    }
+    
+    #[test]
+    fn gives_up_after_a_few_payloads() {
+        let mut table = flow_table();
+        let (stats, _) = table.record(&interface(), Segment::default(), &tcp_packet(true, TcpFlags::ACK, 101, 501, 4, 0));
+        
+        // Empty segments don't count
+        stats.inspect_payload(BasicProtocolType::TCP, b"");
+        for _ in 0..MAX_INSPECTED {
+            stats.inspect_payload(BasicProtocolType::TCP, b"\x00\x01\x02\x03");
+        }
+        stats.inspect_payload(BasicProtocolType::TCP, b"SSH-2.0-OpenSSH_9.6\r\n");
+        assert_eq!(stats.detection, None);
+        assert_eq!(stats.inspected, MAX_INSPECTED);
+    }
+}
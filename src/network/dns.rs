@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use super::types::Timestamp;
+
+// Record types we decode the data of
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_PTR: u16 = 12;
+const TYPE_AAAA: u16 = 28;
+
+pub const RCODE_SERVFAIL: u8 = 2;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+const HEADER_LEN: usize = 12;
+// Compression pointers followed before a name is considered malformed
+const MAX_POINTERS: usize = 32;
+// Sections longer than this are not real traffic
+const MAX_RECORDS: u16 = 256;
+
+// A decoded DNS query or response
+#[derive(Debug, Clone)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub response: bool,
+    pub rcode: u8,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsRecord {
+    pub name: String,
+    pub data: DnsData,
+}
+
+#[derive(Debug, Clone)]
+pub enum DnsData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Name(String),   // CNAME and PTR targets
+    Other,
+}
+
+impl DnsMessage {
+    // Decode a message as carried over UDP. Truncated answer sections still
+    // yield the records read so far
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() < HEADER_LEN {
+            return None;
+        }
+        
+        let id = u16::from_be_bytes([payload[0], payload[1]]);
+        let flags = u16::from_be_bytes([payload[2], payload[3]]);
+        let question_count = u16::from_be_bytes([payload[4], payload[5]]);
+        let answer_count = u16::from_be_bytes([payload[6], payload[7]]);
+        if question_count > MAX_RECORDS || answer_count > MAX_RECORDS {
+            return None;
+        }
+        
+        let mut message = DnsMessage {
+            id,
+            response: flags & 0x8000 != 0,
+            rcode: (flags & 0x000f) as u8,
+            questions: Vec::new(),
+            answers: Vec::new(),
+        };
+        
+        let mut offset = HEADER_LEN;
+        for _ in 0..question_count {
+            let (name, next) = read_name(payload, offset)?;
+            let qtype = read_u16(payload, next)?;
+            // Skip the class
+            offset = next + 4;
+            message.questions.push(DnsQuestion { name, qtype });
+        }
+        
+        for _ in 0..answer_count {
+            let Some((record, next)) = read_record(payload, offset) else { break };
+            message.answers.push(record);
+            offset = next;
+        }
+        
+        Some(message)
+    }
+    
+    // Decode a message as carried over TCP, behind a two-byte length
+    pub fn parse_tcp(payload: &[u8]) -> Option<Self> {
+        let length = read_u16(payload, 0)? as usize;
+        Self::parse(payload.get(2..2 + length)?)
+    }
+}
+
+pub fn record_type_name(rtype: u16) -> String {
+    match rtype {
+        TYPE_A => "A".to_string(),
+        2 => "NS".to_string(),
+        TYPE_CNAME => "CNAME".to_string(),
+        6 => "SOA".to_string(),
+        TYPE_PTR => "PTR".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        TYPE_AAAA => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        35 => "NAPTR".to_string(),
+        43 => "DS".to_string(),
+        48 => "DNSKEY".to_string(),
+        64 => "SVCB".to_string(),
+        65 => "HTTPS".to_string(),
+        255 => "ANY".to_string(),
+        257 => "CAA".to_string(),
+        other => format!("TYPE{}", other),
+    }
+}
+
+pub fn rcode_name(rcode: u8) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        RCODE_SERVFAIL => "SERVFAIL".to_string(),
+        RCODE_NXDOMAIN => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        other => format!("RCODE{}", other),
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    let bytes = buf.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_record(buf: &[u8], offset: usize) -> Option<(DnsRecord, usize)> {
+    let (name, next) = read_name(buf, offset)?;
+    let fixed = buf.get(next..next + 10)?;
+    let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+    let data_len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+    
+    let data_start = next + 10;
+    let data = buf.get(data_start..data_start + data_len)?;
+    
+    let data = match (rtype, data.len()) {
+        (TYPE_A, 4) => DnsData::A(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+        (TYPE_AAAA, 16) => {
+            let octets: [u8; 16] = data.try_into().ok()?;
+            DnsData::Aaaa(Ipv6Addr::from(octets))
+        },
+        // Names in record data may point back into the rest of the message
+        (TYPE_CNAME | TYPE_PTR, _) => read_name(buf, data_start)
+            .map_or(DnsData::Other, |(name, _)| DnsData::Name(name)),
+        _ => DnsData::Other,
+    };
+    
+    Some((DnsRecord { name, data }, data_start + data_len))
+}
+
+// Read a possibly compressed domain name, returning it and the offset just
+// past it in the original position
+fn read_name(buf: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut end = None;
+    let mut pointers = 0;
+    
+    loop {
+        let len = *buf.get(offset)? as usize;
+        match len & 0xc0 {
+            0x00 if len == 0 => {
+                let end = end.unwrap_or(offset + 1);
+                if name.is_empty() {
+                    name.push('.');
+                }
+                return Some((name, end));
+            },
+            0x00 => {
+                let label = buf.get(offset + 1..offset + 1 + len)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.extend(label.iter().map(|&byte| {
+                    if byte.is_ascii_graphic() { (byte as char).to_ascii_lowercase() } else { '?' }
+                }));
+                if name.len() > 255 {
+                    return None;
+                }
+                offset += 1 + len;
+            },
+            0xc0 => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                let target = read_u16(buf, offset)? & 0x3fff;
+                end.get_or_insert(offset + 2);
+                offset = target as usize;
+            },
+            // Extended label types are obsolete
+            _ => return None,
+        }
+    }
+}
+
+// How long a query may go unanswered before it's logged as such
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+// Responses slower than this are listed separately
+pub const SLOW_RESPONSE: Duration = Duration::from_millis(250);
+// Bounds on what is kept
+const MAX_PENDING: usize = 10_000;
+const MAX_LOG: usize = 1_000;
+const MAX_SLOW: usize = 100;
+const MAX_DOMAINS: usize = 10_000;
+
+// Queries waiting for a response: client address and port, server, message ID
+type PendingKey = (IpAddr, u16, IpAddr, u16);
+
+struct PendingQuery {
+    sent: Timestamp,
+    name: String,
+    qtype: u16,
+}
+
+// One query and what became of it
+#[derive(Debug, Clone)]
+pub struct DnsTransaction {
+    pub time: Timestamp,            // When the query was sent, or the response seen if we missed the query
+    pub client: IpAddr,
+    pub server: IpAddr,
+    pub name: String,
+    pub qtype: u16,
+    pub rcode: Option<u8>,          // None when no response came
+    pub answers: Vec<String>,
+    pub latency: Option<Duration>,  // None when the query wasn't seen
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DomainStats {
+    pub queries: u64,
+    pub failures: u64,              // NXDOMAIN, SERVFAIL and the like
+}
+
+// Queries, responses and their timing for DNS traffic on port 53
+#[derive(Default)]
+pub struct DnsStats {
+    pending: HashMap<PendingKey, PendingQuery>,
+    pub log: VecDeque<DnsTransaction>,      // Most recent first
+    pub slow: VecDeque<DnsTransaction>,     // Responses slower than SLOW_RESPONSE, most recent first
+    pub domains: HashMap<String, DomainStats>,
+    pub query_types: HashMap<u16, u64>,
+    pub rcodes: HashMap<u8, u64>,
+    pub queries: u64,
+    pub responses: u64,
+    pub unanswered: u64,
+    pub slow_responses: u64,
+    pub latency_total: Duration,
+    pub latency_samples: u64,
+    pub latency_max: Duration,
+}
+
+impl DnsStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // Account for a message sent from src to dst, captured at `now`
+    pub fn record(&mut self, message: &DnsMessage, src: (IpAddr, u16), dst: (IpAddr, u16), now: Timestamp) {
+        let Some(question) = message.questions.first() else { return };
+        
+        if !message.response {
+            self.queries += 1;
+            *self.query_types.entry(question.qtype).or_default() += 1;
+            
+            if self.domains.len() >= MAX_DOMAINS && !self.domains.contains_key(&question.name) {
+                // Make room by forgetting the names only asked for once
+                self.domains.retain(|_, domain| domain.queries > 1);
+                if self.domains.len() >= MAX_DOMAINS {
+                    self.domains.clear();
+                }
+            }
+            self.domains.entry(question.name.clone()).or_default().queries += 1;
+            
+            if self.pending.len() < MAX_PENDING {
+                self.pending.insert((src.0, src.1, dst.0, message.id), PendingQuery {
+                    sent: now,
+                    name: question.name.clone(),
+                    qtype: question.qtype,
+                });
+            }
+            return;
+        }
+        
+        self.responses += 1;
+        *self.rcodes.entry(message.rcode).or_default() += 1;
+        if message.rcode != 0 {
+            if let Some(domain) = self.domains.get_mut(&question.name) {
+                domain.failures += 1;
+            }
+        }
+        
+        let query = self.pending.remove(&(dst.0, dst.1, src.0, message.id));
+        let latency = query.as_ref().map(|query| now.saturating_sub(query.sent));
+        if let Some(latency) = latency {
+            self.latency_total += latency;
+            self.latency_samples += 1;
+            self.latency_max = self.latency_max.max(latency);
+        }
+        
+        let answers = message.answers.iter()
+            .filter_map(|answer| match &answer.data {
+                DnsData::A(ipv4) => Some(ipv4.to_string()),
+                DnsData::Aaaa(ipv6) => Some(ipv6.to_string()),
+                DnsData::Name(name) => Some(name.clone()),
+                DnsData::Other => None,
+            })
+            .collect();
+        
+        let transaction = DnsTransaction {
+            time: query.as_ref().map_or(now, |query| query.sent),
+            client: dst.0,
+            server: src.0,
+            name: question.name.clone(),
+            qtype: question.qtype,
+            rcode: Some(message.rcode),
+            answers,
+            latency,
+        };
+        
+        if latency.is_some_and(|latency| latency >= SLOW_RESPONSE) {
+            self.slow_responses += 1;
+            push_bounded(&mut self.slow, transaction.clone(), MAX_SLOW);
+        }
+        push_bounded(&mut self.log, transaction, MAX_LOG);
+    }
+    
+    // Log the queries that never got an answer, as of `now` on the capture's clock
+    pub fn expire(&mut self, now: Timestamp) {
+        let expired: Vec<PendingKey> = self.pending.iter()
+            .filter(|(_, query)| now.saturating_sub(query.sent) >= QUERY_TIMEOUT)
+            .map(|(key, _)| *key)
+            .collect();
+        
+        for key in expired {
+            let Some(query) = self.pending.remove(&key) else { continue };
+            self.unanswered += 1;
+            
+            push_bounded(&mut self.log, DnsTransaction {
+                time: query.sent,
+                client: key.0,
+                server: key.2,
+                name: query.name,
+                qtype: query.qtype,
+                rcode: None,
+                answers: Vec::new(),
+                latency: None,
+            }, MAX_LOG);
+        }
+    }
+    
+    // Share of responses with the given code, in percent
+    pub fn rcode_rate(&self, rcode: u8) -> f64 {
+        if self.responses == 0 {
+            return 0.0;
+        }
+        self.rcodes.get(&rcode).copied().unwrap_or(0) as f64 / self.responses as f64 * 100.0
+    }
+    
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.latency_samples > 0).then(|| self.latency_total.div_f64(self.latency_samples as f64))
+    }
+}
+
+fn push_bounded(list: &mut VecDeque<DnsTransaction>, transaction: DnsTransaction, max: usize) {
+    list.push_front(transaction);
+    list.truncate(max);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{ip, CLIENT, START};
+    
+    // Response for www.example.com A: a CNAME to cdn.example.com, written
+    // with a pointer into the question, then an A record for the target
+    const RESPONSE: [u8; 67] = [
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+        // Question at 12: www.example.com A IN
+        3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0,
+        0x00, 0x01, 0x00, 0x01,
+        // Answer at 33: www.example.com CNAME cdn + pointer to example.com at 16
+        0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x06,
+        3, b'c', b'd', b'n', 0xc0, 0x10,
+        // Answer at 51: pointer to the CNAME target at 45, A 93.184.216.34
+        0xc0, 0x2d, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04,
+        93, 184, 216, 34,
+    ];
+    
+    fn query(name: &[u8]) -> Vec<u8> {
+        let mut message = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        message.extend_from_slice(name);
+        message.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        message
+    }
+    
+    #[test]
+    fn follows_compression_pointers() {
+        let message = DnsMessage::parse(&RESPONSE).unwrap();
+        assert!(message.response);
+        assert_eq!(message.id, 0x1234);
+        assert_eq!(message.questions[0].name, "www.example.com");
+        assert_eq!(message.questions[0].qtype, TYPE_A);
+        
+        assert_eq!(message.answers.len(), 2);
+        assert_eq!(message.answers[0].name, "www.example.com");
+        assert!(matches!(&message.answers[0].data, DnsData::Name(name) if name == "cdn.example.com"));
+        assert_eq!(message.answers[1].name, "cdn.example.com");
+        assert!(matches!(message.answers[1].data, DnsData::A(ip) if ip == Ipv4Addr::new(93, 184, 216, 34)));
+    }
+    
+    #[test]
+    fn rejects_pointer_loops() {
+        // A name pointing at itself, and two names pointing at each other
+        assert!(DnsMessage::parse(&query(&[0xc0, 0x0c])).is_none());
+        assert!(DnsMessage::parse(&query(&[0xc0, 0x0e, 0xc0, 0x0c])).is_none());
+    }
+    
+    #[test]
+    fn truncated_messages() {
+        assert!(DnsMessage::parse(&RESPONSE[..11]).is_none());
+        // Cut inside the question
+        assert!(DnsMessage::parse(&RESPONSE[..20]).is_none());
+        // Cut inside the second answer: the first one is kept
+        let message = DnsMessage::parse(&RESPONSE[..RESPONSE.len() - 2]).unwrap();
+        assert_eq!(message.answers.len(), 1);
+        // A label running past the end
+        assert!(DnsMessage::parse(&query(&[9, b'e', b'x'])).is_none());
+    }
+    
+    #[test]
+    fn tcp_length_prefix() {
+        let mut framed = vec![0, RESPONSE.len() as u8];
+        framed.extend_from_slice(&RESPONSE);
+        assert_eq!(DnsMessage::parse_tcp(&framed).unwrap().answers.len(), 2);
+        assert!(DnsMessage::parse_tcp(&framed[..framed.len() - 1]).is_none());
+    }
+    
+    #[test]
+    fn latency_from_capture_timestamps() {
+        let client = (CLIENT, 50000);
+        let server = (ip("10.0.0.53"), 53);
+        let sent = START;
+        let query = DnsMessage::parse(&query(&RESPONSE[12..29])).unwrap();
+        let response = DnsMessage::parse(&RESPONSE).unwrap();
+        
+        let mut stats = DnsStats::new();
+        stats.record(&query, client, server, sent);
+        stats.record(&response, server, client, sent + Duration::from_millis(30));
+        assert_eq!(stats.log[0].latency, Some(Duration::from_millis(30)));
+        
+        // A second query nobody answers
+        stats.record(&query, client, server, sent + Duration::from_secs(1));
+        stats.expire(sent + Duration::from_secs(5));
+        assert_eq!(stats.unanswered, 0);
+        stats.expire(sent + Duration::from_secs(6));
+        assert_eq!(stats.unanswered, 1);
+    }
+}
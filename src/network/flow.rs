@@ -0,0 +1,826 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use pnet::packet::tcp::TcpFlags;
+
+use super::process::SocketTable;
+use super::types::{
+    ConnectionId, ConnectionStats, HelloState, HttpState, IcmpType, PacketType, ProcessInfo, QuicState, Segment, TcpCounters, TcpDirection, TcpSession, TcpState, Timestamp,
+};
+
+// A transport-layer packet as seen by the flow tracker
+#[derive(Debug, Clone, Copy)]
+pub struct FlowPacket {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: PacketType,
+    pub wire_len: u32,
+    pub timestamp: Timestamp,       // When it was captured
+    pub tcp: Option<TcpSegment>,    // Only set for TCP
+    pub icmp: Option<IcmpType>,     // Only set for ICMP
+}
+
+// The TCP header fields the flow tracker looks at
+#[derive(Debug, Clone, Copy)]
+pub struct TcpSegment {
+    pub flags: u8,
+    pub seq: u32,
+    pub ack: u32,
+    pub window: u16,
+    pub payload_len: u32,
+}
+
+impl FlowPacket {
+    // A SYN without ACK opens a connection, so its sender is the client
+    fn is_syn(&self) -> bool {
+        matches!(self.tcp, Some(tcp) if tcp.flags & TcpFlags::SYN != 0 && tcp.flags & TcpFlags::ACK == 0)
+    }
+    
+    // A SYN-ACK answers one, so its receiver is the client
+    fn is_syn_ack(&self) -> bool {
+        matches!(self.tcp, Some(tcp) if tcp.flags & TcpFlags::SYN != 0 && tcp.flags & TcpFlags::ACK != 0)
+    }
+    
+    // Echo replies and errors are sent back to whoever sent the packet
+    // they answer
+    fn is_icmp_response(&self) -> bool {
+        self.icmp.is_some_and(|icmp| icmp.is_echo_reply() || icmp.is_error())
+    }
+}
+
+// How long a handshake may stay unanswered before it counts as failed
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long a closed or reset TCP connection stays listed
+const TCP_CLOSED_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Shortest gap after which an earlier segment counts as a retransmission
+// rather than reordering, when the RTT is unknown or tiny
+const MIN_REORDER_WINDOW: Duration = Duration::from_millis(3);
+
+// How often expired flows are swept out of the table
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long a process without flows stays listed after its last packet
+const PROCESS_LINGER: Duration = Duration::from_secs(600);
+
+// When to drop flows from the table
+#[derive(Debug, Clone)]
+pub struct FlowTimeouts {
+    pub tcp_idle: Duration,     // Open TCP connection without traffic
+    pub tcp_closed: Duration,   // After FIN/RST or a failed handshake
+    pub udp_idle: Duration,     // UDP and everything without connection state
+    pub active: Duration,       // Longest a single flow record may run before it's rolled over
+}
+
+impl FlowTimeouts {
+    pub fn new(tcp_idle: Duration, udp_idle: Duration, active: Duration) -> Self {
+        FlowTimeouts {
+            tcp_idle,
+            tcp_closed: TCP_CLOSED_TIMEOUT,
+            udp_idle,
+            active,
+        }
+    }
+}
+
+// Why a flow record left the table
+#[derive(Debug, Clone, Copy)]
+enum ExpiryReason {
+    Idle,
+    Closed,
+    Active,
+    Evicted,
+}
+
+// Totals of the flow records no longer in the table, so overall counts stay right
+#[derive(Debug, Clone, Default)]
+pub struct ExpiredFlows {
+    pub flows: u64,
+    pub packets: u64,
+    pub bytes: u64,
+    pub idle: u64,      // Timed out without traffic
+    pub closed: u64,    // TCP connections that ended
+    pub active: u64,    // Long-running flows rolled over into a new record
+    pub evicted: u64,   // Dropped to stay under the flow limit
+    pub tcp: TcpCounters,
+}
+
+impl ExpiredFlows {
+    fn add(&mut self, stats: &ConnectionStats, reason: ExpiryReason) {
+        self.flows += 1;
+        self.packets += stats.packet_count;
+        self.bytes += stats.byte_count;
+        
+        // Rolled over flows keep their TCP counters
+        if let (Some(tcp), false) = (&stats.tcp, matches!(reason, ExpiryReason::Active)) {
+            self.tcp.merge(&tcp.counters);
+        }
+        
+        match reason {
+            ExpiryReason::Idle => self.idle += 1,
+            ExpiryReason::Closed => self.closed += 1,
+            ExpiryReason::Active => self.active += 1,
+            ExpiryReason::Evicted => self.evicted += 1,
+        }
+    }
+    
+    fn merge(&mut self, other: &ExpiredFlows) {
+        self.flows += other.flows;
+        self.packets += other.packets;
+        self.bytes += other.bytes;
+        self.idle += other.idle;
+        self.closed += other.closed;
+        self.active += other.active;
+        self.evicted += other.evicted;
+        self.tcp.merge(&other.tcp);
+    }
+}
+
+// Aggregate TCP quality, for the Overview tab
+#[derive(Debug, Clone, Default)]
+pub struct TcpHealth {
+    pub counters: TcpCounters,
+    pub handshake_rtt: Option<Duration>,    // Average over connections with a handshake
+    pub handshake_samples: u32,
+    pub rtt: Option<Duration>,              // Average smoothed RTT
+    pub rtt_max: Option<Duration>,
+    pub rtt_samples: u32,
+}
+
+// Traffic of one local process, summed over its flows
+#[derive(Debug, Clone)]
+pub struct ProcessTraffic {
+    pub info: Arc<ProcessInfo>,
+    pub flows: u64,         // Flows attributed to it so far
+    pub tx_bytes: u64,      // Sent by the process
+    pub rx_bytes: u64,      // Received by the process
+    pub tx_rate: f64,       // Bytes per second over the last sweep
+    pub rx_rate: f64,
+    pub last_seen: Timestamp,   // Latest packet on any of its flows
+    rate_mark: (u64, u64),      // Byte counts at the last sweep
+}
+
+// All tracked flows, plus counters about how TCP connections ended and
+// what has already been expired
+#[derive(Debug)]
+pub struct FlowTable {
+    pub flows: HashMap<ConnectionId, ConnectionStats>,
+    pub tcp_resets: u64,            // Connections torn down by a RST
+    pub failed_handshakes: u64,     // Connections refused or never answered
+    pub unanswered_echoes: u64,     // ICMP echo requests that never got a reply
+    pub expired: HashMap<Arc<str>, ExpiredFlows>,   // Per interface
+    pub timeouts: FlowTimeouts,
+    pub max_flows: usize,
+    pub processes: HashMap<u32, ProcessTraffic>,    // By pid, kept for a while after its flows expire
+    last_sweep: Option<Timestamp>,
+}
+
+impl FlowTable {
+    pub fn new(timeouts: FlowTimeouts, max_flows: usize) -> Self {
+        FlowTable {
+            flows: HashMap::new(),
+            tcp_resets: 0,
+            failed_handshakes: 0,
+            unanswered_echoes: 0,
+            expired: HashMap::new(),
+            timeouts,
+            max_flows: max_flows.max(1),
+            processes: HashMap::new(),
+            last_sweep: None,
+        }
+    }
+    
+    // Expired totals for one interface, or all of them
+    pub fn expired_for(&self, interface: Option<&str>) -> ExpiredFlows {
+        let mut total = ExpiredFlows::default();
+        for (name, expired) in &self.expired {
+            if interface.is_none_or(|interface| **name == *interface) {
+                total.merge(expired);
+            }
+        }
+        total
+    }
+    
+    // Add a packet to its flow, creating the flow on first sight. Both
+    // directions of a conversation land on the same entry. Returns the flow
+    // and whether the packet came from its client
+    pub fn record(&mut self, interface: &Arc<str>, segment: Segment, packet: &FlowPacket) -> (&mut ConnectionStats, bool) {
+        let now = packet.timestamp;
+        let (conn_id, sender) = ConnectionId::new(
+            interface.clone(),
+            segment,
+            packet.protocol,
+            (packet.src_ip, packet.src_port),
+            (packet.dst_ip, packet.dst_port),
+        );
+        
+        // Make room before adding a new flow
+        if self.flows.len() >= self.max_flows && !self.flows.contains_key(&conn_id) {
+            self.evict_least_recent();
+        }
+        
+        let stats = self.flows.entry(conn_id).or_insert_with(|| {
+            // Without a handshake the first packet we see is our best guess
+            let initiator = if packet.is_syn_ack() || packet.is_icmp_response() { sender.other() } else { sender };
+            
+            ConnectionStats {
+                first_seen: now,
+                last_seen: now,
+                packet_count: 0,
+                byte_count: 0,
+                tx_packets: 0,
+                tx_bytes: 0,
+                rx_packets: 0,
+                rx_bytes: 0,
+                initiator,
+                initiator_confirmed: packet.is_syn() || packet.is_syn_ack(),
+                tcp: None,
+                icmp: None,
+                process: None,
+                asn: None,
+                hello: HelloState::default(),
+                tls: None,
+                http: HttpState::default(),
+                quic: QuicState::default(),
+                detection: None,
+                inspected: 0,
+            }
+        });
+        
+        // A handshake settles who the client is, even when the flow was picked
+        // up mid-stream or the ports are being reused for a new connection
+        let reopening = packet.is_syn() && matches!(
+            stats.tcp.as_ref().map(|tcp| tcp.state),
+            Some(TcpState::Reset | TcpState::Closed)
+        );
+        if (!stats.initiator_confirmed || reopening) && (packet.is_syn() || packet.is_syn_ack()) {
+            let initiator = if packet.is_syn() { sender } else { sender.other() };
+            if initiator != stats.initiator {
+                stats.initiator = initiator;
+                std::mem::swap(&mut stats.tx_packets, &mut stats.rx_packets);
+                std::mem::swap(&mut stats.tx_bytes, &mut stats.rx_bytes);
+            }
+            stats.initiator_confirmed = true;
+        }
+        
+        let bytes = packet.wire_len as u64;
+        let from_client = sender == stats.initiator;
+        stats.last_seen = now;
+        stats.packet_count += 1;
+        stats.byte_count += bytes;
+        
+        if from_client {
+            stats.tx_packets += 1;
+            stats.tx_bytes += bytes;
+        } else {
+            stats.rx_packets += 1;
+            stats.rx_bytes += bytes;
+        }
+        
+        if let Some(owner) = &stats.process {
+            if let Some(traffic) = self.processes.get_mut(&owner.info.pid) {
+                traffic.last_seen = traffic.last_seen.max(now);
+                if sender == owner.side {
+                    traffic.tx_bytes += bytes;
+                } else {
+                    traffic.rx_bytes += bytes;
+                }
+            }
+        }
+        
+        if let Some(segment) = &packet.tcp {
+            let tcp = stats.tcp.get_or_insert_with(|| TcpSession::new(segment.flags, now));
+            let previous = tcp.state;
+            tcp.update(segment, from_client, now);
+            
+            // Count each connection's ending once
+            if tcp.state == TcpState::Reset && previous != TcpState::Reset {
+                self.tcp_resets += 1;
+                if previous.in_handshake() {
+                    tcp.handshake_failed = true;
+                    self.failed_handshakes += 1;
+                }
+            }
+        }
+        
+        (stats, from_client)
+    }
+    
+    // TCP quality figures for one interface, or all of them: problem counts
+    // include expired flows, RTTs are averaged over the live connections
+    pub fn tcp_health(&self, interface: Option<&str>) -> TcpHealth {
+        let mut health = TcpHealth {
+            counters: self.expired_for(interface).tcp,
+            ..TcpHealth::default()
+        };
+        let mut handshake_total = Duration::ZERO;
+        let mut rtt_total = Duration::ZERO;
+        
+        for (id, stats) in &self.flows {
+            if interface.is_some_and(|interface| *id.interface != *interface) {
+                continue;
+            }
+            let Some(tcp) = &stats.tcp else { continue };
+            
+            health.counters.merge(&tcp.counters);
+            if let Some(rtt) = tcp.handshake_rtt {
+                handshake_total += rtt;
+                health.handshake_samples += 1;
+            }
+            if let Some(rtt) = tcp.srtt {
+                rtt_total += rtt;
+                health.rtt_samples += 1;
+                health.rtt_max = health.rtt_max.max(Some(rtt));
+            }
+        }
+        
+        if health.handshake_samples > 0 {
+            health.handshake_rtt = Some(handshake_total / health.handshake_samples);
+        }
+        if health.rtt_samples > 0 {
+            health.rtt = Some(rtt_total / health.rtt_samples);
+        }
+        health
+    }
+    
+    // Drop flows that timed out and roll over long-running ones, as of `now`
+    // on the capture's clock. Cheap to call often, the table is only swept
+    // once per interval
+    pub fn expire(&mut self, now: Timestamp) {
+        let Some(last_sweep) = self.last_sweep else {
+            self.last_sweep = Some(now);
+            return;
+        };
+        let elapsed = now.saturating_sub(last_sweep);
+        if elapsed < SWEEP_INTERVAL {
+            return;
+        }
+        self.last_sweep = Some(now);
+        
+        self.update_process_rates(elapsed);
+        self.expire_handshakes(now);
+        self.expire_echoes(now);
+        
+        let timeouts = &self.timeouts;
+        let expired = &mut self.expired;
+        self.flows.retain(|id, stats| {
+            let idle = now.saturating_sub(stats.last_seen);
+            let reason = match &stats.tcp {
+                Some(tcp) if matches!(tcp.state, TcpState::Closed | TcpState::Reset) && idle >= timeouts.tcp_closed => {
+                    Some(ExpiryReason::Closed)
+                },
+                Some(_) if idle >= timeouts.tcp_idle => Some(ExpiryReason::Idle),
+                None if idle >= timeouts.udp_idle => Some(ExpiryReason::Idle),
+                _ => None,
+            };
+            
+            if let Some(reason) = reason {
+                expired.entry(id.interface.clone()).or_default().add(stats, reason);
+                return false;
+            }
+            
+            // Long-running flows stay listed with fresh counters, keeping
+            // their initiator and TCP state
+            if now.saturating_sub(stats.first_seen) >= timeouts.active {
+                expired.entry(id.interface.clone()).or_default().add(stats, ExpiryReason::Active);
+                stats.rollover(now);
+            }
+            true
+        });
+        
+        self.expire_processes(now);
+    }
+    
+    // Tag flows that have no process yet with the one owning their socket,
+    // carrying over the traffic they already saw
+    pub fn attribute_processes(&mut self, sockets: &SocketTable) {
+        for (id, stats) in self.flows.iter_mut() {
+            if stats.process.is_some() {
+                continue;
+            }
+            let Some(owner) = sockets.owner(id) else { continue };
+            
+            // The pid may have been reused by another program
+            let traffic = self.processes.entry(owner.info.pid)
+                .and_modify(|traffic| {
+                    if traffic.info != owner.info {
+                        *traffic = ProcessTraffic::new(owner.info.clone(), stats.last_seen);
+                    }
+                })
+                .or_insert_with(|| ProcessTraffic::new(owner.info.clone(), stats.last_seen));
+            traffic.last_seen = traffic.last_seen.max(stats.last_seen);
+            
+            let (sent, received) = if owner.side == stats.initiator {
+                (stats.tx_bytes, stats.rx_bytes)
+            } else {
+                (stats.rx_bytes, stats.tx_bytes)
+            };
+            traffic.flows += 1;
+            traffic.tx_bytes += sent;
+            traffic.rx_bytes += received;
+            // Earlier traffic isn't part of the current rate
+            traffic.rate_mark.0 += sent;
+            traffic.rate_mark.1 += received;
+            
+            stats.process = Some(owner);
+        }
+    }
+    
+    fn update_process_rates(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        for traffic in self.processes.values_mut() {
+            let (tx_mark, rx_mark) = traffic.rate_mark;
+            traffic.tx_rate = (traffic.tx_bytes - tx_mark) as f64 / seconds;
+            traffic.rx_rate = (traffic.rx_bytes - rx_mark) as f64 / seconds;
+            traffic.rate_mark = (traffic.tx_bytes, traffic.rx_bytes);
+        }
+    }
+    
+    // Give up on handshakes that were never answered
+    fn expire_handshakes(&mut self, now: Timestamp) {
+        for stats in self.flows.values_mut() {
+            if let Some(tcp) = stats.tcp.as_mut() {
+                if tcp.state.in_handshake() && now.saturating_sub(stats.last_seen) >= HANDSHAKE_TIMEOUT {
+                    tcp.state = TcpState::Closed;
+                    tcp.handshake_failed = true;
+                    self.failed_handshakes += 1;
+                }
+            }
+        }
+    }
+    
+    // Forget processes with no flows left that have been quiet for a while,
+    // so short-lived programs don't pile up over a long capture
+    fn expire_processes(&mut self, now: Timestamp) {
+        let live: HashSet<u32> = self.flows.values()
+            .filter_map(|stats| stats.process.as_ref())
+            .map(|owner| owner.info.pid)
+            .collect();
+        self.processes.retain(|pid, traffic| live.contains(pid) || now.saturating_sub(traffic.last_seen) < PROCESS_LINGER);
+    }
+    
+    // Count pings whose reply is overdue, even if nothing else arrives on their flow
+    fn expire_echoes(&mut self, now: Timestamp) {
+        for stats in self.flows.values_mut() {
+            if let Some(icmp) = stats.icmp.as_mut() {
+                self.unanswered_echoes += icmp.expire(now);
+            }
+        }
+    }
+    
+    // Evict the least recently seen tenth of the table in one pass, so a
+    // full table doesn't need a scan for every new flow
+    fn evict_least_recent(&mut self) {
+        let keep = self.max_flows - self.max_flows / 10;
+        let excess = self.flows.len().saturating_sub(keep).max(1);
+        
+        let mut last_seen: Vec<Timestamp> = self.flows.values().map(|stats| stats.last_seen).collect();
+        let (_, cutoff, _) = last_seen.select_nth_unstable(excess - 1);
+        let cutoff = *cutoff;
+        
+        let expired = &mut self.expired;
+        self.flows.retain(|id, stats| {
+            if stats.last_seen <= cutoff {
+                expired.entry(id.interface.clone()).or_default().add(stats, ExpiryReason::Evicted);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+// Processes by current bandwidth, then by total traffic, as the Processes tab lists them
+pub fn sorted_processes(table: &FlowTable) -> Vec<&ProcessTraffic> {
+    let mut processes: Vec<&ProcessTraffic> = table.processes.values().collect();
+    processes.sort_by(|a, b| {
+        let a_rate = a.tx_rate + a.rx_rate;
+        let b_rate = b.tx_rate + b.rx_rate;
+        b_rate.total_cmp(&a_rate)
+            .then((b.tx_bytes + b.rx_bytes).cmp(&(a.tx_bytes + a.rx_bytes)))
+            .then(a.info.pid.cmp(&b.info.pid))
+    });
+    processes
+}
+
+impl ProcessTraffic {
+    fn new(info: Arc<ProcessInfo>, last_seen: Timestamp) -> Self {
+        ProcessTraffic {
+            info,
+            flows: 0,
+            tx_bytes: 0,
+            rx_bytes: 0,
+            tx_rate: 0.0,
+            rx_rate: 0.0,
+            last_seen,
+            rate_mark: (0, 0),
+        }
+    }
+}
+
+impl ConnectionStats {
+    // Start a new record for a flow that's still running
+    fn rollover(&mut self, now: Timestamp) {
+        self.first_seen = now;
+        self.packet_count = 0;
+        self.byte_count = 0;
+        self.tx_packets = 0;
+        self.tx_bytes = 0;
+        self.rx_packets = 0;
+        self.rx_bytes = 0;
+    }
+}
+
+impl TcpSession {
+    // State of a connection first seen with these flags. Flows picked up
+    // mid-stream are assumed to be established
+    fn new(flags: u8, now: Timestamp) -> Self {
+        let state = if flags & TcpFlags::SYN != 0 && flags & TcpFlags::ACK == 0 {
+            TcpState::SynSent
+        } else if flags & TcpFlags::SYN != 0 {
+            TcpState::SynReceived
+        } else {
+            TcpState::Established
+        };
+        
+        TcpSession {
+            state,
+            client_fin: false,
+            server_fin: false,
+            handshake_failed: false,
+            syn_time: (state == TcpState::SynSent).then_some(now),
+            handshake_rtt: None,
+            srtt: None,
+            client: TcpDirection::default(),
+            server: TcpDirection::default(),
+            counters: TcpCounters::default(),
+        }
+    }
+    
+    fn update(&mut self, segment: &TcpSegment, from_client: bool, now: Timestamp) {
+        self.advance_state(segment.flags, from_client, now);
+        self.analyze(segment, from_client, now);
+    }
+    
+    // Advance the state machine with one packet's flags
+    fn advance_state(&mut self, flags: u8, from_client: bool, now: Timestamp) {
+        let syn = flags & TcpFlags::SYN != 0;
+        let ack = flags & TcpFlags::ACK != 0;
+        
+        if flags & TcpFlags::RST != 0 {
+            self.state = TcpState::Reset;
+            return;
+        }
+        
+        match self.state {
+            TcpState::SynSent if syn && ack && !from_client => self.state = TcpState::SynReceived,
+            TcpState::SynReceived if ack && !syn && from_client => {
+                self.state = TcpState::Established;
+                self.handshake_rtt = self.syn_time.map(|sent| now.saturating_sub(sent));
+            },
+            // A new connection reusing the same ports; the flow keeps its counters
+            TcpState::Reset | TcpState::Closed if syn && !ack => {
+                let counters = self.counters;
+                *self = TcpSession::new(flags, now);
+                self.counters = counters;
+                return;
+            },
+            _ => {},
+        }
+        
+        if flags & TcpFlags::FIN != 0 && matches!(self.state, TcpState::Established | TcpState::FinWait) {
+            if from_client {
+                self.client_fin = true;
+            } else {
+                self.server_fin = true;
+            }
+            
+            self.state = if self.client_fin && self.server_fin {
+                TcpState::Closed
+            } else {
+                TcpState::FinWait
+            };
+        }
+    }
+    
+    // Sequence and ACK analysis: RTT samples, retransmissions, reordering,
+    // duplicate ACKs and zero windows
+    fn analyze(&mut self, segment: &TcpSegment, from_client: bool, now: Timestamp) {
+        let syn = segment.flags & TcpFlags::SYN != 0;
+        let fin = segment.flags & TcpFlags::FIN != 0;
+        let rst = segment.flags & TcpFlags::RST != 0;
+        let ack = segment.flags & TcpFlags::ACK != 0;
+        
+        let TcpSession { client, server, counters, srtt, .. } = self;
+        let (sender, receiver) = if from_client { (client, server) } else { (server, client) };
+        
+        counters.segments += 1;
+        if rst {
+            return;
+        }
+        
+        // The window in a SYN is never scaled, and zero there is meaningless
+        if segment.window == 0 && !syn {
+            counters.zero_windows += 1;
+        }
+        
+        // Sequence space the segment uses up: its payload plus SYN and FIN
+        let seq_len = segment.payload_len + syn as u32 + fin as u32;
+        if seq_len > 0 {
+            let end = segment.seq.wrapping_add(seq_len);
+            
+            match sender.next_seq {
+                // Keep-alives resend the last byte and aren't retransmissions
+                Some(next) if seq_before(segment.seq, next) && !(segment.payload_len <= 1 && segment.seq == next.wrapping_sub(1)) => {
+                    // An earlier segment arriving right behind a later one was
+                    // reordered on the way; otherwise it's being sent again
+                    let reorder_window = srtt.unwrap_or(MIN_REORDER_WINDOW).max(MIN_REORDER_WINDOW);
+                    let reordered = sender.highest_seen.is_some_and(|seen| now.saturating_sub(seen) < reorder_window);
+                    
+                    if reordered {
+                        counters.out_of_order += 1;
+                    } else {
+                        counters.retransmissions += 1;
+                        // Karn's rule: don't time a segment that's been retransmitted
+                        if sender.rtt_probe.is_some_and(|(expected, _)| !seq_before(segment.seq, expected)) {
+                            sender.rtt_probe = None;
+                        }
+                    }
+                },
+                Some(next) if !seq_before(segment.seq, next) => {
+                    sender.next_seq = Some(end);
+                    sender.highest_seen = Some(now);
+                    sender.rtt_probe.get_or_insert((end, now));
+                },
+                Some(_) => {},
+                None => {
+                    sender.next_seq = Some(end);
+                    sender.highest_seen = Some(now);
+                    sender.rtt_probe = Some((end, now));
+                },
+            }
+        }
+        
+        if !ack {
+            return;
+        }
+        
+        // An ACK covering the other side's timed segment gives an RTT sample
+        if let Some((expected, sent)) = receiver.rtt_probe {
+            if !seq_before(segment.ack, expected) {
+                let sample = now.saturating_sub(sent);
+                *srtt = Some(match *srtt {
+                    Some(smoothed) => smoothed * 7 / 8 + sample / 8,
+                    None => sample,
+                });
+                receiver.rtt_probe = None;
+            }
+        }
+        
+        // A pure ACK repeating the last one while data is outstanding
+        let pure_ack = segment.payload_len == 0 && !syn && !fin;
+        let outstanding = receiver.next_seq.is_some_and(|next| seq_before(segment.ack, next));
+        if pure_ack && outstanding && sender.last_ack == Some(segment.ack) && sender.last_window == segment.window {
+            counters.dup_acks += 1;
+        }
+        sender.last_ack = Some(segment.ack);
+        sender.last_window = segment.window;
+    }
+}
+
+// Whether sequence number a comes before b, allowing for wraparound
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{flow_table as table, interface, tcp_packet as tcp};
+    use crate::network::types::{FlowSide, ProcessOwner, Tunnel};
+    
+    // Feed packets to a fresh table and return the one flow's TCP session
+    fn session(packets: &[FlowPacket]) -> TcpSession {
+        let mut table = table();
+        let interface = interface();
+        for packet in packets {
+            table.record(&interface, Segment::default(), packet);
+        }
+        table.flows.values().next().and_then(|stats| stats.tcp.clone()).unwrap()
+    }
+    
+    fn handshake() -> Vec<FlowPacket> {
+        vec![
+            tcp(true, TcpFlags::SYN, 100, 0, 0, 0),
+            tcp(false, TcpFlags::SYN | TcpFlags::ACK, 500, 101, 0, 40),
+            tcp(true, TcpFlags::ACK, 101, 501, 0, 80),
+        ]
+    }
+    
+    #[test]
+    fn rtts_come_from_packet_timestamps() {
+        let mut packets = handshake();
+        packets.push(tcp(true, TcpFlags::ACK, 101, 501, 100, 100));
+        packets.push(tcp(false, TcpFlags::ACK, 501, 201, 0, 130));
+        
+        let tcp = session(&packets);
+        assert_eq!(tcp.state, TcpState::Established);
+        assert_eq!(tcp.handshake_rtt, Some(Duration::from_millis(80)));
+        // 40ms from both handshake segments, then a 30ms data sample
+        assert_eq!(tcp.srtt, Some(Duration::from_micros(38_750)));
+    }
+    
+    #[test]
+    fn late_resend_is_a_retransmission() {
+        let mut packets = handshake();
+        packets.push(tcp(true, TcpFlags::ACK, 101, 501, 100, 100));
+        packets.push(tcp(true, TcpFlags::ACK, 201, 501, 100, 101));
+        packets.push(tcp(true, TcpFlags::ACK, 101, 501, 100, 400));
+        
+        let tcp = session(&packets);
+        assert_eq!(tcp.counters.retransmissions, 1);
+        assert_eq!(tcp.counters.out_of_order, 0);
+    }
+    
+    #[test]
+    fn segment_right_behind_a_later_one_is_reordered() {
+        let mut packets = handshake();
+        packets.push(tcp(true, TcpFlags::ACK, 201, 501, 100, 100));
+        packets.push(tcp(true, TcpFlags::ACK, 101, 501, 100, 101));
+        
+        let tcp = session(&packets);
+        assert_eq!(tcp.counters.out_of_order, 1);
+        assert_eq!(tcp.counters.retransmissions, 0);
+    }
+    
+    #[test]
+    fn vlans_and_tunnels_keep_flows_apart() {
+        let mut table = table();
+        let interface = interface();
+        let syn = tcp(true, TcpFlags::SYN, 100, 0, 0, 0);
+        let segments = [
+            Segment::default(),
+            Segment { vlan: Some(100), ..Segment::default() },
+            Segment { vlan: Some(200), ..Segment::default() },
+            Segment { vlan: Some(100), inner_vlan: Some(20), ..Segment::default() },
+            Segment { tunnel: Some(Tunnel::Vxlan(5000)), ..Segment::default() },
+            Segment { tunnel: Some(Tunnel::Gre(Some(1))), ..Segment::default() },
+        ];
+        for segment in segments {
+            table.record(&interface, segment, &syn);
+        }
+        assert_eq!(table.flows.len(), segments.len());
+        
+        // The same segment again is the same flow
+        table.record(&interface, segments[1], &tcp(false, TcpFlags::SYN | TcpFlags::ACK, 500, 101, 0, 40));
+        assert_eq!(table.flows.len(), segments.len());
+    }
+    
+    #[test]
+    fn flows_expire_on_the_capture_clock() {
+        let mut table = table();
+        let interface = interface();
+        let syn = tcp(true, TcpFlags::SYN, 100, 0, 0, 0);
+        table.record(&interface, Segment::default(), &syn);
+        
+        table.expire(syn.timestamp);
+        table.expire(syn.timestamp + Duration::from_secs(29));
+        assert_eq!(table.failed_handshakes, 0);
+        table.expire(syn.timestamp + HANDSHAKE_TIMEOUT);
+        assert_eq!(table.failed_handshakes, 1);
+        table.expire(syn.timestamp + HANDSHAKE_TIMEOUT + TCP_CLOSED_TIMEOUT);
+        assert!(table.flows.is_empty());
+    }
+    
+    #[test]
+    fn processes_age_out_once_their_flows_are_gone() {
+        let mut table = FlowTable::new(FlowTimeouts::new(Duration::from_secs(3600), Duration::from_secs(60), Duration::from_secs(7200)), 100);
+        let interface = interface();
+        let packets = handshake();
+        for packet in &packets {
+            table.record(&interface, Segment::default(), packet);
+        }
+        let start = packets[0].timestamp;
+        
+        let process = |pid| Arc::new(ProcessInfo { pid, name: format!("pid {}", pid), user: "root".to_string() });
+        for stats in table.flows.values_mut() {
+            stats.process = Some(ProcessOwner { info: process(1), side: FlowSide::A });
+        }
+        table.processes.insert(1, ProcessTraffic::new(process(1), start));
+        table.processes.insert(2, ProcessTraffic::new(process(2), start));
+        
+        table.expire(start);
+        table.expire(start + PROCESS_LINGER - Duration::from_secs(1));
+        assert_eq!(table.processes.len(), 2);
+        // The idle connection still holds on to its process
+        table.expire(start + PROCESS_LINGER);
+        assert_eq!(table.processes.keys().copied().collect::<Vec<_>>(), vec![1]);
+    }
+}
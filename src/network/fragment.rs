@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use super::types::Timestamp;
+
+// Incomplete datagrams are given up on after this long (RFC 791 suggests
+// 15 seconds or more, RFC 8200 uses 60)
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+// Bounds on what is buffered while waiting for the missing pieces
+const MAX_DATAGRAMS: usize = 1024;
+const MAX_FRAGMENTS: usize = 64;
+const MAX_BUFFERED_BYTES: usize = 4 * 1024 * 1024;
+// Largest payload an IP datagram can carry
+const MAX_DATAGRAM_LEN: usize = 65_535;
+
+// IPv6 extension headers that may come before the Fragment header (RFC 8200 4.1)
+const IPV6_HOP_BY_HOP: u8 = 0;
+const IPV6_ROUTING: u8 = 43;
+const IPV6_DESTINATION_OPTIONS: u8 = 60;
+
+// Fragments belong together when they share addresses, protocol and ID
+// (RFC 791, RFC 8200), on the same interface
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FragmentKey {
+    pub interface: Arc<str>,
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub protocol: u8,
+    pub id: u32,
+}
+
+// Where a fragment goes in its datagram
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment {
+    pub id: u32,
+    pub offset: usize,  // In bytes
+    pub more: bool,     // More fragments follow
+}
+
+impl Fragment {
+    // Offset 0 without more to come is a whole datagram, e.g. an IPv6
+    // atomic fragment (RFC 6946)
+    pub fn is_whole(&self) -> bool {
+        self.offset == 0 && !self.more
+    }
+}
+
+// Skip the Hop-by-Hop, Routing and Destination Options headers at the start
+// of an IPv6 payload. Returns the header that follows them, which may be a
+// Fragment header, and its data
+pub fn skip_ipv6_extensions(mut next_header: u8, mut payload: &[u8]) -> Option<(u8, &[u8])> {
+    while matches!(next_header, IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DESTINATION_OPTIONS) {
+        // Length in 8-byte units, not counting the first 8 bytes
+        let len = (*payload.get(1)? as usize + 1) * 8;
+        next_header = payload[0];
+        payload = payload.get(len..)?;
+    }
+    Some((next_header, payload))
+}
+
+// The IPv6 Fragment extension header: the next header, where the fragment
+// goes, and the fragment's data
+pub fn parse_ipv6_fragment(payload: &[u8]) -> Option<(u8, Fragment, &[u8])> {
+    let header = payload.get(..8)?;
+    let offset_flags = u16::from_be_bytes([header[2], header[3]]);
+    let fragment = Fragment {
+        id: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+        offset: (offset_flags >> 3) as usize * 8,
+        more: offset_flags & 0x01 != 0,
+    };
+    Some((header[0], fragment, &payload[8..]))
+}
+
+// A datagram being put back together
+struct Reassembly {
+    first_seen: Timestamp,
+    parts: Vec<(usize, Vec<u8>)>,   // Offset and data of each fragment
+    total_len: Option<usize>,       // Known once the last fragment arrives
+    wire_len: u32,                  // Of all the frames the fragments came in
+    discarded: bool,                // Overlapped or too large; later fragments are swallowed
+}
+
+impl Reassembly {
+    fn timed_out(&self, now: Timestamp) -> bool {
+        now.saturating_sub(self.first_seen) >= REASSEMBLY_TIMEOUT
+    }
+    
+    fn buffered(&self) -> usize {
+        self.parts.iter().map(|(_, data)| data.len()).sum()
+    }
+    
+    // The whole payload, once the pieces cover it without gaps
+    fn assemble(&mut self) -> Option<Vec<u8>> {
+        let total_len = self.total_len?;
+        self.parts.sort_by_key(|(offset, _)| *offset);
+        
+        let mut covered = 0;
+        for (offset, data) in &self.parts {
+            if *offset != covered {
+                return None;
+            }
+            covered += data.len();
+        }
+        if covered != total_len {
+            return None;
+        }
+        
+        let mut payload = Vec::with_capacity(total_len);
+        for (_, data) in self.parts.drain(..) {
+            payload.extend_from_slice(&data);
+        }
+        Some(payload)
+    }
+}
+
+// How fragmented traffic fared
+#[derive(Debug, Clone, Default)]
+pub struct FragmentStats {
+    pub fragments: u64,
+    pub reassembled: u64,   // Datagrams put back together
+    pub overlaps: u64,      // Datagrams dropped for fragments overlapping with different data
+    pub duplicates: u64,    // Fragments seen twice
+    pub timeouts: u64,      // Datagrams still missing pieces after REASSEMBLY_TIMEOUT
+    pub dropped: u64,       // Datagrams given up on to stay within the buffer limits, or too large
+}
+
+// Reassembly of fragmented IPv4 and IPv6 datagrams
+pub struct FragmentTable {
+    pending: HashMap<FragmentKey, Reassembly>,
+    buffered: usize,    // Bytes held in pending
+    pub stats: FragmentStats,
+}
+
+impl FragmentTable {
+    pub fn new() -> Self {
+        FragmentTable {
+            pending: HashMap::new(),
+            buffered: 0,
+            stats: FragmentStats::default(),
+        }
+    }
+    
+    // Datagrams waiting for more fragments, and the bytes held for them
+    pub fn in_progress(&self) -> (usize, usize) {
+        let waiting = self.pending.values().filter(|reassembly| !reassembly.discarded).count();
+        (waiting, self.buffered)
+    }
+    
+    // Add a fragment of `wire_len` bytes on the wire. Returns the datagram's
+    // payload and the wire length of all its fragments once it is complete
+    pub fn add(&mut self, key: FragmentKey, fragment: Fragment, data: &[u8], wire_len: u32, now: Timestamp) -> Option<(Vec<u8>, u32)> {
+        let Fragment { offset, more, .. } = fragment;
+        self.stats.fragments += 1;
+        
+        // An ID reused after the timeout starts a new datagram, even when
+        // no sweep has run in between
+        if self.pending.get(&key).is_some_and(|reassembly| reassembly.timed_out(now)) {
+            self.time_out(&key);
+        }
+        
+        if !self.pending.contains_key(&key) {
+            // Make room by giving up on the oldest datagram
+            if self.pending.len() >= MAX_DATAGRAMS {
+                let oldest = self.pending.iter()
+                    .min_by_key(|(_, reassembly)| reassembly.first_seen)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    self.evict(&oldest);
+                }
+            }
+            self.pending.insert(key.clone(), Reassembly {
+                first_seen: now,
+                parts: Vec::new(),
+                total_len: None,
+                wire_len: 0,
+                discarded: false,
+            });
+        }
+        let reassembly = self.pending.get_mut(&key)?;
+        if reassembly.discarded {
+            return None;
+        }
+        reassembly.wire_len += wire_len;
+        
+        let end = offset + data.len();
+        // Fragments past the largest datagram, a second different end, or
+        // data past the end can't be put together
+        let bad_end = !more && reassembly.total_len.is_some_and(|total| total != end);
+        let past_end = reassembly.total_len.is_some_and(|total| end > total)
+            || (!more && reassembly.parts.iter().any(|(offset, data)| offset + data.len() > end));
+        if end > MAX_DATAGRAM_LEN || reassembly.parts.len() >= MAX_FRAGMENTS || bad_end || past_end {
+            self.discard(&key, false);
+            return None;
+        }
+        
+        // An exact repeat is harmless; different data for the same bytes is
+        // how overlapping fragment attacks work, so the datagram is dropped
+        for (part_offset, part) in &reassembly.parts {
+            let part_end = part_offset + part.len();
+            if offset < part_end && *part_offset < end {
+                if *part_offset == offset && part.as_slice() == data {
+                    self.stats.duplicates += 1;
+                    return None;
+                }
+                self.discard(&key, true);
+                return None;
+            }
+        }
+        
+        if self.buffered + data.len() > MAX_BUFFERED_BYTES {
+            self.discard(&key, false);
+            return None;
+        }
+        reassembly.parts.push((offset, data.to_vec()));
+        if !more {
+            reassembly.total_len = Some(end);
+        }
+        self.buffered += data.len();
+        
+        let buffered = reassembly.buffered();
+        let payload = reassembly.assemble()?;
+        let wire_len = reassembly.wire_len;
+        self.buffered -= buffered;
+        self.pending.remove(&key);
+        self.stats.reassembled += 1;
+        Some((payload, wire_len))
+    }
+    
+    // Give up on incomplete datagrams that waited too long
+    pub fn expire(&mut self, now: Timestamp) {
+        let expired: Vec<FragmentKey> = self.pending.iter()
+            .filter(|(_, reassembly)| reassembly.timed_out(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        
+        for key in expired {
+            self.time_out(&key);
+        }
+    }
+    
+    fn time_out(&mut self, key: &FragmentKey) {
+        let Some(reassembly) = self.pending.remove(key) else { return };
+        self.buffered -= reassembly.buffered();
+        if !reassembly.discarded {
+            self.stats.timeouts += 1;
+        }
+    }
+    
+    // Drop a datagram's data but remember it until it times out, so its
+    // remaining fragments don't start a new one
+    fn discard(&mut self, key: &FragmentKey, overlap: bool) {
+        let Some(reassembly) = self.pending.get_mut(key) else { return };
+        self.buffered -= reassembly.buffered();
+        reassembly.parts.clear();
+        reassembly.discarded = true;
+        if overlap {
+            self.stats.overlaps += 1;
+        } else {
+            self.stats.dropped += 1;
+        }
+    }
+    
+    fn evict(&mut self, key: &FragmentKey) {
+        let Some(reassembly) = self.pending.remove(key) else { return };
+        self.buffered -= reassembly.buffered();
+        if !reassembly.discarded {
+            self.stats.dropped += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{interface, CLIENT, SERVER, START};
+    
+    fn key() -> FragmentKey {
+        FragmentKey {
+            interface: interface(),
+            src: CLIENT,
+            dst: SERVER,
+            protocol: 17,
+            id: 7,
+        }
+    }
+    
+    fn fragment(offset: usize, more: bool) -> Fragment {
+        Fragment { id: 7, offset, more }
+    }
+    
+    #[test]
+    fn out_of_order_fragments() {
+        let mut table = FragmentTable::new();
+        let now = START;
+        assert!(table.add(key(), fragment(8, false), b"world", 60, now).is_none());
+        assert_eq!(table.in_progress(), (1, 5));
+        
+        let (payload, wire_len) = table.add(key(), fragment(0, true), b"hello, w", 100, now).unwrap();
+        assert_eq!(payload, b"hello, wworld");
+        assert_eq!(wire_len, 160);
+        assert_eq!(table.stats.reassembled, 1);
+        assert_eq!(table.in_progress(), (0, 0));
+    }
+    
+    #[test]
+    fn duplicate_fragment() {
+        let mut table = FragmentTable::new();
+        let now = START;
+        table.add(key(), fragment(0, true), b"12345678", 100, now);
+        assert!(table.add(key(), fragment(0, true), b"12345678", 100, now).is_none());
+        assert_eq!(table.stats.duplicates, 1);
+        
+        let (payload, _) = table.add(key(), fragment(8, false), b"9", 60, now).unwrap();
+        assert_eq!(payload, b"123456789");
+    }
+    
+    #[test]
+    fn overlap_with_different_data_drops_the_datagram() {
+        let mut table = FragmentTable::new();
+        let now = START;
+        table.add(key(), fragment(0, true), b"12345678", 100, now);
+        assert!(table.add(key(), fragment(0, true), b"evil!!!!", 100, now).is_none());
+        assert_eq!(table.stats.overlaps, 1);
+        assert_eq!(table.in_progress(), (0, 0));
+        
+        // The rest of it is swallowed rather than starting over
+        assert!(table.add(key(), fragment(8, false), b"9", 60, now).is_none());
+        assert_eq!(table.stats.reassembled, 0);
+    }
+    
+    #[test]
+    fn conflicting_ends_drop_the_datagram() {
+        let mut table = FragmentTable::new();
+        let now = START;
+        table.add(key(), fragment(8, false), b"9", 60, now);
+        assert!(table.add(key(), fragment(16, false), b"0", 60, now).is_none());
+        assert_eq!(table.stats.dropped, 1);
+        
+        // As does data past a known end
+        let mut table = FragmentTable::new();
+        table.add(key(), fragment(8, false), b"9", 60, now);
+        assert!(table.add(key(), fragment(16, true), b"12345678", 100, now).is_none());
+        assert_eq!(table.stats.dropped, 1);
+    }
+    
+    #[test]
+    fn incomplete_datagrams_time_out() {
+        let mut table = FragmentTable::new();
+        let now = START;
+        table.add(key(), fragment(0, true), b"12345678", 100, now);
+        
+        table.expire(now + REASSEMBLY_TIMEOUT - Duration::from_secs(1));
+        assert_eq!(table.stats.timeouts, 0);
+        table.expire(now + REASSEMBLY_TIMEOUT);
+        assert_eq!(table.stats.timeouts, 1);
+        assert_eq!(table.in_progress(), (0, 0));
+    }
+    
+    #[test]
+    fn reused_id_after_the_timeout_is_a_new_datagram() {
+        let mut table = FragmentTable::new();
+        table.add(key(), fragment(0, true), b"12345678", 100, START);
+        
+        // No sweep in between, as when a capture file is read in one go
+        let later = START + REASSEMBLY_TIMEOUT + Duration::from_secs(1);
+        assert!(table.add(key(), fragment(8, false), b"9", 60, later).is_none());
+        assert_eq!(table.stats.timeouts, 1);
+        assert_eq!(table.in_progress(), (1, 1));
+        
+        let (payload, wire_len) = table.add(key(), fragment(0, true), b"abcdefgh", 100, later).unwrap();
+        assert_eq!(payload, b"abcdefgh9");
+        assert_eq!(wire_len, 160);
+    }
+    
+    #[test]
+    fn fragment_header_after_other_extensions() {
+        // Hop-by-Hop (8 bytes), Destination Options (16 bytes), then Fragment
+        let mut payload = vec![IPV6_DESTINATION_OPTIONS, 0, 5, 2, 0, 0, 1, 0];
+        payload.extend([44, 1, 1, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        payload.extend([17, 0, 0x00, 0x11, 0, 0, 0, 42]);
+        payload.extend(b"data");
+        
+        let (next_header, rest) = skip_ipv6_extensions(IPV6_HOP_BY_HOP, &payload).unwrap();
+        assert_eq!(next_header, 44);
+        let (protocol, fragment, data) = parse_ipv6_fragment(rest).unwrap();
+        assert_eq!(protocol, 17);
+        assert_eq!((fragment.id, fragment.offset, fragment.more), (42, 16, true));
+        assert_eq!(data, b"data");
+        
+        // Transports pass straight through; a header running past the end doesn't
+        assert_eq!(skip_ipv6_extensions(6, b"tcp").unwrap(), (6, &b"tcp"[..]));
+        assert!(skip_ipv6_extensions(IPV6_ROUTING, &[6, 1, 0, 0, 0, 0, 0, 0]).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use maxminddb::{geoip2, Reader};
+
+use super::types::GeoLocation;
+
+// Cached lookups before the cache is cleared, to bound memory on busy links
+const MAX_CACHED_IPS: usize = 100_000;
+
+// Location lookups from a MaxMind GeoIP2/GeoLite2 or DB-IP .mmdb database
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+    cache: Mutex<HashMap<IpAddr, Option<Arc<GeoLocation>>>>,
+}
+
+impl GeoIp {
+    pub fn open(path: &Path) -> Result<Self> {
+        let reader = Reader::open_readfile(path)
+            .with_context(|| format!("Failed to open GeoIP database {}", path.display()))?;
+        
+        Ok(GeoIp {
+            reader,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+    
+    // Database type and build date, e.g. "GeoLite2-City (2024-05-14)"
+    pub fn description(&self) -> String {
+        let metadata = &self.reader.metadata;
+        match DateTime::from_timestamp(metadata.build_epoch as i64, 0) {
+            Some(built) => format!("{} ({})", metadata.database_type, built.format("%Y-%m-%d")),
+            None => metadata.database_type.clone(),
+        }
+    }
+    
+    // Country, city, continent and coordinates of an address, if the
+    // database knows it. Results, including misses, are cached per IP
+    pub fn lookup(&self, ip: IpAddr) -> Option<Arc<GeoLocation>> {
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(cached) = cache.get(&ip) {
+                return cached.clone();
+            }
+        }
+        
+        let location = self.reader.lookup::<geoip2::City>(ip).ok()
+            .and_then(|record| to_location(&record))
+            .map(Arc::new);
+        
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.len() >= MAX_CACHED_IPS {
+                cache.clear();
+            }
+            cache.insert(ip, location.clone());
+        }
+        
+        location
+    }
+}
+
+// City records are a superset of country records, so this reads both kinds
+// of database. Addresses without a country fall back to where the block is registered
+fn to_location(record: &geoip2::City) -> Option<GeoLocation> {
+    let country = record.country.as_ref()
+        .filter(|country| country.iso_code.is_some())
+        .or(record.registered_country.as_ref())?;
+    let iso_code = country.iso_code?;
+    
+    let english_name = |names: &Option<std::collections::BTreeMap<&str, &str>>| {
+        names.as_ref().and_then(|names| names.get("en")).map(|name| name.to_string())
+    };
+    
+    let location = record.location.as_ref();
+    
+    Some(GeoLocation {
+        country: iso_code.to_string(),
+        country_name: english_name(&country.names).unwrap_or_else(|| iso_code.to_string()),
+        city: record.city.as_ref().and_then(|city| english_name(&city.names)),
+        continent: record.continent.as_ref()
+            .and_then(|continent| english_name(&continent.names))
+            .unwrap_or_else(|| "Unknown".to_string()),
+        latitude: location.and_then(|location| location.latitude),
+        longitude: location.and_then(|location| location.longitude),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use maxminddb::geoip2::city;
+    
+    fn country<'a>(iso_code: Option<&'a str>, name: &'a str) -> city::Country<'a> {
+        city::Country {
+            geoname_id: None,
+            is_in_european_union: None,
+            iso_code,
+            names: Some(BTreeMap::from([("de", "Deutschland"), ("en", name)])),
+        }
+    }
+    
+    fn record<'a>(country: Option<city::Country<'a>>, registered_country: Option<city::Country<'a>>) -> geoip2::City<'a> {
+        geoip2::City {
+            city: Some(city::City { geoname_id: None, names: Some(BTreeMap::from([("en", "Berlin")])) }),
+            continent: None,
+            country,
+            location: Some(city::Location {
+                accuracy_radius: None,
+                latitude: Some(52.5),
+                longitude: Some(13.4),
+                metro_code: None,
+                time_zone: None,
+            }),
+            postal: None,
+            registered_country,
+            represented_country: None,
+            subdivisions: None,
+            traits: None,
+        }
+    }
+    
+    #[test]
+    fn english_names_and_coordinates() {
+        let location = to_location(&record(Some(country(Some("DE"), "Germany")), None)).unwrap();
+        assert_eq!((location.country.as_str(), location.country_name.as_str()), ("DE", "Germany"));
+        assert_eq!(location.city.as_deref(), Some("Berlin"));
+        assert_eq!(location.continent, "Unknown");
+        assert_eq!((location.latitude, location.longitude), (Some(52.5), Some(13.4)));
+    }
+    
+    #[test]
+    fn falls_back_to_the_registered_country() {
+        let registered = Some(country(Some("NL"), "Netherlands"));
+        let location = to_location(&record(Some(country(None, "Unknown")), registered.clone())).unwrap();
+        assert_eq!(location.country, "NL");
+        let location = to_location(&record(None, registered)).unwrap();
+        assert_eq!(location.country_name, "Netherlands");
+        
+        assert!(to_location(&record(None, None)).is_none());
+    }
+}
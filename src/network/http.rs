@@ -0,0 +1,365 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    time::Duration,
+};
+
+use super::types::{HttpRequest, HttpSession, HttpState, Timestamp};
+
+// Headers longer than this are abandoned rather than buffered further
+const MAX_HEAD_LEN: usize = 16 * 1024;
+// Requests per flow waiting for a response; pipelining rarely goes deeper
+const MAX_PENDING: usize = 32;
+const MAX_LOG: usize = 1_000;
+const MAX_SLOWEST: usize = 50;
+const MAX_HOSTS: usize = 10_000;
+
+const METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+
+// A complete request or response head
+#[derive(Debug, Clone)]
+pub enum HttpEvent {
+    Request(HttpRequest),
+    Response {
+        sent: Timestamp,            // When the request it answers was seen
+        request: HttpRequest,
+        status: u16,
+        content_length: Option<u64>,
+    },
+}
+
+impl HttpState {
+    // Feed a segment from either side of a TCP flow. Only a flow whose
+    // client opens with a request line is followed
+    pub fn feed(&mut self, from_client: bool, payload: &[u8], now: Timestamp) -> Option<HttpEvent> {
+        match self {
+            HttpState::NotHttp => return None,
+            HttpState::Unknown => {
+                // Servers that speak first (SMTP, SSH) are settled by the client's reply
+                if !from_client {
+                    return None;
+                }
+                if !is_request_start(payload) {
+                    *self = HttpState::NotHttp;
+                    return None;
+                }
+                *self = HttpState::Http(Box::default());
+            },
+            HttpState::Http(_) => {},
+        }
+        
+        let HttpState::Http(session) = self else { return None };
+        session.feed(from_client, payload, now)
+    }
+    
+    pub fn session(&self) -> Option<&HttpSession> {
+        match self {
+            HttpState::Http(session) => Some(session),
+            _ => None,
+        }
+    }
+}
+
+impl HttpSession {
+    // Heads are recognised at the start of a segment and collected until the
+    // blank line. Bodies are skipped, as is anything pipelined behind a head
+    fn feed(&mut self, from_client: bool, payload: &[u8], now: Timestamp) -> Option<HttpEvent> {
+        let head = if from_client { &mut self.client_head } else { &mut self.server_head };
+        if head.is_empty() {
+            let starts = if from_client { is_request_start(payload) } else { payload.starts_with(b"HTTP/1.") };
+            if !starts {
+                return None;
+            }
+        }
+        
+        head.extend_from_slice(payload);
+        let Some(end) = head.windows(4).position(|window| window == b"\r\n\r\n") else {
+            if head.len() > MAX_HEAD_LEN {
+                head.clear();
+            }
+            return None;
+        };
+        let text = String::from_utf8_lossy(&head[..end]).into_owned();
+        head.clear();
+        
+        if from_client {
+            let request = parse_request(&text)?;
+            self.requests += 1;
+            self.last_request = Some(request.clone());
+            if self.pending.len() >= MAX_PENDING {
+                self.pending.pop_front();
+            }
+            self.pending.push_back((now, request.clone()));
+            Some(HttpEvent::Request(request))
+        } else {
+            let (status, content_length) = parse_response(&text)?;
+            // 100 Continue and friends come ahead of the real response
+            if status < 200 {
+                return None;
+            }
+            
+            self.responses += 1;
+            self.last_status = Some(status);
+            self.response_bytes += content_length.unwrap_or(0);
+            if status >= 400 {
+                self.errors += 1;
+            }
+            
+            let (sent, request) = self.pending.pop_front()?;
+            Some(HttpEvent::Response { sent, request, status, content_length })
+        }
+    }
+}
+
+pub fn is_request_start(payload: &[u8]) -> bool {
+    METHODS.iter().any(|method| {
+        payload.len() > method.len() && payload.starts_with(method.as_bytes()) && payload[method.len()] == b' '
+    })
+}
+
+// Header value by case-insensitive name
+fn header<'a>(lines: &[&'a str], name: &str) -> Option<&'a str> {
+    lines.iter().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+// "GET /path HTTP/1.1" and its headers
+fn parse_request(text: &str) -> Option<HttpRequest> {
+    let lines: Vec<&str> = text.split("\r\n").collect();
+    let mut request_line = lines.first()?.split(' ');
+    let method = request_line.next()?;
+    let path = request_line.next()?;
+    if !request_line.next()?.starts_with("HTTP/1.") {
+        return None;
+    }
+    
+    Some(HttpRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        host: header(&lines[1..], "host").map(|host| host.to_lowercase()),
+        user_agent: header(&lines[1..], "user-agent").map(str::to_string),
+    })
+}
+
+// "HTTP/1.1 200 OK": the status and the body length, when given
+fn parse_response(text: &str) -> Option<(u16, Option<u64>)> {
+    let lines: Vec<&str> = text.split("\r\n").collect();
+    let status = lines.first()?.split(' ').nth(1)?.parse::<u16>().ok()?;
+    let content_length = header(&lines[1..], "content-length").and_then(|length| length.parse().ok());
+    Some((status, content_length))
+}
+
+// One request and the response to it
+#[derive(Debug, Clone)]
+pub struct HttpExchange {
+    pub time: Timestamp,            // When the request was seen
+    pub client: (IpAddr, u16),
+    pub server: (IpAddr, u16),
+    pub request: HttpRequest,
+    pub status: u16,
+    pub content_length: Option<u64>,
+    pub latency: Duration,          // Request to response head
+}
+
+// Requests, responses and their timing across all plain HTTP flows
+#[derive(Default)]
+pub struct HttpStats {
+    pub log: VecDeque<HttpExchange>,        // Most recent first
+    pub slowest: Vec<HttpExchange>,         // Slowest first
+    pub statuses: HashMap<u16, u64>,
+    pub methods: HashMap<String, u64>,
+    pub hosts: HashMap<String, u64>,        // Requests per Host header
+    pub requests: u64,
+    pub responses: u64,
+    pub latency_total: Duration,
+    pub latency_max: Duration,
+}
+
+impl HttpStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // Account for a request or response head captured at `now`
+    pub fn record(&mut self, event: HttpEvent, client: (IpAddr, u16), server: (IpAddr, u16), now: Timestamp) {
+        match event {
+            HttpEvent::Request(request) => {
+                self.requests += 1;
+                *self.methods.entry(request.method).or_default() += 1;
+                if let Some(host) = request.host {
+                    if self.hosts.len() >= MAX_HOSTS && !self.hosts.contains_key(&host) {
+                        self.hosts.clear();
+                    }
+                    *self.hosts.entry(host).or_default() += 1;
+                }
+            },
+            HttpEvent::Response { sent, request, status, content_length } => {
+                let latency = now.saturating_sub(sent);
+                self.responses += 1;
+                *self.statuses.entry(status).or_default() += 1;
+                self.latency_total += latency;
+                self.latency_max = self.latency_max.max(latency);
+                
+                let exchange = HttpExchange {
+                    time: sent,
+                    client,
+                    server,
+                    request,
+                    status,
+                    content_length,
+                    latency,
+                };
+                
+                // Keep the slowest few, in order
+                let idx = self.slowest.partition_point(|slow| slow.latency >= latency);
+                if idx < MAX_SLOWEST {
+                    self.slowest.insert(idx, exchange.clone());
+                    self.slowest.truncate(MAX_SLOWEST);
+                }
+                
+                self.log.push_front(exchange);
+                self.log.truncate(MAX_LOG);
+            },
+        }
+    }
+    
+    // Share of responses with a status in the given class, e.g. 4 for 4xx
+    pub fn status_class_rate(&self, class: u16) -> f64 {
+        if self.responses == 0 {
+            return 0.0;
+        }
+        let count: u64 = self.statuses.iter()
+            .filter(|(status, _)| **status / 100 == class)
+            .map(|(_, count)| count)
+            .sum();
+        count as f64 / self.responses as f64 * 100.0
+    }
+    
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.responses > 0).then(|| self.latency_total.div_f64(self.responses as f64))
+    }
+}
+
+// Reason phrase of the common status codes
+pub fn status_reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{at, ip, CLIENT, START};
+    
+    fn session(state: &HttpState) -> &HttpSession {
+        state.session().unwrap()
+    }
+    
+    fn response_to(event: Option<HttpEvent>) -> (String, u16) {
+        match event {
+            Some(HttpEvent::Response { request, status, .. }) => (request.path, status),
+            other => panic!("expected a response, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn latency_from_capture_timestamps() {
+        let client = (CLIENT, 50000);
+        let server = (ip("192.0.2.80"), 80);
+        let sent = START;
+        let answered = at(120);
+        
+        let mut state = HttpState::default();
+        let mut stats = HttpStats::new();
+        let request = state.feed(true, b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n", sent).unwrap();
+        stats.record(request, client, server, sent);
+        let response = state.feed(false, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", answered).unwrap();
+        stats.record(response, client, server, answered);
+        
+        assert_eq!(stats.log[0].latency, Duration::from_millis(120));
+        assert_eq!(stats.log[0].time, sent);
+        assert_eq!(stats.slowest[0].request.path, "/index.html");
+        assert_eq!(stats.hosts.get("example.com"), Some(&1));
+    }
+    
+    #[test]
+    fn request_head_split_across_segments() {
+        let mut state = HttpState::default();
+        assert!(state.feed(true, b"GET /split HTTP/1.1\r\nHo", START).is_none());
+        let Some(HttpEvent::Request(request)) = state.feed(true, b"st: example.com\r\nUser-Agent: curl/8.5\r\n\r\n", at(1)) else {
+            panic!("head not completed");
+        };
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/split"));
+        assert_eq!(request.host.as_deref(), Some("example.com"));
+        assert_eq!(request.user_agent.as_deref(), Some("curl/8.5"));
+        assert!(session(&state).client_head.is_empty());
+    }
+    
+    #[test]
+    fn continue_comes_before_the_real_response() {
+        let mut state = HttpState::default();
+        state.feed(true, b"POST /upload HTTP/1.1\r\nExpect: 100-continue\r\n\r\n", START);
+        assert!(state.feed(false, b"HTTP/1.1 100 Continue\r\n\r\n", at(10)).is_none());
+        // The body isn't a head
+        assert!(state.feed(true, b"{\"file\": 1}", at(20)).is_none());
+        
+        let response = state.feed(false, b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n", at(50));
+        assert_eq!(response_to(response), ("/upload".to_string(), 201));
+        assert_eq!((session(&state).requests, session(&state).responses), (1, 1));
+    }
+    
+    #[test]
+    fn pipelined_requests_are_answered_in_order() {
+        let mut state = HttpState::default();
+        state.feed(true, b"GET /first HTTP/1.1\r\n\r\n", START);
+        state.feed(true, b"GET /second HTTP/1.1\r\n\r\n", at(1));
+        assert_eq!(session(&state).pending.len(), 2);
+        
+        let first = state.feed(false, b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789", at(30));
+        assert_eq!(response_to(first), ("/first".to_string(), 200));
+        let second = state.feed(false, b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n", at(31));
+        assert_eq!(response_to(second), ("/second".to_string(), 404));
+        
+        let session = session(&state);
+        assert!(session.pending.is_empty());
+        assert_eq!((session.errors, session.response_bytes), (1, 10));
+    }
+    
+    #[test]
+    fn oversized_head_is_dropped() {
+        let mut state = HttpState::default();
+        let mut head = b"GET /big HTTP/1.1\r\nCookie: ".to_vec();
+        head.resize(MAX_HEAD_LEN + 1, b'a');
+        assert!(state.feed(true, &head, START).is_none());
+        assert!(session(&state).client_head.is_empty());
+        
+        // Its tail isn't mistaken for a request, and the next one is still seen
+        assert!(state.feed(true, b"aaaa\r\n\r\n", at(1)).is_none());
+        assert!(matches!(state.feed(true, b"GET /next HTTP/1.1\r\n\r\n", at(2)), Some(HttpEvent::Request(request)) if request.path == "/next"));
+        assert_eq!(session(&state).requests, 1);
+    }
+}
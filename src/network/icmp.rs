@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use super::types::{IcmpSession, IcmpType, QuotedPacket, Timestamp};
+
+// Echo requests without a reply after this long count as unanswered
+const ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+// Echo requests remembered per flow while waiting for their replies
+const MAX_PENDING_ECHOES: usize = 64;
+
+// ICMP (RFC 792) and ICMPv6 (RFC 4443) message types
+const V4_ECHO_REPLY: u8 = 0;
+const V4_UNREACHABLE: u8 = 3;
+const V4_SOURCE_QUENCH: u8 = 4;
+const V4_REDIRECT: u8 = 5;
+const V4_ECHO_REQUEST: u8 = 8;
+const V4_TIME_EXCEEDED: u8 = 11;
+const V4_PARAMETER_PROBLEM: u8 = 12;
+const V4_FRAGMENTATION_NEEDED: u8 = 4;     // Code of V4_UNREACHABLE
+
+const V6_UNREACHABLE: u8 = 1;
+const V6_PACKET_TOO_BIG: u8 = 2;
+const V6_TIME_EXCEEDED: u8 = 3;
+const V6_PARAMETER_PROBLEM: u8 = 4;
+const V6_ECHO_REQUEST: u8 = 128;
+const V6_ECHO_REPLY: u8 = 129;
+
+const HEADER_LEN: usize = 8;
+
+// A decoded ICMP or ICMPv6 message
+#[derive(Debug, Clone)]
+pub struct IcmpMessage {
+    pub icmp_type: IcmpType,
+    pub echo: Option<(u16, u16)>,       // Identifier and sequence number of echo requests and replies
+    pub mtu: Option<u32>,               // Next-hop MTU of Fragmentation Needed / Packet Too Big
+    pub about: Option<QuotedPacket>,    // Packet an error was sent in response to
+}
+
+impl IcmpMessage {
+    pub fn parse(payload: &[u8], v6: bool) -> Option<Self> {
+        if payload.len() < HEADER_LEN {
+            return None;
+        }
+        let icmp_type = IcmpType { v6, kind: payload[0], code: payload[1] };
+        
+        let echo = (icmp_type.is_echo_request() || icmp_type.is_echo_reply()).then(|| {
+            (u16::from_be_bytes([payload[4], payload[5]]), u16::from_be_bytes([payload[6], payload[7]]))
+        });
+        
+        let mtu = match (v6, icmp_type.kind, icmp_type.code) {
+            (false, V4_UNREACHABLE, V4_FRAGMENTATION_NEEDED) => Some(u16::from_be_bytes([payload[6], payload[7]]) as u32),
+            (true, V6_PACKET_TOO_BIG, _) => Some(u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]])),
+            _ => None,
+        };
+        
+        // Errors carry the start of the packet that caused them
+        let about = if icmp_type.is_error() {
+            parse_quoted(&payload[HEADER_LEN..])
+        } else {
+            None
+        };
+        
+        Some(IcmpMessage { icmp_type, echo, mtu, about })
+    }
+    
+    // Echoes and errors get a pseudo-flow in the connections table; the
+    // rest, like Neighbor Discovery, are only counted
+    pub fn has_flow(&self) -> bool {
+        self.echo.is_some() || self.icmp_type.is_error()
+    }
+}
+
+impl IcmpType {
+    pub fn is_echo_request(&self) -> bool {
+        self.kind == if self.v6 { V6_ECHO_REQUEST } else { V4_ECHO_REQUEST }
+    }
+    
+    pub fn is_echo_reply(&self) -> bool {
+        self.kind == if self.v6 { V6_ECHO_REPLY } else { V4_ECHO_REPLY }
+    }
+    
+    // Reports about another packet that couldn't be delivered as sent
+    pub fn is_error(&self) -> bool {
+        if self.v6 {
+            self.kind < 128
+        } else {
+            matches!(self.kind, V4_UNREACHABLE | V4_SOURCE_QUENCH | V4_REDIRECT | V4_TIME_EXCEEDED | V4_PARAMETER_PROBLEM)
+        }
+    }
+    
+    // Readable type and code, e.g. "Port unreachable"
+    pub fn name(&self) -> String {
+        let name = if self.v6 {
+            match (self.kind, self.code) {
+                (V6_UNREACHABLE, 0) => "No route",
+                (V6_UNREACHABLE, 1) => "Admin prohibited",
+                (V6_UNREACHABLE, 3) => "Address unreachable",
+                (V6_UNREACHABLE, 4) => "Port unreachable",
+                (V6_UNREACHABLE, 5 | 6) => "Source policy rejected",
+                (V6_UNREACHABLE, _) => "Unreachable",
+                (V6_PACKET_TOO_BIG, _) => "Packet too big",
+                (V6_TIME_EXCEEDED, 0) => "Hop limit exceeded",
+                (V6_TIME_EXCEEDED, _) => "Reassembly timeout",
+                (V6_PARAMETER_PROBLEM, _) => "Parameter problem",
+                (V6_ECHO_REQUEST, _) => "Echo request",
+                (V6_ECHO_REPLY, _) => "Echo reply",
+                (130..=132 | 143, _) => "Multicast listener",
+                (133, _) => "Router solicitation",
+                (134, _) => "Router advertisement",
+                (135, _) => "Neighbor solicitation",
+                (136, _) => "Neighbor advertisement",
+                (137, _) => "Redirect",
+                _ => return format!("ICMPv6 type {} code {}", self.kind, self.code),
+            }
+        } else {
+            match (self.kind, self.code) {
+                (V4_ECHO_REPLY, _) => "Echo reply",
+                (V4_UNREACHABLE, 0) => "Net unreachable",
+                (V4_UNREACHABLE, 1) => "Host unreachable",
+                (V4_UNREACHABLE, 2) => "Protocol unreachable",
+                (V4_UNREACHABLE, 3) => "Port unreachable",
+                (V4_UNREACHABLE, V4_FRAGMENTATION_NEEDED) => "Fragmentation needed",
+                (V4_UNREACHABLE, 9 | 10 | 13) => "Admin prohibited",
+                (V4_UNREACHABLE, _) => "Unreachable",
+                (V4_SOURCE_QUENCH, _) => "Source quench",
+                (V4_REDIRECT, _) => "Redirect",
+                (V4_ECHO_REQUEST, _) => "Echo request",
+                (9, _) => "Router advertisement",
+                (10, _) => "Router solicitation",
+                (V4_TIME_EXCEEDED, 0) => "TTL exceeded",
+                (V4_TIME_EXCEEDED, _) => "Reassembly timeout",
+                (V4_PARAMETER_PROBLEM, _) => "Parameter problem",
+                (13 | 14, _) => "Timestamp",
+                _ => return format!("ICMP type {} code {}", self.kind, self.code),
+            }
+        };
+        name.to_string()
+    }
+    
+    // Fits the Connections tab's state column
+    pub fn short_name(&self) -> &'static str {
+        match (self.v6, self.kind) {
+            (false, V4_UNREACHABLE) if self.code == V4_FRAGMENTATION_NEEDED => "FRAG NEEDED",
+            (false, V4_UNREACHABLE) | (true, V6_UNREACHABLE) => "UNREACHABLE",
+            (true, V6_PACKET_TOO_BIG) => "TOO BIG",
+            (false, V4_TIME_EXCEEDED) | (true, V6_TIME_EXCEEDED) => "TIME EXCEED",
+            (false, V4_REDIRECT) => "REDIRECT",
+            (false, V4_SOURCE_QUENCH) => "QUENCH",
+            (false, V4_PARAMETER_PROBLEM) | (true, V6_PARAMETER_PROBLEM) => "PARAM PROB",
+            _ if self.is_echo_request() || self.is_echo_reply() => "ECHO",
+            _ => "-",
+        }
+    }
+}
+
+// The IP header and first transport bytes quoted in an error
+fn parse_quoted(data: &[u8]) -> Option<QuotedPacket> {
+    let (protocol, dst, transport) = match *data.first()? >> 4 {
+        4 => {
+            let header_len = (data[0] & 0x0f) as usize * 4;
+            let dst: [u8; 4] = data.get(16..20)?.try_into().ok()?;
+            (data[9], IpAddr::V4(Ipv4Addr::from(dst)), data.get(header_len..))
+        },
+        6 => {
+            let dst: [u8; 16] = data.get(24..40)?.try_into().ok()?;
+            (*data.get(6)?, IpAddr::V6(Ipv6Addr::from(dst)), data.get(40..))
+        },
+        _ => return None,
+    };
+    
+    // TCP, UDP and SCTP start with the source and destination ports
+    let dst_port = match protocol {
+        6 | 17 | 132 => transport
+            .and_then(|transport| transport.get(2..4))
+            .map(|port| u16::from_be_bytes([port[0], port[1]])),
+        _ => None,
+    };
+    Some(QuotedPacket { dst, protocol, dst_port })
+}
+
+impl QuotedPacket {
+    // e.g. "203.0.113.7 udp/53"
+    pub fn label(&self) -> String {
+        let protocol = match self.protocol {
+            1 => "icmp",
+            6 => "tcp",
+            17 => "udp",
+            58 => "icmpv6",
+            132 => "sctp",
+            _ => return format!("{} proto {}", self.dst, self.protocol),
+        };
+        match self.dst_port {
+            Some(port) => format!("{} {}/{}", self.dst, protocol, port),
+            None => format!("{} {}", self.dst, protocol),
+        }
+    }
+}
+
+impl IcmpSession {
+    // Account for a message on this pseudo-flow, captured at `now`, pairing
+    // echo replies with their requests. Returns the round trip a reply
+    // completed and the number of requests that gave up waiting
+    pub fn record(&mut self, message: &IcmpMessage, now: Timestamp) -> (Option<Duration>, u64) {
+        let mut timed_out = self.expire(now);
+        
+        let mut rtt = None;
+        match message.echo {
+            Some((_, sequence)) if message.icmp_type.is_echo_request() => {
+                self.requests += 1;
+                if self.pending.len() >= MAX_PENDING_ECHOES {
+                    self.pending.pop_front();
+                    self.unanswered += 1;
+                    timed_out += 1;
+                }
+                self.pending.push_back((sequence, now));
+            },
+            Some((_, sequence)) => {
+                self.replies += 1;
+                let position = self.pending.iter().position(|(pending, _)| *pending == sequence);
+                if let Some((_, sent)) = position.and_then(|idx| self.pending.remove(idx)) {
+                    let sample = now.saturating_sub(sent);
+                    self.last_rtt = Some(sample);
+                    self.srtt = Some(match self.srtt {
+                        Some(smoothed) => smoothed * 7 / 8 + sample / 8,
+                        None => sample,
+                    });
+                    rtt = Some(sample);
+                }
+            },
+            None => {
+                self.errors += 1;
+                self.about = message.about;
+                if message.mtu.is_some() {
+                    self.mtu = message.mtu;
+                }
+            },
+        }
+        
+        self.last_message = Some(message.icmp_type);
+        (rtt, timed_out)
+    }
+    
+    // Give up on requests that waited ECHO_TIMEOUT for a reply, as of `now`
+    // on the capture's clock. Returns how many
+    pub fn expire(&mut self, now: Timestamp) -> u64 {
+        let mut timed_out = 0;
+        while self.pending.front().is_some_and(|(_, sent)| now.saturating_sub(*sent) >= ECHO_TIMEOUT) {
+            self.pending.pop_front();
+            timed_out += 1;
+        }
+        self.unanswered += timed_out;
+        timed_out
+    }
+}
+
+// Messages by type and code, and the echo round trips and errors seen
+#[derive(Default)]
+pub struct IcmpStats {
+    pub messages: HashMap<IcmpType, u64>,
+    pub echo_requests: u64,
+    pub echo_replies: u64,
+    pub errors: u64,
+    pub smallest_mtu: Option<u32>,      // From Fragmentation Needed / Packet Too Big
+    pub rtt_total: Duration,
+    pub rtt_samples: u64,
+    pub rtt_max: Duration,
+}
+
+impl IcmpStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn record(&mut self, message: &IcmpMessage, rtt: Option<Duration>) {
+        *self.messages.entry(message.icmp_type).or_default() += 1;
+        if message.icmp_type.is_echo_request() {
+            self.echo_requests += 1;
+        } else if message.icmp_type.is_echo_reply() {
+            self.echo_replies += 1;
+        } else if message.icmp_type.is_error() {
+            self.errors += 1;
+        }
+        if let Some(mtu) = message.mtu {
+            self.smallest_mtu = Some(self.smallest_mtu.map_or(mtu, |smallest| smallest.min(mtu)));
+        }
+        
+        if let Some(rtt) = rtt {
+            self.rtt_total += rtt;
+            self.rtt_samples += 1;
+            self.rtt_max = self.rtt_max.max(rtt);
+        }
+    }
+    
+    pub fn average_rtt(&self) -> Option<Duration> {
+        (self.rtt_samples > 0).then(|| self.rtt_total.div_f64(self.rtt_samples as f64))
+    }
+    
+    // Message types, most frequent first
+    pub fn top_messages(&self) -> Vec<(IcmpType, u64)> {
+        let mut messages: Vec<(IcmpType, u64)> = self.messages.iter().map(|(kind, count)| (*kind, *count)).collect();
+        messages.sort_by(|a, b| b.1.cmp(&a.1).then((a.0.v6, a.0.kind, a.0.code).cmp(&(b.0.v6, b.0.kind, b.0.code))));
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{at, START};
+    
+    fn echo(kind: u8, sequence: u8) -> IcmpMessage {
+        IcmpMessage::parse(&[kind, 0, 0, 0, 0, 1, 0, sequence], false).unwrap()
+    }
+    
+    #[test]
+    fn echo_rtt_from_capture_timestamps() {
+        let mut session = IcmpSession::default();
+        assert_eq!(session.record(&echo(V4_ECHO_REQUEST, 1), START), (None, 0));
+        let (rtt, timed_out) = session.record(&echo(V4_ECHO_REPLY, 1), at(25));
+        assert_eq!(rtt, Some(Duration::from_millis(25)));
+        assert_eq!(timed_out, 0);
+        assert_eq!(session.srtt, Some(Duration::from_millis(25)));
+    }
+    
+    #[test]
+    fn lone_ping_expires_without_further_traffic() {
+        let mut session = IcmpSession::default();
+        session.record(&echo(V4_ECHO_REQUEST, 1), START);
+        assert_eq!(session.expire(START + Duration::from_secs(1)), 0);
+        assert_eq!(session.expire(START + ECHO_TIMEOUT), 1);
+        assert_eq!(session.unanswered, 1);
+        assert!(session.pending.is_empty());
+        
+        // A reply after the timeout no longer gives an RTT
+        let (rtt, _) = session.record(&echo(V4_ECHO_REPLY, 1), START + ECHO_TIMEOUT);
+        assert_eq!(rtt, None);
+    }
+}
@@ -1,5 +1,24 @@
 pub mod types;
 pub mod capture;
+pub mod decode;
+pub mod flow;
+pub mod process;
+pub mod geoip;
+pub mod asn;
+pub mod dns;
+pub mod names;
+pub mod tls;
+pub mod http;
+pub mod services;
+pub mod detect;
+pub mod quic;
+pub mod oui;
+pub mod neighbors;
+pub mod icmp;
+pub mod fragment;
+pub mod recorder;
+#[cfg(test)]
+pub mod test_util;
 
 pub use types::*;
 pub use capture::*;
\ No newline at end of file
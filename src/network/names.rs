@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::dns::{DnsData, DnsMessage};
+
+// Names remembered per source before the table is cleared
+const MAX_NAMES: usize = 50_000;
+// Reverse lookups run in parallel so one slow resolver doesn't hold up the rest
+const PTR_WORKERS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PtrLookup {
+    Pending,
+    Found(String),
+    Failed,
+}
+
+// Host names for addresses, learned from DNS answers seen on the wire and,
+// when enabled, from reverse (PTR) lookups done in the background
+#[derive(Debug, Default)]
+pub struct HostNames {
+    passive: HashMap<IpAddr, String>,
+    ptr: HashMap<IpAddr, PtrLookup>,
+    lookups: Option<Sender<IpAddr>>,    // Queue of the PTR workers, when they are running
+}
+
+impl HostNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // Remember the name each address in a response was asked for by. The
+    // question is used rather than the end of a CNAME chain, since that is
+    // the name the client actually wanted
+    pub fn record_answers(&mut self, message: &DnsMessage) {
+        if !message.response || message.rcode != 0 {
+            return;
+        }
+        
+        for answer in &message.answers {
+            let ip = match answer.data {
+                DnsData::A(ipv4) => IpAddr::V4(ipv4),
+                DnsData::Aaaa(ipv6) => IpAddr::V6(ipv6),
+                _ => continue,
+            };
+            let name = message.questions.first().map_or(&answer.name, |question| &question.name);
+            
+            if self.passive.len() >= MAX_NAMES && !self.passive.contains_key(&ip) {
+                self.passive.clear();
+            }
+            self.passive.insert(ip, name.clone());
+        }
+    }
+    
+    // Best known name: one seen in DNS traffic, else the PTR record
+    pub fn name(&self, ip: IpAddr) -> Option<&str> {
+        if let Some(name) = self.passive.get(&ip) {
+            return Some(name.as_str());
+        }
+        match self.ptr.get(&ip) {
+            Some(PtrLookup::Found(name)) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+    
+    // Queue a reverse lookup for an address without a name. Never blocks
+    pub fn request(&mut self, ip: IpAddr) {
+        let Some(lookups) = &self.lookups else { return };
+        if self.passive.contains_key(&ip) || self.ptr.contains_key(&ip) {
+            return;
+        }
+        
+        if self.ptr.len() >= MAX_NAMES {
+            self.ptr.clear();
+        }
+        if lookups.send(ip).is_ok() {
+            self.ptr.insert(ip, PtrLookup::Pending);
+        }
+    }
+    
+    pub fn reverse_lookups(&self) -> bool {
+        self.lookups.is_some()
+    }
+    
+    // Names learned from DNS traffic and from PTR lookups
+    pub fn counts(&self) -> (usize, usize) {
+        let resolved = self.ptr.values()
+            .filter(|lookup| matches!(lookup, PtrLookup::Found(_)))
+            .count();
+        (self.passive.len(), resolved)
+    }
+}
+
+// Start the reverse lookup workers and route requests to them
+pub fn start_ptr_resolver(names: Arc<Mutex<HostNames>>, running: Arc<AtomicBool>) {
+    let (sender, receiver) = mpsc::channel();
+    let receiver = Arc::new(Mutex::new(receiver));
+    
+    if let Ok(mut names) = names.lock() {
+        names.lookups = Some(sender);
+    }
+    
+    for _ in 0..PTR_WORKERS {
+        let names = names.clone();
+        let receiver = receiver.clone();
+        let running = running.clone();
+        
+        thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                let Some(ip) = next_request(&receiver) else { continue };
+                
+                // getnameinfo blocks, possibly for seconds, so no lock is held meanwhile
+                let result = match dns_lookup::lookup_addr(&ip) {
+                    Ok(name) => PtrLookup::Found(name.trim_end_matches('.').to_lowercase()),
+                    Err(_) => PtrLookup::Failed,
+                };
+                
+                if let Ok(mut names) = names.lock() {
+                    names.ptr.insert(ip, result);
+                }
+            }
+        });
+    }
+}
+
+// Wait briefly for the next address, so quitting stays responsive
+fn next_request(receiver: &Mutex<Receiver<IpAddr>>) -> Option<IpAddr> {
+    let receiver = receiver.lock().ok()?;
+    match receiver.recv_timeout(Duration::from_millis(100)) {
+        Ok(ip) => Some(ip),
+        Err(RecvTimeoutError::Timeout) => None,
+        Err(RecvTimeoutError::Disconnected) => {
+            // The sender lives as long as the app; back off if it's gone
+            drop(receiver);
+            thread::sleep(Duration::from_millis(100));
+            None
+        },
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    net::{IpAddr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use pnet::packet::arp::{ArpHardwareTypes, ArpPacket};
+use pnet::packet::ethernet::EtherTypes;
+use pnet::util::MacAddr;
+
+use super::oui::OuiTable;
+use super::types::Timestamp;
+
+// Another MAC claiming an address within this long of the last claim means
+// two devices are answering for it; after it, the address changed hands
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(60);
+const MAX_NEIGHBORS: usize = 4096;
+const MAX_EVENTS: usize = 500;
+
+// ICMPv6 Neighbor Discovery messages and options (RFC 4861)
+const ROUTER_SOLICITATION: u8 = 133;
+const ROUTER_ADVERTISEMENT: u8 = 134;
+const NEIGHBOR_SOLICITATION: u8 = 135;
+const NEIGHBOR_ADVERTISEMENT: u8 = 136;
+const OPTION_SOURCE_LINK_ADDRESS: u8 = 1;
+const OPTION_TARGET_LINK_ADDRESS: u8 = 2;
+
+// Where a binding was learned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborProtocol {
+    Arp,
+    Ndp,
+}
+
+impl fmt::Display for NeighborProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            NeighborProtocol::Arp => "ARP",
+            NeighborProtocol::Ndp => "NDP",
+        })
+    }
+}
+
+// An address and the hardware address answering for it
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    pub interface: Arc<str>,
+    pub ip: IpAddr,
+    pub mac: MacAddr,
+    pub vendor: Option<Arc<str>>,
+    pub protocol: NeighborProtocol,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    pub packets: u64,
+    pub previous_mac: Option<MacAddr>,  // MAC the address had before the current one
+    pub macs: u32,                      // Distinct changes of MAC, plus one
+    pub duplicate: bool,                // Two MACs answered for it at once
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborEventKind {
+    DuplicateIp,    // Two MACs claiming the address at the same time
+    MacChanged,     // The address moved to another MAC
+}
+
+impl fmt::Display for NeighborEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            NeighborEventKind::DuplicateIp => "Duplicate IP",
+            NeighborEventKind::MacChanged => "MAC changed",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NeighborEvent {
+    pub time: Timestamp,
+    pub kind: NeighborEventKind,
+    pub interface: Arc<str>,
+    pub ip: IpAddr,
+    pub old_mac: MacAddr,
+    pub new_mac: MacAddr,
+    pub vendor: Option<Arc<str>>,   // Of the new MAC
+}
+
+// Live IP to MAC bindings per interface, learned from ARP and NDP
+pub struct NeighborTable {
+    pub neighbors: HashMap<(Arc<str>, IpAddr), Neighbor>,
+    pub events: VecDeque<NeighborEvent>,    // Most recent first
+    pub arp_packets: u64,
+    pub ndp_packets: u64,
+    pub duplicates: u64,
+    pub mac_changes: u64,
+    oui: OuiTable,
+}
+
+impl NeighborTable {
+    pub fn new(oui: OuiTable) -> Self {
+        NeighborTable {
+            neighbors: HashMap::new(),
+            events: VecDeque::new(),
+            arp_packets: 0,
+            ndp_packets: 0,
+            duplicates: 0,
+            mac_changes: 0,
+            oui,
+        }
+    }
+    
+    pub fn record(&mut self, interface: &Arc<str>, ip: IpAddr, mac: MacAddr, protocol: NeighborProtocol, now: Timestamp) {
+        match protocol {
+            NeighborProtocol::Arp => self.arp_packets += 1,
+            NeighborProtocol::Ndp => self.ndp_packets += 1,
+        }
+        
+        let key = (interface.clone(), ip);
+        if let Some(neighbor) = self.neighbors.get_mut(&key) {
+            if neighbor.mac != mac {
+                let kind = if now.saturating_sub(neighbor.last_seen) < DUPLICATE_WINDOW {
+                    self.duplicates += 1;
+                    neighbor.duplicate = true;
+                    NeighborEventKind::DuplicateIp
+                } else {
+                    self.mac_changes += 1;
+                    NeighborEventKind::MacChanged
+                };
+                
+                let vendor = self.oui.lookup(mac);
+                self.events.push_front(NeighborEvent {
+                    time: now,
+                    kind,
+                    interface: interface.clone(),
+                    ip,
+                    old_mac: neighbor.mac,
+                    new_mac: mac,
+                    vendor: vendor.clone(),
+                });
+                self.events.truncate(MAX_EVENTS);
+                
+                neighbor.previous_mac = Some(neighbor.mac);
+                neighbor.mac = mac;
+                neighbor.vendor = vendor;
+                neighbor.macs += 1;
+            }
+            neighbor.protocol = protocol;
+            neighbor.last_seen = now;
+            neighbor.packets += 1;
+            return;
+        }
+        
+        // Make room by forgetting the longest silent address
+        if self.neighbors.len() >= MAX_NEIGHBORS {
+            let oldest = self.neighbors.iter()
+                .min_by_key(|(_, neighbor)| neighbor.last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.neighbors.remove(&oldest);
+            }
+        }
+        
+        self.neighbors.insert(key, Neighbor {
+            interface: interface.clone(),
+            ip,
+            mac,
+            vendor: self.oui.lookup(mac),
+            protocol,
+            first_seen: now,
+            last_seen: now,
+            packets: 1,
+            previous_mac: None,
+            macs: 1,
+            duplicate: false,
+        });
+    }
+}
+
+// The binding an ARP request or reply announces for its sender. Probes,
+// sent from 0.0.0.0 while checking an address is free, announce nothing
+pub fn parse_arp(payload: &[u8]) -> Option<(IpAddr, MacAddr)> {
+    let arp = ArpPacket::new(payload)?;
+    if arp.get_hardware_type() != ArpHardwareTypes::Ethernet || arp.get_protocol_type() != EtherTypes::Ipv4 {
+        return None;
+    }
+    let ip = arp.get_sender_proto_addr();
+    let mac = arp.get_sender_hw_addr();
+    if ip.is_unspecified() || !is_unicast(mac) {
+        return None;
+    }
+    Some((IpAddr::V4(ip), mac))
+}
+
+// The binding in a Neighbor Discovery message: its sender's, carried in a
+// source link-layer option, or for advertisements the target's
+pub fn parse_ndp(src_ip: IpAddr, icmp: &[u8]) -> Option<(IpAddr, MacAddr)> {
+    let (ip, options, wanted) = match *icmp.first()? {
+        ROUTER_SOLICITATION => (src_ip, icmp.get(8..)?, OPTION_SOURCE_LINK_ADDRESS),
+        ROUTER_ADVERTISEMENT => (src_ip, icmp.get(16..)?, OPTION_SOURCE_LINK_ADDRESS),
+        NEIGHBOR_SOLICITATION => (src_ip, icmp.get(24..)?, OPTION_SOURCE_LINK_ADDRESS),
+        NEIGHBOR_ADVERTISEMENT => {
+            let target: [u8; 16] = icmp.get(8..24)?.try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(target)), icmp.get(24..)?, OPTION_TARGET_LINK_ADDRESS)
+        },
+        _ => return None,
+    };
+    // Duplicate address detection solicits from ::
+    if ip.is_unspecified() {
+        return None;
+    }
+    
+    // Options are type, length in units of 8 bytes, then data
+    let mut rest = options;
+    while rest.len() >= 8 {
+        let length = rest[1] as usize * 8;
+        if length == 0 || length > rest.len() {
+            return None;
+        }
+        if rest[0] == wanted {
+            let mac = MacAddr::new(rest[2], rest[3], rest[4], rest[5], rest[6], rest[7]);
+            return is_unicast(mac).then_some((ip, mac));
+        }
+        rest = &rest[length..];
+    }
+    None
+}
+
+fn is_unicast(mac: MacAddr) -> bool {
+    mac.0 & 0x01 == 0 && mac != MacAddr::zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{at, interface, ip, START};
+    
+    const HOST: MacAddr = MacAddr(0x00, 0x11, 0x22, 0x33, 0x44, 0x55);
+    const OTHER: MacAddr = MacAddr(0x00, 0x66, 0x77, 0x88, 0x99, 0xaa);
+    
+    fn arp(operation: u8, sender_mac: MacAddr, sender_ip: [u8; 4]) -> Vec<u8> {
+        let mut packet = vec![0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, operation];
+        packet.extend(sender_mac.octets());
+        packet.extend(sender_ip);
+        packet.extend([0; 6]);
+        packet.extend([10, 0, 0, 1]);
+        packet
+    }
+    
+    // An ICMPv6 message: type, zero code and checksum, the fixed body, then options
+    fn ndp(kind: u8, body: &[u8], options: &[u8]) -> Vec<u8> {
+        let mut message = vec![kind, 0, 0, 0];
+        message.extend_from_slice(body);
+        message.extend_from_slice(options);
+        message
+    }
+    
+    fn link_option(kind: u8, mac: MacAddr) -> Vec<u8> {
+        let mut option = vec![kind, 1];
+        option.extend(mac.octets());
+        option
+    }
+    
+    fn target(address: &str) -> Vec<u8> {
+        let IpAddr::V6(address) = ip(address) else { unreachable!() };
+        let mut body = vec![0; 4];
+        body.extend(address.octets());
+        body
+    }
+    
+    fn table() -> NeighborTable {
+        NeighborTable::new(OuiTable::load(None).unwrap())
+    }
+    
+    #[test]
+    fn arp_announces_its_sender() {
+        assert_eq!(parse_arp(&arp(2, HOST, [10, 0, 0, 7])), Some((ip("10.0.0.7"), HOST)));
+        assert_eq!(parse_arp(&arp(1, HOST, [10, 0, 0, 7])), Some((ip("10.0.0.7"), HOST)));
+        assert_eq!(parse_arp(&arp(2, HOST, [10, 0, 0, 7])[..20]), None);
+    }
+    
+    #[test]
+    fn arp_probes_and_non_unicast_senders_are_ignored() {
+        assert_eq!(parse_arp(&arp(1, HOST, [0, 0, 0, 0])), None);
+        assert_eq!(parse_arp(&arp(2, MacAddr(0x01, 0x00, 0x5e, 0x00, 0x00, 0x01), [10, 0, 0, 7])), None);
+        assert_eq!(parse_arp(&arp(2, MacAddr::zero(), [10, 0, 0, 7])), None);
+    }
+    
+    #[test]
+    fn ndp_bindings_by_message_type() {
+        let source = link_option(OPTION_SOURCE_LINK_ADDRESS, HOST);
+        let sender = ip("fe80::1");
+        
+        let solicitation = ndp(ROUTER_SOLICITATION, &[0; 4], &source);
+        assert_eq!(parse_ndp(sender, &solicitation), Some((sender, HOST)));
+        let advertisement = ndp(ROUTER_ADVERTISEMENT, &[0; 12], &source);
+        assert_eq!(parse_ndp(sender, &advertisement), Some((sender, HOST)));
+        let solicitation = ndp(NEIGHBOR_SOLICITATION, &target("fe80::2"), &source);
+        assert_eq!(parse_ndp(sender, &solicitation), Some((sender, HOST)));
+        
+        // Advertisements bind their target, skipping options they don't need
+        let mut options = link_option(OPTION_SOURCE_LINK_ADDRESS, OTHER);
+        options.extend(link_option(OPTION_TARGET_LINK_ADDRESS, HOST));
+        let advertisement = ndp(NEIGHBOR_ADVERTISEMENT, &target("2001:db8::7"), &options);
+        assert_eq!(parse_ndp(sender, &advertisement), Some((ip("2001:db8::7"), HOST)));
+        
+        // Echo requests carry no binding
+        assert_eq!(parse_ndp(sender, &ndp(128, &[0; 4], &source)), None);
+    }
+    
+    #[test]
+    fn malformed_ndp_options() {
+        let sender = ip("fe80::1");
+        
+        // A zero length would never advance
+        let mut option = link_option(OPTION_SOURCE_LINK_ADDRESS, HOST);
+        option[1] = 0;
+        assert_eq!(parse_ndp(sender, &ndp(ROUTER_SOLICITATION, &[0; 4], &option)), None);
+        
+        // Claims 16 bytes with only 8 there
+        option[1] = 2;
+        assert_eq!(parse_ndp(sender, &ndp(ROUTER_SOLICITATION, &[0; 4], &option)), None);
+        
+        // Body cut short before the options
+        assert_eq!(parse_ndp(sender, &ndp(NEIGHBOR_ADVERTISEMENT, &[0; 10], &[])), None);
+    }
+    
+    #[test]
+    fn duplicate_address_detection_is_ignored() {
+        let source = link_option(OPTION_SOURCE_LINK_ADDRESS, HOST);
+        let solicitation = ndp(NEIGHBOR_SOLICITATION, &target("fe80::2"), &source);
+        assert_eq!(parse_ndp(ip("::"), &solicitation), None);
+    }
+    
+    #[test]
+    fn second_mac_inside_the_window_is_a_duplicate() {
+        let mut table = table();
+        let address = ip("10.0.0.7");
+        table.record(&interface(), address, HOST, NeighborProtocol::Arp, START);
+        table.record(&interface(), address, OTHER, NeighborProtocol::Arp, START + DUPLICATE_WINDOW - Duration::from_secs(1));
+        
+        assert_eq!((table.duplicates, table.mac_changes), (1, 0));
+        assert_eq!(table.events[0].kind, NeighborEventKind::DuplicateIp);
+        assert_eq!((table.events[0].old_mac, table.events[0].new_mac), (HOST, OTHER));
+        let neighbor = &table.neighbors[&(interface(), address)];
+        assert!(neighbor.duplicate);
+        assert_eq!((neighbor.mac, neighbor.previous_mac, neighbor.macs), (OTHER, Some(HOST), 2));
+    }
+    
+    #[test]
+    fn second_mac_after_the_window_is_a_change() {
+        let mut table = table();
+        let address = ip("10.0.0.7");
+        table.record(&interface(), address, HOST, NeighborProtocol::Arp, START);
+        table.record(&interface(), address, OTHER, NeighborProtocol::Arp, START + DUPLICATE_WINDOW);
+        
+        assert_eq!((table.duplicates, table.mac_changes), (0, 1));
+        assert_eq!(table.events[0].kind, NeighborEventKind::MacChanged);
+        assert_eq!(table.events[0].time, START + DUPLICATE_WINDOW);
+        let neighbor = &table.neighbors[&(interface(), address)];
+        assert!(!neighbor.duplicate);
+        assert_eq!((neighbor.first_seen, neighbor.last_seen), (START, START + DUPLICATE_WINDOW));
+    }
+    
+    #[test]
+    fn longest_silent_address_is_forgotten_when_full() {
+        let mut table = table();
+        let address = |n: usize| IpAddr::V4([10, 1, (n >> 8) as u8, n as u8].into());
+        for n in 0..MAX_NEIGHBORS {
+            table.record(&interface(), address(n), HOST, NeighborProtocol::Arp, at(n as u64));
+        }
+        // The first one speaks up again, leaving the second as the oldest
+        table.record(&interface(), address(0), HOST, NeighborProtocol::Arp, at(MAX_NEIGHBORS as u64));
+        table.record(&interface(), ip("10.2.0.1"), HOST, NeighborProtocol::Arp, at(MAX_NEIGHBORS as u64 + 1));
+        
+        assert_eq!(table.neighbors.len(), MAX_NEIGHBORS);
+        assert!(table.neighbors.contains_key(&(interface(), address(0))));
+        assert!(!table.neighbors.contains_key(&(interface(), address(1))));
+        assert!(table.neighbors.contains_key(&(interface(), ip("10.2.0.1"))));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use pnet::util::MacAddr;
+
+// A few well-known prefixes, enough to spot virtual machines, routers and
+// the usual devices without a registry file
+const DEFAULT_VENDORS: [([u8; 3], &str); 37] = [
+    ([0x00, 0x00, 0x0c], "Cisco"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x04, 0x4b], "NVIDIA"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x05, 0x85], "Juniper"),
+    ([0x00, 0x09, 0x0f], "Fortinet"),
+    ([0x00, 0x0c, 0x29], "VMware"),
+    ([0x00, 0x0d, 0xb9], "PC Engines"),
+    ([0x00, 0x11, 0x32], "Synology"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x15, 0x5d], "Microsoft Hyper-V"),
+    ([0x00, 0x16, 0x3e], "Xen"),
+    ([0x00, 0x17, 0x88], "Philips Lighting"),
+    ([0x00, 0x1a, 0x11], "Google"),
+    ([0x00, 0x1b, 0x17], "Palo Alto Networks"),
+    ([0x00, 0x1c, 0x42], "Parallels"),
+    ([0x00, 0x25, 0x90], "Supermicro"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0xe0, 0x4c], "Realtek"),
+    ([0x02, 0x42, 0xac], "Docker"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x18, 0xb4, 0x30], "Nest Labs"),
+    ([0x24, 0xa4, 0x3c], "Ubiquiti"),
+    ([0x28, 0xcf, 0xe9], "Apple"),
+    ([0x2c, 0xcf, 0x67], "Raspberry Pi"),
+    ([0x3c, 0x22, 0xfb], "Apple"),
+    ([0x3c, 0xfd, 0xfe], "Intel"),
+    ([0x52, 0x54, 0x00], "QEMU/KVM"),
+    ([0x78, 0x8a, 0x20], "Ubiquiti"),
+    ([0xac, 0x1f, 0x6b], "Supermicro"),
+    ([0xb4, 0xfb, 0xe4], "Ubiquiti"),
+    ([0xb8, 0x27, 0xeb], "Raspberry Pi"),
+    ([0xd8, 0x3a, 0xdd], "Raspberry Pi"),
+    ([0xdc, 0xa6, 0x32], "Raspberry Pi"),
+    ([0xe4, 0x5f, 0x01], "Raspberry Pi"),
+    ([0xf0, 0x18, 0x98], "Apple"),
+    ([0xfc, 0xec, 0xda], "Ubiquiti"),
+];
+
+// Vendors of MAC address blocks (OUIs)
+pub struct OuiTable {
+    vendors: HashMap<[u8; 3], Arc<str>>,
+    random: Arc<str>,
+}
+
+impl OuiTable {
+    // The built-in prefixes, with a registry file on top: Wireshark's manuf,
+    // the IEEE oui.txt, or the IEEE oui.csv
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut table = OuiTable {
+            vendors: DEFAULT_VENDORS.iter()
+                .map(|(prefix, vendor)| (*prefix, Arc::from(*vendor)))
+                .collect(),
+            random: Arc::from("(random)"),
+        };
+        
+        if let Some(path) = path {
+            let before = table.vendors.len();
+            let result = if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+                table.read_csv(path)
+            } else {
+                table.read_text(path)
+            };
+            result.with_context(|| format!("Failed to read OUI database {}", path.display()))?;
+            if table.vendors.len() == before {
+                bail!("No OUI entries found in {}", path.display());
+            }
+        }
+        
+        Ok(table)
+    }
+    
+    // "Registry,Assignment,Organization Name,Organization Address"
+    fn read_csv(&mut self, path: &Path) -> Result<()> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .from_path(path)?;
+        for record in reader.records() {
+            let record = record?;
+            if let (Some(prefix), Some(vendor)) = (record.get(1).and_then(parse_prefix), record.get(2)) {
+                self.vendors.insert(prefix, Arc::from(vendor.trim()));
+            }
+        }
+        Ok(())
+    }
+    
+    // "00:00:0C<tab>Cisco<tab>Cisco Systems, Inc" (manuf) or
+    // "00-00-0C   (hex)<tabs>CISCO SYSTEMS, INC." (oui.txt); the last field
+    // is the fullest name. Longer /28 and /36 blocks are skipped
+    fn read_text(&mut self, path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path)?;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let Some(prefix) = line.split_whitespace().next().and_then(parse_prefix) else { continue };
+            let fields: Vec<&str> = line.split('\t')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .collect();
+            if let [_, .., vendor] = fields.as_slice() {
+                self.vendors.insert(prefix, Arc::from(*vendor));
+            }
+        }
+        Ok(())
+    }
+    
+    // Vendor of the block an address belongs to. Locally administered
+    // addresses, like the randomized ones phones use, belong to nobody
+    pub fn lookup(&self, mac: MacAddr) -> Option<Arc<str>> {
+        if let Some(vendor) = self.vendors.get(&[mac.0, mac.1, mac.2]) {
+            return Some(vendor.clone());
+        }
+        (mac.0 & 0x02 != 0).then(|| self.random.clone())
+    }
+}
+
+// Six hex digits, with or without ':' / '-' separators
+fn parse_prefix(text: &str) -> Option<[u8; 3]> {
+    let digits: String = text.chars().filter(|c| *c != ':' && *c != '-').collect();
+    if digits.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(&digits, 16).ok()?;
+    let [_, a, b, c] = value.to_be_bytes();
+    Some([a, b, c])
+}
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::flow::FlowTable;
+use super::types::{BasicProtocolType, ConnectionId, FlowSide, ProcessInfo, ProcessOwner};
+
+// How often the socket tables are re-read
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Socket tables under <proc root>/net
+const SOCKET_TABLES: [(&str, SocketProtocol); 4] = [
+    ("tcp", SocketProtocol::Tcp),
+    ("tcp6", SocketProtocol::Tcp),
+    ("udp", SocketProtocol::Udp),
+    ("udp6", SocketProtocol::Udp),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SocketProtocol {
+    Tcp,
+    Udp,
+}
+
+type Endpoint = (IpAddr, u16);
+// Sockets bound to one port, by local address
+type BoundSockets = Vec<(IpAddr, Arc<ProcessInfo>)>;
+
+// Which process owns which socket, as of the last refresh
+#[derive(Debug, Default)]
+pub struct SocketTable {
+    connected: HashMap<(SocketProtocol, Endpoint, Endpoint), Arc<ProcessInfo>>,     // Keyed by local and remote end
+    bound: HashMap<(SocketProtocol, u16), BoundSockets>,                             // Listening / unconnected, by local port
+}
+
+impl SocketTable {
+    // Find the process on either end of a flow. Connected sockets are an exact
+    // match; listening and unconnected UDP sockets match on the local port
+    pub fn owner(&self, id: &ConnectionId) -> Option<ProcessOwner> {
+        let protocol = match id.protocol.basic {
+            BasicProtocolType::TCP => SocketProtocol::Tcp,
+            BasicProtocolType::UDP => SocketProtocol::Udp,
+            _ => return None,
+        };
+        
+        for side in [FlowSide::A, FlowSide::B] {
+            let local = id.endpoint(side);
+            let remote = id.endpoint(side.other());
+            if let Some(info) = self.connected.get(&(protocol, local, remote)) {
+                return Some(ProcessOwner { info: info.clone(), side });
+            }
+        }
+        
+        for side in [FlowSide::A, FlowSide::B] {
+            let (ip, port) = id.endpoint(side);
+            let Some(candidates) = self.bound.get(&(protocol, port)) else { continue };
+            
+            // Sockets bound to the wildcard address take traffic for any local address
+            let found = candidates.iter()
+                .find(|(bound_ip, _)| *bound_ip == ip)
+                .or_else(|| candidates.iter().find(|(bound_ip, _)| bound_ip.is_unspecified()));
+            if let Some((_, info)) = found {
+                return Some(ProcessOwner { info: info.clone(), side });
+            }
+        }
+        
+        None
+    }
+}
+
+// Reads sockets and their owners from a procfs tree. The root is a
+// parameter so a container's or a fake /proc can be used
+#[derive(Debug)]
+pub struct ProcessResolver {
+    root: PathBuf,
+    users: HashMap<u32, String>,
+}
+
+impl ProcessResolver {
+    // None when the root has no socket tables, e.g. when not on Linux
+    pub fn new(root: &Path) -> Option<Self> {
+        if !root.join("net").join("tcp").is_file() {
+            return None;
+        }
+        
+        // User names come from the same system as the processes
+        let system_root = root.parent().unwrap_or(root);
+        Some(ProcessResolver {
+            root: root.to_path_buf(),
+            users: read_users(&system_root.join("etc").join("passwd")),
+        })
+    }
+    
+    // Read every socket table and map the sockets to their processes
+    pub fn snapshot(&self) -> SocketTable {
+        let inodes = self.socket_inodes();
+        let mut processes: HashMap<u32, Arc<ProcessInfo>> = HashMap::new();
+        let mut table = SocketTable::default();
+        
+        for (file, protocol) in SOCKET_TABLES {
+            let Ok(contents) = fs::read_to_string(self.root.join("net").join(file)) else { continue };
+            
+            for socket in contents.lines().skip(1).filter_map(parse_socket_line) {
+                let Some(&pid) = inodes.get(&socket.inode) else { continue };
+                
+                let info = processes.entry(pid)
+                    .or_insert_with(|| Arc::new(self.process_info(pid, socket.uid)))
+                    .clone();
+                
+                if socket.remote.1 == 0 {
+                    table.bound.entry((protocol, socket.local.1)).or_default().push((socket.local.0, info));
+                } else {
+                    table.connected.insert((protocol, socket.local, socket.remote), info);
+                }
+            }
+        }
+        
+        table
+    }
+    
+    // Socket inode -> pid, from the fd links of every process we can read
+    fn socket_inodes(&self) -> HashMap<u64, u32> {
+        let mut inodes = HashMap::new();
+        let Ok(entries) = fs::read_dir(&self.root) else { return inodes };
+        
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else { continue };
+            // Other users' processes are unreadable without privileges
+            let Ok(fds) = fs::read_dir(entry.path().join("fd")) else { continue };
+            
+            for fd in fds.flatten() {
+                let Ok(target) = fs::read_link(fd.path()) else { continue };
+                let inode = target.to_str()
+                    .and_then(|target| target.strip_prefix("socket:["))
+                    .and_then(|target| target.strip_suffix(']'))
+                    .and_then(|inode| inode.parse::<u64>().ok());
+                if let Some(inode) = inode {
+                    inodes.insert(inode, pid);
+                }
+            }
+        }
+        
+        inodes
+    }
+    
+    fn process_info(&self, pid: u32, uid: u32) -> ProcessInfo {
+        let name = fs::read_to_string(self.root.join(pid.to_string()).join("comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_else(|_| format!("pid {}", pid));
+        let user = self.users.get(&uid).cloned().unwrap_or_else(|| uid.to_string());
+        
+        ProcessInfo { pid, name, user }
+    }
+}
+
+// One row of /proc/net/{tcp,udp}[6]
+struct SocketEntry {
+    local: Endpoint,
+    remote: Endpoint,
+    uid: u32,
+    inode: u64,
+}
+
+// "sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ..."
+fn parse_socket_line(line: &str) -> Option<SocketEntry> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 10 {
+        return None;
+    }
+    
+    let inode = fields[9].parse::<u64>().ok()?;
+    // Sockets in TIME_WAIT and the like no longer belong to anyone
+    if inode == 0 {
+        return None;
+    }
+    
+    Some(SocketEntry {
+        local: parse_socket_address(fields[1])?,
+        remote: parse_socket_address(fields[2])?,
+        uid: fields[7].parse().ok()?,
+        inode,
+    })
+}
+
+// "0100007F:0035": the address as 32-bit words in host byte order, then the port
+fn parse_socket_address(field: &str) -> Option<Endpoint> {
+    let (address, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    
+    let mut bytes = Vec::with_capacity(16);
+    for chunk in address.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => {
+            let octets: [u8; 16] = bytes.try_into().ok()?;
+            let ipv6 = Ipv6Addr::from(octets);
+            // IPv4 traffic on dual-stack sockets shows up as ::ffff:a.b.c.d
+            match ipv6.to_ipv4_mapped() {
+                Some(ipv4) => IpAddr::V4(ipv4),
+                None => IpAddr::V6(ipv6),
+            }
+        },
+        _ => return None,
+    };
+    
+    Some((ip, port))
+}
+
+// uid -> user name from a passwd file
+fn read_users(path: &Path) -> HashMap<u32, String> {
+    let Ok(contents) = fs::read_to_string(path) else { return HashMap::new() };
+    
+    contents.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+// Periodically re-read the socket tables and tag new flows with their process
+pub fn start_process_monitor(resolver: ProcessResolver, connections: Arc<Mutex<FlowTable>>, running: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            let sockets = resolver.snapshot();
+            if let Ok(mut conns) = connections.lock() {
+                conns.attribute_processes(&sockets);
+            }
+            
+            // Sleep in short steps so quitting stays responsive
+            let next_refresh = Instant::now() + REFRESH_INTERVAL;
+            while running.load(Ordering::Relaxed) && Instant::now() < next_refresh {
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    });
+}
+
+// procfs prints addresses in host byte order; these are as a little-endian kernel writes them
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use crate::network::test_util::{basic, connection, ip};
+    use crate::network::types::{PacketType, QUIC_SERVICE};
+    
+    const TCP_HEADER: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode";
+    
+    // A throwaway system root with /etc/passwd and a /proc holding the given socket tables and processes
+    struct FakeRoot(PathBuf);
+    
+    impl FakeRoot {
+        fn new(name: &str, tables: &[(&str, &[&str])], processes: &[(u32, &str, &[u64])]) -> Self {
+            let root = std::env::temp_dir().join(format!("rnet-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            let proc_root = root.join("proc");
+            fs::create_dir_all(proc_root.join("net")).unwrap();
+            fs::create_dir_all(root.join("etc")).unwrap();
+            fs::write(root.join("etc").join("passwd"), "root:x:0:0::/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\n").unwrap();
+            
+            for (file, rows) in tables {
+                let mut contents = format!("{}\n", TCP_HEADER);
+                for row in *rows {
+                    contents.push_str(row);
+                    contents.push('\n');
+                }
+                fs::write(proc_root.join("net").join(file), contents).unwrap();
+            }
+            
+            for (pid, comm, inodes) in processes {
+                let dir = proc_root.join(pid.to_string());
+                fs::create_dir_all(dir.join("fd")).unwrap();
+                fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+                for (fd, inode) in inodes.iter().enumerate() {
+                    symlink(format!("socket:[{}]", inode), dir.join("fd").join(fd.to_string())).unwrap();
+                }
+                // Not every fd is a socket
+                symlink("/dev/null", dir.join("fd").join("99")).unwrap();
+            }
+            
+            FakeRoot(root)
+        }
+        
+        fn proc_root(&self) -> PathBuf {
+            self.0.join("proc")
+        }
+    }
+    
+    impl Drop for FakeRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+    
+    fn socket_row(local: &str, remote: &str, uid: u32, inode: u64) -> String {
+        format!("   0: {} {} 01 00000000:00000000 00:00000000 00000000 {:>5}        0 {} 1 0000000000000000 20 4 30 10 -1", local, remote, uid, inode)
+    }
+    
+    fn flow(protocol: BasicProtocolType, src: Endpoint, dst: Endpoint) -> ConnectionId {
+        connection(basic(protocol), src, dst)
+    }
+    
+    #[test]
+    fn parses_ipv4_addresses() {
+        assert_eq!(parse_socket_address("0100007F:0035"), Some((ip("127.0.0.1"), 53)));
+        assert_eq!(parse_socket_address("00000000:0000"), Some((ip("0.0.0.0"), 0)));
+        assert_eq!(parse_socket_address("0100007F"), None);
+        assert_eq!(parse_socket_address("0100007G:0035"), None);
+    }
+    
+    #[test]
+    fn parses_byte_swapped_ipv6_words() {
+        // Each 32-bit word is printed in host order, not the whole address
+        assert_eq!(parse_socket_address("B80D0120000000000000000001000000:01BB"), Some((ip("2001:db8::1"), 443)));
+        assert_eq!(parse_socket_address("00000000000000000000000001000000:0016"), Some((ip("::1"), 22)));
+        // Dual-stack sockets carrying IPv4 match IPv4 flows
+        assert_eq!(parse_socket_address("0000000000000000FFFF0000010200C0:0050"), Some((ip("192.0.2.1"), 80)));
+        assert_eq!(parse_socket_address("B80D01200000:01BB"), None);
+    }
+    
+    #[test]
+    fn skips_sockets_without_an_inode() {
+        assert!(parse_socket_line(&socket_row("0100007F:0035", "0100007F:C350", 0, 0)).is_none());
+        assert!(parse_socket_line(TCP_HEADER).is_none());
+    }
+    
+    #[test]
+    fn attributes_connected_and_listening_sockets() {
+        let connected = socket_row("0100000A:C350", "010200C0:01BB", 1000, 1111);
+        let listening = socket_row("00000000:0016", "00000000:0000", 0, 2222);
+        let connected_v6 = socket_row("B80D0120000000000000000001000000:D431", "B80D0120000000000000000002000000:01BB", 1000, 3333);
+        let dns = socket_row("3500007F:0035", "00000000:0000", 4242, 4444);
+        let root = FakeRoot::new(
+            "proc-attribution",
+            &[
+                ("tcp", &[connected.as_str(), listening.as_str()]),
+                ("tcp6", &[connected_v6.as_str()]),
+                ("udp", &[dns.as_str()]),
+            ],
+            &[(100, "curl", &[1111, 3333]), (200, "sshd", &[2222]), (300, "resolved", &[4444])],
+        );
+        
+        let resolver = ProcessResolver::new(&root.proc_root()).unwrap();
+        let sockets = resolver.snapshot();
+        
+        // Outgoing connection, seen from either direction
+        let id = flow(BasicProtocolType::TCP, (ip("192.0.2.1"), 443), (ip("10.0.0.1"), 50000));
+        let owner = sockets.owner(&id).unwrap();
+        assert_eq!((owner.info.pid, owner.info.name.as_str(), owner.info.user.as_str()), (100, "curl", "alice"));
+        assert_eq!(id.endpoint(owner.side), (ip("10.0.0.1"), 50000));
+        
+        let id = flow(BasicProtocolType::TCP, (ip("2001:db8::1"), 54321), (ip("2001:db8::2"), 443));
+        assert_eq!(sockets.owner(&id).map(|owner| owner.info.pid), Some(100));
+        
+        // Incoming connection to a wildcard listener
+        let id = flow(BasicProtocolType::TCP, (ip("198.51.100.7"), 60000), (ip("10.0.0.1"), 22));
+        let owner = sockets.owner(&id).unwrap();
+        assert_eq!((owner.info.pid, owner.info.user.as_str()), (200, "root"));
+        assert_eq!(id.endpoint(owner.side), (ip("10.0.0.1"), 22));
+        
+        // UDP socket bound to one address; unknown uids are shown as numbers
+        let id = flow(BasicProtocolType::UDP, (ip("127.0.0.53"), 53), (ip("127.0.0.1"), 40000));
+        let owner = sockets.owner(&id).unwrap();
+        assert_eq!((owner.info.pid, owner.info.user.as_str()), (300, "4242"));
+        let id = flow(BasicProtocolType::UDP, (ip("10.0.0.1"), 53), (ip("192.0.2.1"), 40000));
+        assert!(sockets.owner(&id).is_none());
+        
+        // Same ports over the other transport belong to nobody
+        let id = flow(BasicProtocolType::UDP, (ip("198.51.100.7"), 60000), (ip("10.0.0.1"), 22));
+        assert!(sockets.owner(&id).is_none());
+    }
+    
+    #[test]
+    fn quic_flows_are_attributed_as_udp() {
+        let browser = socket_row("0100000A:D431", "010200C0:01BB", 1000, 5555);
+        let root = FakeRoot::new("proc-quic", &[("tcp", &[]), ("udp", &[browser.as_str()])], &[(400, "firefox", &[5555])]);
+        let sockets = ProcessResolver::new(&root.proc_root()).unwrap().snapshot();
+        
+        let quic = PacketType { basic: BasicProtocolType::UDP, service: Some(QUIC_SERVICE) };
+        let id = connection(quic, (ip("10.0.0.1"), 54321), (ip("192.0.2.1"), 443));
+        assert_eq!(sockets.owner(&id).map(|owner| owner.info.pid), Some(400));
+    }
+    
+    #[test]
+    fn needs_socket_tables() {
+        let root = FakeRoot::new("proc-empty", &[], &[]);
+        assert!(ProcessResolver::new(&root.proc_root()).is_none());
+    }
+}
@@ -0,0 +1,404 @@
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::Aes128;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use super::tls::{hello_message, parse_client_hello};
+use super::types::{QuicSession, QuicState, TlsInfo};
+
+// Client Initial packets decrypted before giving up on the ClientHello;
+// large hellos span two or three of them
+const MAX_INITIALS: u32 = 8;
+// Largest CRYPTO stream collected, and frames held back waiting for a gap
+const MAX_CRYPTO_LEN: usize = 32 * 1024;
+const MAX_EARLY_FRAMES: usize = 32;
+
+const VERSION_1: u32 = 0x0000_0001;
+const VERSION_2: u32 = 0x6b33_43cf;
+
+// Salts the Initial keys are derived with (RFC 9001, RFC 9369, draft-29)
+const SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17,
+    0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a,
+];
+const SALT_V2: [u8; 20] = [
+    0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93,
+    0x81, 0xbe, 0x6e, 0x26, 0x9d, 0xcb, 0xf9, 0xbd, 0x2e, 0xd9,
+];
+const SALT_DRAFT_29: [u8; 20] = [
+    0xaf, 0xbf, 0xec, 0x28, 0x99, 0x93, 0xd2, 0x4c, 0x9e, 0x97,
+    0x86, 0xf1, 0x9c, 0x61, 0x11, 0xe0, 0x43, 0x90, 0xa8, 0x99,
+];
+
+const FRAME_PADDING: u64 = 0x00;
+const FRAME_PING: u64 = 0x01;
+const FRAME_ACK: u64 = 0x02;
+const FRAME_ACK_ECN: u64 = 0x03;
+const FRAME_CRYPTO: u64 = 0x06;
+
+impl QuicState {
+    // Feed a datagram from either side of a UDP flow. Returns the client's
+    // ClientHello once its Initial packets have been decrypted
+    pub fn feed(&mut self, from_client: bool, payload: &[u8]) -> Option<TlsInfo> {
+        match self {
+            QuicState::NotQuic => return None,
+            QuicState::Unknown => {
+                if long_header_version(payload).is_none() {
+                    *self = QuicState::NotQuic;
+                    return None;
+                }
+                *self = QuicState::Quic(Box::default());
+            },
+            QuicState::Quic(_) => {},
+        }
+        
+        let QuicState::Quic(session) = self else { return None };
+        session.feed(from_client, payload)
+    }
+    
+    pub fn session(&self) -> Option<&QuicSession> {
+        match self {
+            QuicState::Quic(session) => Some(session),
+            _ => None,
+        }
+    }
+}
+
+impl QuicSession {
+    fn feed(&mut self, from_client: bool, payload: &[u8]) -> Option<TlsInfo> {
+        // Short header packets are encrypted with keys we never see
+        let version = long_header_version(payload)?;
+        if version == 0 {
+            return None;    // Version negotiation
+        }
+        self.version = version;
+        
+        if !from_client || self.done {
+            return None;
+        }
+        let header = InitialHeader::parse(payload, version)?;
+        if self.dcid.is_empty() {
+            self.dcid = header.dcid.to_vec();
+        }
+        
+        self.initials += 1;
+        if let Some(plaintext) = decrypt_initial(payload, &header, &self.dcid, version) {
+            for (offset, data) in crypto_frames(&plaintext) {
+                self.add_crypto(offset, data);
+            }
+        }
+        
+        match hello_message(&self.crypto) {
+            Some(Ok(body)) => {
+                self.done = true;
+                parse_client_hello(&body, true)
+            },
+            Some(Err(())) => {
+                self.done = true;
+                None
+            },
+            None => {
+                if self.initials >= MAX_INITIALS || self.crypto.len() > MAX_CRYPTO_LEN {
+                    self.done = true;
+                }
+                None
+            },
+        }
+    }
+    
+    // Put a CRYPTO frame in place, holding it back if it lies past a gap
+    fn add_crypto(&mut self, offset: u64, data: &[u8]) {
+        let end = offset + data.len() as u64;
+        let have = self.crypto.len() as u64;
+        if offset > have {
+            if self.early.len() < MAX_EARLY_FRAMES {
+                self.early.push((offset, data.to_vec()));
+            }
+            return;
+        }
+        if end > have {
+            self.crypto.extend_from_slice(&data[(have - offset) as usize..]);
+        }
+        
+        // Take any held frames that now line up
+        while let Some(idx) = self.early.iter().position(|(offset, _)| *offset <= self.crypto.len() as u64) {
+            let (offset, data) = self.early.swap_remove(idx);
+            let have = self.crypto.len() as u64;
+            if offset + data.len() as u64 > have {
+                self.crypto.extend_from_slice(&data[(have - offset) as usize..]);
+            }
+        }
+    }
+}
+
+// Version of a long header packet, None for anything else. Only versions in
+// use are accepted: 1, 2, the IETF drafts, and 0 for version negotiation
+pub fn long_header_version(payload: &[u8]) -> Option<u32> {
+    if payload.len() < 7 || payload[0] & 0x80 == 0 {
+        return None;
+    }
+    let version = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    // Version negotiation leaves the fixed bit random
+    if version != 0 && payload[0] & 0x40 == 0 {
+        return None;
+    }
+    let known = matches!(version, 0 | VERSION_1 | VERSION_2) || version >> 8 == 0x00ff_0000;
+    // Destination connection ID, at most 20 bytes
+    (known && payload[5] <= 20).then_some(version)
+}
+
+// Readable version, e.g. "v1" or "draft-29"
+pub fn version_name(version: u32) -> String {
+    match version {
+        VERSION_1 => "v1".to_string(),
+        VERSION_2 => "v2".to_string(),
+        _ if version >> 8 == 0x00ff_0000 => format!("draft-{}", version & 0xff),
+        _ => format!("0x{:08x}", version),
+    }
+}
+
+// Where the parts of an Initial packet are
+struct InitialHeader<'a> {
+    dcid: &'a [u8],
+    pn_offset: usize,   // Start of the protected packet number
+    end: usize,         // End of this packet; more may be coalesced behind it
+}
+
+impl<'a> InitialHeader<'a> {
+    fn parse(payload: &'a [u8], version: u32) -> Option<Self> {
+        // Version 2 shuffled the long header packet types
+        let initial_type = if version == VERSION_2 { 1 } else { 0 };
+        if (payload[0] >> 4) & 0x03 != initial_type {
+            return None;
+        }
+        
+        let mut pos = 5;
+        let dcid_len = *payload.get(pos)? as usize;
+        let dcid = payload.get(pos + 1..pos + 1 + dcid_len)?;
+        pos += 1 + dcid_len;
+        let scid_len = *payload.get(pos)? as usize;
+        pos += 1 + scid_len;
+        
+        let token_len = varint(payload, &mut pos)? as usize;
+        pos = pos.checked_add(token_len)?;
+        let length = varint(payload, &mut pos)? as usize;
+        
+        let end = pos.checked_add(length)?;
+        (end <= payload.len()).then_some(InitialHeader { dcid, pn_offset: pos, end })
+    }
+}
+
+// Packet protection keys of client Initial packets, which anyone can derive
+// from the destination connection ID (RFC 9001 5.2)
+struct InitialKeys {
+    key: Vec<u8>,
+    iv: Vec<u8>,
+    hp: Vec<u8>,        // Header protection
+}
+
+impl InitialKeys {
+    fn client(dcid: &[u8], version: u32) -> Option<Self> {
+        let (salt, prefix) = match version {
+            VERSION_1 => (&SALT_V1, "quic"),
+            VERSION_2 => (&SALT_V2, "quicv2"),
+            0xff00_001d..=0xff00_0020 => (&SALT_DRAFT_29, "quic"),
+            _ => return None,
+        };
+        
+        let (initial_secret, _) = Hkdf::<Sha256>::extract(Some(&salt[..]), dcid);
+        let client_secret = expand_label(&initial_secret, "client in", 32)?;
+        Some(InitialKeys {
+            key: expand_label(&client_secret, &format!("{} key", prefix), 16)?,
+            iv: expand_label(&client_secret, &format!("{} iv", prefix), 12)?,
+            hp: expand_label(&client_secret, &format!("{} hp", prefix), 16)?,
+        })
+    }
+    
+    // Mask for the first byte and packet number, from 16 bytes of ciphertext
+    fn header_mask(&self, sample: &[u8]) -> Option<[u8; 5]> {
+        let mut block = GenericArray::clone_from_slice(sample);
+        Aes128::new_from_slice(&self.hp).ok()?.encrypt_block(&mut block);
+        block[..5].try_into().ok()
+    }
+}
+
+// Remove header protection and decrypt the payload of a client Initial packet
+fn decrypt_initial(payload: &[u8], header: &InitialHeader, dcid: &[u8], version: u32) -> Option<Vec<u8>> {
+    let keys = InitialKeys::client(dcid, version)?;
+    
+    // The mask comes from a sample taken as if the packet number were 4 bytes
+    let sample = payload.get(header.pn_offset + 4..header.pn_offset + 20)?;
+    let mask = keys.header_mask(sample)?;
+    
+    let first = payload[0] ^ (mask[0] & 0x0f);
+    let pn_len = (first & 0x03) as usize + 1;
+    let mut unprotected = payload.get(..header.pn_offset + pn_len)?.to_vec();
+    unprotected[0] = first;
+    let mut packet_number: u64 = 0;
+    for i in 0..pn_len {
+        unprotected[header.pn_offset + i] ^= mask[1 + i];
+        packet_number = (packet_number << 8) | unprotected[header.pn_offset + i] as u64;
+    }
+    
+    // Client packet numbers start at 0, so the truncated value is the full one
+    let mut nonce = keys.iv;
+    for (byte, pn_byte) in nonce[4..].iter_mut().zip(packet_number.to_be_bytes()) {
+        *byte ^= pn_byte;
+    }
+    
+    let ciphertext = payload.get(header.pn_offset + pn_len..header.end)?;
+    Aes128Gcm::new_from_slice(&keys.key).ok()?
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: ciphertext, aad: &unprotected })
+        .ok()
+}
+
+// HKDF-Expand-Label from TLS 1.3 with an empty context
+fn expand_label(secret: &[u8], label: &str, len: usize) -> Option<Vec<u8>> {
+    let label = format!("tls13 {}", label);
+    let mut info = Vec::with_capacity(4 + label.len());
+    info.extend_from_slice(&(len as u16).to_be_bytes());
+    info.push(label.len() as u8);
+    info.extend_from_slice(label.as_bytes());
+    info.push(0);
+    
+    let mut output = vec![0; len];
+    Hkdf::<Sha256>::from_prk(secret).ok()?.expand(&info, &mut output).ok()?;
+    Some(output)
+}
+
+// The CRYPTO frames of a decrypted Initial, as (offset, data). Stops at the
+// first frame type an Initial has no business carrying
+fn crypto_frames(plaintext: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut frames = Vec::new();
+    let mut pos = 0;
+    
+    while pos < plaintext.len() {
+        let Some(frame_type) = varint(plaintext, &mut pos) else { break };
+        match frame_type {
+            FRAME_PADDING | FRAME_PING => {},
+            FRAME_ACK | FRAME_ACK_ECN => {
+                // Largest acknowledged, delay, range count, first range
+                let fields: Option<Vec<u64>> = (0..4).map(|_| varint(plaintext, &mut pos)).collect();
+                let Some(fields) = fields else { break };
+                // Then a gap and a length per extra range, and the ECN counts
+                let extra = fields[2] * 2 + if frame_type == FRAME_ACK_ECN { 3 } else { 0 };
+                if (0..extra).any(|_| varint(plaintext, &mut pos).is_none()) {
+                    break;
+                }
+            },
+            FRAME_CRYPTO => {
+                let (Some(offset), Some(length)) = (varint(plaintext, &mut pos), varint(plaintext, &mut pos)) else { break };
+                let Some(data) = plaintext.get(pos..pos + length as usize) else { break };
+                pos += length as usize;
+                frames.push((offset, data));
+            },
+            _ => break,
+        }
+    }
+    frames
+}
+
+// Variable-length integer: the top two bits of the first byte give its size
+fn varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let first = *data.get(*pos)?;
+    let len = 1 << (first >> 6);
+    let bytes = data.get(*pos..*pos + len)?;
+    *pos += len;
+    Some(bytes[1..].iter().fold((first & 0x3f) as u64, |value, byte| (value << 8) | *byte as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // RFC 9001 Appendix A: the client's destination connection ID, and its
+    // first Initial packet carrying a ClientHello for example.com
+    const DCID: [u8; 8] = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+    const CLIENT_INITIAL: &str = "\
+        c000000001088394c8f03e5157080000449e7b9aec34d1b1c98dd7689fb8ec11d242b123dc9bd8bab936b47d92ec356c0bab7df5976d27cd449f63300099f399\
+        1c260ec4c60d17b31f8429157bb35a1282a643a8d2262cad67500cadb8e7378c8eb7539ec4d4905fed1bee1fc8aafba17c750e2c7ace01e6005f80fcb7df6212\
+        30c83711b39343fa028cea7f7fb5ff89eac2308249a02252155e2347b63d58c5457afd84d05dfffdb20392844ae812154682e9cf012f9021a6f0be17ddd0c208\
+        4dce25ff9b06cde535d0f920a2db1bf362c23e596d11a4f5a6cf3948838a3aec4e15daf8500a6ef69ec4e3feb6b1d98e610ac8b7ec3faf6ad760b7bad1db4ba3\
+        485e8a94dc250ae3fdb41ed15fb6a8e5eba0fc3dd60bc8e30c5c4287e53805db059ae0648db2f64264ed5e39be2e20d82df566da8dd5998ccabdae053060ae6c\
+        7b4378e846d29f37ed7b4ea9ec5d82e7961b7f25a9323851f681d582363aa5f89937f5a67258bf63ad6f1a0b1d96dbd4faddfcefc5266ba6611722395c906556\
+        be52afe3f565636ad1b17d508b73d8743eeb524be22b3dcbc2c7468d54119c7468449a13d8e3b95811a198f3491de3e7fe942b330407abf82a4ed7c1b311663a\
+        c69890f4157015853d91e923037c227a33cdd5ec281ca3f79c44546b9d90ca00f064c99e3dd97911d39fe9c5d0b23a229a234cb36186c4819e8b9c5927726632\
+        291d6a418211cc2962e20fe47feb3edf330f2c603a9d48c0fcb5699dbfe5896425c5bac4aee82e57a85aaf4e2513e4f05796b07ba2ee47d80506f8d2c25e50fd\
+        14de71e6c418559302f939b0e1abd576f279c4b2e0feb85c1f28ff18f58891ffef132eef2fa09346aee33c28eb130ff28f5b766953334113211996d20011a198\
+        e3fc433f9f2541010ae17c1bf202580f6047472fb36857fe843b19f5984009ddc324044e847a4f4a0ab34f719595de37252d6235365e9b84392b061085349d73\
+        203a4a13e96f5432ec0fd4a1ee65accdd5e3904df54c1da510b0ff20dcc0c77fcb2c0e0eb605cb0504db87632cf3d8b4dae6e705769d1de354270123cb11450e\
+        fc60ac47683d7b8d0f811365565fd98c4c8eb936bcab8d069fc33bd801b03adea2e1fbc5aa463d08ca19896d2bf59a071b851e6c239052172f296bfb5e724047\
+        90a2181014f3b94a4e97d117b438130368cc39dbb2d198065ae3986547926cd2162f40a29f0c3c8745c0f50fba3852e566d44575c29d39a03f0cda721984b6f4\
+        40591f355e12d439ff150aab7613499dbd49adabc8676eef023b15b65bfc5ca06948109f23f350db82123535eb8a7433bdabcb909271a6ecbcb58b936a88cd4e\
+        8f2e6ff5800175f113253d8fa9ca8885c2f552e657dc603f252e1a8e308f76f0be79e2fb8f5d5fbbe2e30ecadd220723c8c0aea8078cdfcb3868263ff8f09400\
+        54da48781893a7e49ad5aff4af300cd804a6b6279ab3ff3afb64491c85194aab760d58a606654f9f4400e8b38591356fbf6425aca26dc85244259ff2b19c41b9\
+        f96f3ca9ec1dde434da7d2d392b905ddf3d1f9af93d1af5950bd493f5aa731b4056df31bd267b6b90a079831aaf579be0a39013137aac6d404f518cfd4684064\
+        7e78bfe706ca4cf5e9c5453e9f7cfd2b8b4c8d169a44e55c88d4a9a7f9474241e221af44860018ab0856972e194cd934";
+    
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+    
+    #[test]
+    fn rfc9001_initial_keys() {
+        let keys = InitialKeys::client(&DCID, VERSION_1).unwrap();
+        assert_eq!(keys.key, hex("1f369613dd76d5467730efcbe3b1a22d"));
+        assert_eq!(keys.iv, hex("fa044b2f42a3fd3b46fb255c"));
+        assert_eq!(keys.hp, hex("9f50449e04a0e810283a1e9933adedd2"));
+        assert_eq!(keys.header_mask(&hex("d1b1c98dd7689fb8ec11d242b123dc9b")).unwrap().to_vec(), hex("437b9aec36"));
+    }
+    
+    #[test]
+    fn rfc9369_initial_keys() {
+        let keys = InitialKeys::client(&DCID, VERSION_2).unwrap();
+        assert_eq!(keys.key, hex("8b1a0bc121284290a29e0971b5cd045d"));
+        assert_eq!(keys.iv, hex("91f73e2351d8fa91660e909f"));
+        assert_eq!(keys.hp, hex("45b95e15235d6f45a6b19cbcb0294ba9"));
+        assert_eq!(keys.header_mask(&hex("ffe67b6abcdb4298b485dd04de806071")).unwrap().to_vec(), hex("94a0c95e80"));
+    }
+    
+    #[test]
+    fn rfc9001_client_initial() {
+        let packet = hex(CLIENT_INITIAL);
+        assert_eq!(long_header_version(&packet), Some(VERSION_1));
+        let header = InitialHeader::parse(&packet, VERSION_1).unwrap();
+        assert_eq!(header.dcid, DCID);
+        assert_eq!((header.pn_offset, header.end), (18, 1200));
+        
+        // A single CRYPTO frame at offset 0 with the whole ClientHello, then padding
+        let plaintext = decrypt_initial(&packet, &header, &DCID, VERSION_1).unwrap();
+        assert_eq!(plaintext.len(), 1162);
+        let frames = crypto_frames(&plaintext);
+        assert_eq!(frames.len(), 1);
+        assert_eq!((frames[0].0, frames[0].1.len()), (0, 241));
+        assert_eq!(frames[0].1[..4], [0x01, 0x00, 0x00, 0xed]);
+        
+        let mut state = QuicState::Unknown;
+        let info = state.feed(true, &packet).unwrap();
+        assert_eq!(info.sni.as_deref(), Some("example.com"));
+        assert_eq!(info.version, 0x0304);
+        assert_eq!(info.alpn, vec!["alpn"]);
+        assert!(info.ja4.starts_with("q13d"));
+        assert!(state.feed(true, &packet).is_none());
+    }
+    
+    #[test]
+    fn tampered_initial_fails_to_decrypt() {
+        let mut packet = hex(CLIENT_INITIAL);
+        packet[100] ^= 1;
+        let header = InitialHeader::parse(&packet, VERSION_1).unwrap();
+        assert!(decrypt_initial(&packet, &header, &DCID, VERSION_1).is_none());
+    }
+    
+    #[test]
+    fn crypto_frames_out_of_order() {
+        let mut session = QuicSession::default();
+        session.add_crypto(4, b"efgh");
+        assert!(session.crypto.is_empty());
+        session.add_crypto(0, b"abcd");
+        session.add_crypto(2, b"cdef");
+        assert_eq!(session.crypto, b"abcdefgh");
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use pcap::{BpfProgram, Capture, Linktype};
+
+// Largest frame written; longer ones are truncated to it
+const SNAPLEN: u32 = 65_535;
+// Memory the ring buffer may use, whatever its time window
+const MAX_RING_BYTES: usize = 512 * 1024 * 1024;
+
+// pcapng block types
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+// if_name option of the interface description block
+const OPTION_IF_NAME: u16 = 2;
+
+// What to record, from the command line
+#[derive(Debug, Clone)]
+pub struct RecorderOptions {
+    pub directory: Option<PathBuf>,     // Rotating files are written here; ring dumps too
+    pub filter: Option<String>,         // BPF expression packets must match to be recorded
+    pub rotate_size: u64,               // Bytes per file before starting the next one
+    pub rotate_time: Duration,          // Capture time per file before starting the next one
+    pub max_files: Option<usize>,       // The oldest files are deleted beyond this many
+    pub ring_window: Option<Duration>,  // Time kept in the ring buffer
+}
+
+// A packet held in the ring buffer
+#[derive(Clone)]
+struct RecordedPacket {
+    interface: usize,
+    timestamp: Duration,    // Since the Unix epoch
+    orig_len: u32,
+    data: Arc<[u8]>,
+}
+
+// Writes pcapng (the IETF pcapng draft) with an interface description block
+// per capture source, added before that source's first packet
+struct PcapngWriter<W: Write> {
+    out: W,
+    interface_ids: HashMap<usize, u32>,     // Recorder interface index to IDB number
+    bytes: u64,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    fn new(out: W) -> io::Result<Self> {
+        let mut writer = PcapngWriter { out, interface_ids: HashMap::new(), bytes: 0 };
+        
+        let mut body = Vec::with_capacity(16);
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());    // Major version
+        body.extend_from_slice(&0u16.to_le_bytes());    // Minor version
+        body.extend_from_slice(&(-1i64).to_le_bytes()); // Section length not given
+        writer.write_block(SECTION_HEADER_BLOCK, &body)?;
+        Ok(writer)
+    }
+    
+    fn write_packet(&mut self, interfaces: &[(Arc<str>, Linktype)], interface: usize, timestamp: Duration, orig_len: u32, data: &[u8]) -> io::Result<()> {
+        let interface_id = match self.interface_ids.get(&interface) {
+            Some(id) => *id,
+            None => {
+                let (name, linktype) = &interfaces[interface];
+                let id = self.interface_ids.len() as u32;
+                self.write_interface(name, *linktype)?;
+                self.interface_ids.insert(interface, id);
+                id
+            }
+        };
+        
+        // Timestamps are in the default resolution, microseconds
+        let timestamp = timestamp.as_micros() as u64;
+        let data = &data[..data.len().min(SNAPLEN as usize)];
+        let mut body = Vec::with_capacity(20 + data.len() + 3);
+        body.extend_from_slice(&interface_id.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&orig_len.to_le_bytes());
+        body.extend_from_slice(data);
+        pad(&mut body);
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+    
+    fn write_interface(&mut self, name: &str, linktype: Linktype) -> io::Result<()> {
+        let mut body = Vec::with_capacity(20 + name.len());
+        body.extend_from_slice(&(linktype.0 as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());    // Reserved
+        body.extend_from_slice(&SNAPLEN.to_le_bytes());
+        body.extend_from_slice(&OPTION_IF_NAME.to_le_bytes());
+        body.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        body.extend_from_slice(name.as_bytes());
+        pad(&mut body);
+        body.extend_from_slice(&[0; 4]);   // End of options
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)
+    }
+    
+    // Type, total length, body, and the total length again
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let total_len = body.len() as u32 + 12;
+        self.out.write_all(&block_type.to_le_bytes())?;
+        self.out.write_all(&total_len.to_le_bytes())?;
+        self.out.write_all(body)?;
+        self.out.write_all(&total_len.to_le_bytes())?;
+        self.bytes += total_len as u64;
+        Ok(())
+    }
+}
+
+// Blocks and options are padded to 32 bits
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+// The file packets are currently written to
+struct RecordingFile {
+    path: PathBuf,
+    writer: PcapngWriter<BufWriter<File>>,
+    started: Duration,     // Timestamp of its first packet
+}
+
+// Writes captured packets to rotating pcapng files and keeps the last few
+// minutes in memory to be saved on request. Shared by all capture threads
+pub struct Recorder {
+    options: RecorderOptions,
+    interfaces: Vec<(Arc<str>, Linktype)>,
+    // The record filter compiled for each interface's link type; Err when it
+    // doesn't compile for that link type, and nothing is recorded from it
+    filters: Vec<Result<Option<BpfProgram>, ()>>,
+    file: Option<RecordingFile>,
+    files: VecDeque<PathBuf>,           // Written so far, oldest first
+    sequence: u64,                      // Numbers the files, which may rotate within a second
+    dumps: u64,                         // Numbers the ring dumps the same way
+    ring: VecDeque<RecordedPacket>,
+    ring_bytes: usize,
+    pub packets: u64,                   // Recorded to files
+    failed: bool,                       // Writing files stopped after an error
+    pub error: Option<String>,          // Last problem recording, shown in the title
+    pub last_dump: Option<Result<PathBuf, String>>,
+    pub dumping: bool,                  // A ring buffer dump is being written
+}
+
+impl Recorder {
+    pub fn new(options: RecorderOptions) -> Result<Self> {
+        if let Some(directory) = &options.directory {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("Failed to create recording directory {}", directory.display()))?;
+        }
+        // Check the filter up front rather than when the first capture starts
+        if let Some(filter) = &options.filter {
+            compile_filter(filter, Linktype::ETHERNET)
+                .with_context(|| format!("Invalid record filter \"{}\"", filter))?;
+        }
+        
+        Ok(Recorder {
+            options,
+            interfaces: Vec::new(),
+            filters: Vec::new(),
+            file: None,
+            files: VecDeque::new(),
+            sequence: 0,
+            dumps: 0,
+            ring: VecDeque::new(),
+            ring_bytes: 0,
+            packets: 0,
+            failed: false,
+            error: None,
+            last_dump: None,
+            dumping: false,
+        })
+    }
+    
+    // Register a capture source; the returned index is passed to record()
+    pub fn add_interface(&mut self, name: &str, linktype: Linktype) -> usize {
+        let filter = match &self.options.filter {
+            Some(filter) => match compile_filter(filter, linktype) {
+                Ok(program) => Ok(Some(program)),
+                Err(error) => {
+                    // Record nothing from this interface rather than everything
+                    self.error = Some(format!("Record filter on {}: {}", name, error));
+                    Err(())
+                }
+            },
+            None => Ok(None),
+        };
+        self.interfaces.push((Arc::from(name), linktype));
+        self.filters.push(filter);
+        self.interfaces.len() - 1
+    }
+    
+    pub fn recording(&self) -> bool {
+        self.options.directory.is_some() && !self.failed
+    }
+    
+    pub fn ring_enabled(&self) -> bool {
+        self.options.ring_window.is_some()
+    }
+    
+    // File being written and its size so far
+    pub fn current_file(&self) -> Option<(&str, u64)> {
+        let file = self.file.as_ref()?;
+        let name = file.path.file_name()?.to_str()?;
+        Some((name, file.writer.bytes))
+    }
+    
+    // Files started so far, including the current one and any deleted
+    pub fn files_written(&self) -> u64 {
+        self.sequence
+    }
+    
+    // Packets and bytes held in the ring buffer
+    pub fn ring_usage(&self) -> (usize, usize) {
+        (self.ring.len(), self.ring_bytes)
+    }
+    
+    pub fn record(&mut self, interface: usize, timestamp: Duration, orig_len: u32, data: &[u8]) {
+        match self.filters.get(interface) {
+            Some(Ok(Some(filter))) if !filter.filter(data) => return,
+            Some(Err(())) | None => return,
+            _ => {}
+        }
+        
+        if self.recording() {
+            if let Err(error) = self.write(interface, timestamp, orig_len, data) {
+                // Stop instead of retrying on every packet, e.g. when the disk is full
+                self.error = Some(format!("{:#}", error));
+                self.failed = true;
+                self.file = None;
+            }
+        }
+        
+        // Only the ring buffer needs a copy of the packet
+        if let Some(window) = self.options.ring_window {
+            self.ring_bytes += data.len();
+            self.ring.push_back(RecordedPacket { interface, timestamp, orig_len, data: Arc::from(data) });
+            while let Some(oldest) = self.ring.front() {
+                if timestamp.saturating_sub(oldest.timestamp) <= window && self.ring_bytes <= MAX_RING_BYTES {
+                    break;
+                }
+                self.ring_bytes -= oldest.data.len();
+                self.ring.pop_front();
+            }
+        }
+    }
+    
+    fn write(&mut self, interface: usize, timestamp: Duration, orig_len: u32, data: &[u8]) -> Result<()> {
+        // Rotate by size or by capture time
+        let rotate = self.file.as_ref().is_none_or(|file| {
+            file.writer.bytes >= self.options.rotate_size
+                || timestamp.saturating_sub(file.started) >= self.options.rotate_time
+        });
+        if rotate {
+            self.rotate(timestamp)?;
+        }
+        
+        let Some(file) = self.file.as_mut() else { return Ok(()) };
+        file.writer.write_packet(&self.interfaces, interface, timestamp, orig_len, data)
+            .with_context(|| format!("Failed to write {}", file.path.display()))?;
+        self.packets += 1;
+        Ok(())
+    }
+    
+    // Close the current file and start the next one, deleting the oldest
+    // beyond the file limit
+    fn rotate(&mut self, started: Duration) -> Result<()> {
+        self.flush()?;
+        self.file = None;
+        
+        let Some(directory) = &self.options.directory else { return Ok(()) };
+        self.sequence += 1;
+        let name = format!("rnet-{}-{:04}.pcapng", Local::now().format("%Y%m%d-%H%M%S"), self.sequence);
+        let path = directory.join(name);
+        let out = File::create(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let writer = PcapngWriter::new(BufWriter::new(out))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        
+        self.files.push_back(path.clone());
+        if let Some(max_files) = self.options.max_files {
+            while self.files.len() > max_files {
+                if let Some(oldest) = self.files.pop_front() {
+                    let _ = fs::remove_file(oldest);
+                }
+            }
+        }
+        
+        self.file = Some(RecordingFile { path, writer, started });
+        Ok(())
+    }
+    
+    // Push buffered packets to disk, so the file is readable while recording
+    // and complete on exit
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.writer.out.flush()
+                .with_context(|| format!("Failed to write {}", file.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn compile_filter(expression: &str, linktype: Linktype) -> Result<BpfProgram> {
+    Ok(Capture::dead(linktype)?.compile(expression, true)?)
+}
+
+// Write the ring buffer to a new file in the recording directory, or the
+// current one, without holding up the capture threads while it's written
+pub fn dump_ring_buffer(recorder: &Arc<Mutex<Recorder>>) {
+    let (packets, interfaces, path) = {
+        let Ok(mut recorder) = recorder.lock() else { return };
+        if !recorder.ring_enabled() || recorder.dumping {
+            return;
+        }
+        recorder.dumping = true;
+        recorder.dumps += 1;
+        let packets: Vec<RecordedPacket> = recorder.ring.iter().cloned().collect();
+        let directory = recorder.options.directory.clone().unwrap_or_else(|| PathBuf::from("."));
+        let path = directory.join(format!("rnet-ring-{}-{:04}.pcapng", Local::now().format("%Y%m%d-%H%M%S"), recorder.dumps));
+        (packets, recorder.interfaces.clone(), path)
+    };
+    
+    let recorder = recorder.clone();
+    thread::spawn(move || {
+        let result = write_file(&path, &interfaces, &packets)
+            .map(|_| path)
+            .map_err(|error| format!("{:#}", error));
+        
+        if let Ok(mut recorder) = recorder.lock() {
+            recorder.last_dump = Some(result);
+            recorder.dumping = false;
+        }
+    });
+}
+
+fn write_file(path: &Path, interfaces: &[(Arc<str>, Linktype)], packets: &[RecordedPacket]) -> Result<()> {
+    let out = File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = PcapngWriter::new(BufWriter::new(out))?;
+    for packet in packets {
+        writer.write_packet(interfaces, packet.interface, packet.timestamp, packet.orig_len, &packet.data)?;
+    }
+    writer.out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_util::{interface, START};
+    
+    #[test]
+    fn pcapng_round_trips_through_libpcap() {
+        let interfaces: Vec<(Arc<str>, Linktype)> = vec![(interface(), Linktype::ETHERNET), (Arc::from("eth1"), Linktype::ETHERNET)];
+        let first: Vec<u8> = (0..60).collect();
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        writer.write_packet(&interfaces, 1, START + Duration::from_micros(123_456), 60, &first).unwrap();
+        // Truncated, and with a length that needs padding
+        writer.write_packet(&interfaces, 0, Duration::new(1_700_000_001, 7_000), 1514, &first[..13]).unwrap();
+        assert_eq!(writer.bytes, writer.out.len() as u64);
+        
+        let path = std::env::temp_dir().join(format!("rnet-roundtrip-{}.pcapng", std::process::id()));
+        fs::write(&path, &writer.out).unwrap();
+        let mut capture = Capture::from_file(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(capture.get_datalink(), Linktype::ETHERNET);
+        
+        let packet = capture.next_packet().unwrap();
+        assert_eq!((packet.header.ts.tv_sec, packet.header.ts.tv_usec), (1_700_000_000, 123_456));
+        assert_eq!((packet.header.caplen, packet.header.len), (60, 60));
+        assert_eq!(packet.data, &first[..]);
+        
+        let packet = capture.next_packet().unwrap();
+        assert_eq!((packet.header.ts.tv_sec, packet.header.ts.tv_usec), (1_700_000_001, 7));
+        assert_eq!((packet.header.caplen, packet.header.len), (13, 1514));
+        assert_eq!(packet.data, &first[..13]);
+        
+        assert!(capture.next_packet().is_err());
+    }
+}
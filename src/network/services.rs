@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::types::{BasicProtocolType, PacketType};
+
+// Built-in services, in the same format as a user services file. Names
+// follow the IANA registry, shortened where a common name is better known
+const DEFAULT_SERVICES: &str = "
+ftp-data        20/tcp
+ftp             21/tcp
+ssh             22/tcp
+telnet          23/tcp
+smtp            25/tcp
+dns             53/tcp
+dns             53/udp
+dhcp            67-68/udp
+tftp            69/udp
+http            80/tcp
+kerberos        88/tcp
+kerberos        88/udp
+pop3            110/tcp
+ntp             123/udp
+netbios         137-139/tcp
+netbios         137-139/udp
+imap            143/tcp
+snmp            161-162/udp
+bgp             179/tcp
+ldap            389/tcp
+https           443/tcp
+quic            443/udp
+smb             445/tcp
+smtps           465/tcp
+isakmp          500/udp
+syslog          514/udp
+submission      587/tcp
+ldaps           636/tcp
+dns-over-tls    853/tcp
+imaps           993/tcp
+pop3s           995/tcp
+socks           1080/tcp
+openvpn         1194/udp
+mssql           1433/tcp
+oracle          1521/tcp
+mqtt            1883/tcp
+ssdp            1900/udp
+nfs             2049/tcp
+zookeeper       2181/tcp
+etcd            2379-2380/tcp
+mysql           3306/tcp
+rdp             3389/tcp
+stun            3478/udp
+ipsec-nat-t     4500/udp
+sip             5060/tcp
+sip             5060/udp
+mdns            5353/udp
+llmnr           5355/udp
+postgres        5432/tcp
+amqp            5672/tcp
+vnc             5900/tcp
+redis           6379/tcp
+kubernetes      6443/tcp
+irc             6667/tcp
+bittorrent      6881-6889/tcp
+bittorrent      6881-6889/udp
+http-alt        8080/tcp
+https-alt       8443/tcp
+mqtts           8883/tcp
+prometheus      9090/tcp
+kafka           9092/tcp
+elasticsearch   9200/tcp
+memcached       11211/tcp
+mongodb         27017/tcp
+wireguard       51820/udp
+";
+
+// Maps ports to service names for classifying traffic
+pub struct ServiceTable {
+    ports: HashMap<(BasicProtocolType, u16), &'static str>,
+}
+
+impl ServiceTable {
+    // The built-in table, with the entries of a services file on top
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut table = ServiceTable { ports: HashMap::new() };
+        let mut names = HashMap::new();
+        
+        table.add_entries(DEFAULT_SERVICES, &mut names)
+            .context("Invalid built-in service table")?;
+        
+        if let Some(path) = path {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read services file {}", path.display()))?;
+            table.add_entries(&text, &mut names)
+                .with_context(|| format!("Invalid services file {}", path.display()))?;
+        }
+        
+        Ok(table)
+    }
+    
+    // Lines look like /etc/services: "name port/proto" or "name first-last/proto",
+    // with '#' comments. Later lines override earlier ones for the same port
+    fn add_entries(&mut self, text: &str, names: &mut HashMap<String, &'static str>) -> Result<()> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            
+            let entry = parse_entry(line)
+                .with_context(|| format!("line {}: '{}'", number + 1, line))?;
+            let Some((name, ports, transport)) = entry else { continue };
+            
+            // Names live for the whole run, so each is leaked once to keep PacketType Copy
+            let name = *names.entry(name.to_string())
+                .or_insert_with(|| Box::leak(name.to_string().into_boxed_str()));
+            
+            for port in ports.0..=ports.1 {
+                self.ports.insert((transport, port), name);
+            }
+        }
+        Ok(())
+    }
+    
+    // Service of a TCP or UDP packet. When both ports are known the lower one
+    // wins, as servers sit on the low, well-known ports and clients don't
+    pub fn classify(&self, transport: BasicProtocolType, src_port: u16, dst_port: u16) -> PacketType {
+        let (low, high) = if src_port <= dst_port { (src_port, dst_port) } else { (dst_port, src_port) };
+        let service = self.ports.get(&(transport, low))
+            .or_else(|| self.ports.get(&(transport, high)))
+            .copied();
+        PacketType { basic: transport, service }
+    }
+}
+
+// Service name, first and last port, and transport of one line
+type ServiceEntry<'a> = (&'a str, (u16, u16), BasicProtocolType);
+
+// None for the SCTP, DCCP and AppleTalk entries /etc/services also lists
+fn parse_entry(line: &str) -> Result<Option<ServiceEntry<'_>>> {
+    let mut fields = line.split_whitespace();
+    let name = fields.next().ok_or_else(|| anyhow!("missing service name"))?;
+    let spec = fields.next().ok_or_else(|| anyhow!("missing port, expected e.g. 5432/tcp"))?;
+    
+    let (ports, protocol) = spec.split_once('/')
+        .ok_or_else(|| anyhow!("expected port/protocol, e.g. 5432/tcp"))?;
+    let transport = match protocol.to_ascii_lowercase().as_str() {
+        "tcp" => BasicProtocolType::TCP,
+        "udp" => BasicProtocolType::UDP,
+        "sctp" | "dccp" | "ddp" => return Ok(None),
+        other => bail!("unknown protocol '{}', expected tcp or udp", other),
+    };
+    
+    let (first, last) = ports.split_once('-').unwrap_or((ports, ports));
+    let first = first.parse::<u16>().map_err(|_| anyhow!("invalid port '{}'", first))?;
+    let last = last.parse::<u16>().map_err(|_| anyhow!("invalid port '{}'", last))?;
+    if first > last {
+        bail!("port range {}-{} is backwards", first, last);
+    }
+    
+    Ok(Some((name, (first, last), transport)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    fn entry(line: &str) -> Option<ServiceEntry<'_>> {
+        parse_entry(line).unwrap()
+    }
+    
+    fn service(table: &ServiceTable, transport: BasicProtocolType, src_port: u16, dst_port: u16) -> Option<&'static str> {
+        table.classify(transport, src_port, dst_port).service
+    }
+    
+    #[test]
+    fn etc_services_lines() {
+        assert_eq!(entry("ssh\t\t22/tcp"), Some(("ssh", (22, 22), BasicProtocolType::TCP)));
+        // Aliases after the port are ignored
+        assert_eq!(entry("http\t\t80/tcp\t\twww"), Some(("http", (80, 80), BasicProtocolType::TCP)));
+        assert_eq!(entry("domain 53/UDP"), Some(("domain", (53, 53), BasicProtocolType::UDP)));
+        assert_eq!(entry("x11 6000-6063/tcp"), Some(("x11", (6000, 6063), BasicProtocolType::TCP)));
+        // Transports we don't classify are skipped rather than rejected
+        assert_eq!(entry("amqp\t\t5672/sctp"), None);
+        assert_eq!(entry("rtmp\t\t1/ddp"), None);
+    }
+    
+    #[test]
+    fn malformed_lines() {
+        for line in ["ssh", "ssh 22", "ssh 22/tpc", "ssh port/tcp", "ssh 70000/tcp", "range 200-100/udp", "range 1-/udp"] {
+            assert!(parse_entry(line).is_err(), "{}", line);
+        }
+    }
+    
+    #[test]
+    fn comments_and_blank_lines() {
+        let mut table = ServiceTable { ports: HashMap::new() };
+        let text = "# Network services\n\n  \nhttp 80/tcp www # WorldWideWeb HTTP\n#redis 6379/tcp\n";
+        table.add_entries(text, &mut HashMap::new()).unwrap();
+        assert_eq!(table.ports.len(), 1);
+        assert_eq!(service(&table, BasicProtocolType::TCP, 50000, 80), Some("http"));
+        
+        // Errors name the line
+        let error = table.add_entries("http 80/tcp\nbroken\n", &mut HashMap::new()).unwrap_err();
+        assert!(format!("{:#}", error).starts_with("line 2: 'broken'"));
+    }
+    
+    #[test]
+    fn user_file_overrides_the_defaults() {
+        let path = std::env::temp_dir().join(format!("rnet-services-{}", std::process::id()));
+        fs::write(&path, "web 443/tcp\nmyapp 9000-9001/udp\n").unwrap();
+        let table = ServiceTable::load(Some(&path));
+        let _ = fs::remove_file(&path);
+        let table = table.unwrap();
+        
+        assert_eq!(service(&table, BasicProtocolType::TCP, 50000, 443), Some("web"));
+        assert_eq!(service(&table, BasicProtocolType::UDP, 9001, 50000), Some("myapp"));
+        // Everything else is still there
+        assert_eq!(service(&table, BasicProtocolType::UDP, 50000, 443), Some("quic"));
+        assert_eq!(service(&table, BasicProtocolType::TCP, 50000, 22), Some("ssh"));
+    }
+    
+    #[test]
+    fn lower_known_port_wins() {
+        let table = ServiceTable::load(None).unwrap();
+        // A client that happens to use a known port as its source
+        assert_eq!(service(&table, BasicProtocolType::TCP, 3306, 443), Some("https"));
+        assert_eq!(service(&table, BasicProtocolType::TCP, 443, 3306), Some("https"));
+        // Only one end known
+        assert_eq!(service(&table, BasicProtocolType::TCP, 50000, 3306), Some("mysql"));
+        assert_eq!(service(&table, BasicProtocolType::UDP, 50000, 50001), None);
+    }
+}
@@ -0,0 +1,69 @@
+// Fixtures shared by the network module tests
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use super::flow::{FlowPacket, FlowTable, FlowTimeouts, TcpSegment};
+use super::types::{BasicProtocolType, ConnectionId, PacketType, Segment, Timestamp};
+
+// Capture clock the tests start from (November 2023)
+pub const START: Timestamp = Duration::from_secs(1_700_000_000);
+
+pub const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+pub const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+// Capture time `millis` after START
+pub fn at(millis: u64) -> Timestamp {
+    START + Duration::from_millis(millis)
+}
+
+pub fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+pub fn interface() -> Arc<str> {
+    Arc::from("eth0")
+}
+
+// Flow table with the default idle timeouts and room for 100 flows
+pub fn flow_table() -> FlowTable {
+    FlowTable::new(FlowTimeouts::new(Duration::from_secs(600), Duration::from_secs(60), Duration::from_secs(3600)), 100)
+}
+
+// Canonical key for an untagged flow on eth0
+pub fn connection(protocol: PacketType, src: (IpAddr, u16), dst: (IpAddr, u16)) -> ConnectionId {
+    ConnectionId::new(interface(), Segment::default(), protocol, src, dst).0
+}
+
+pub fn basic(basic: BasicProtocolType) -> PacketType {
+    PacketType { basic, service: None }
+}
+
+// TCP segment between CLIENT:40000 and SERVER:443, `millis` after START
+pub fn tcp_packet(from_client: bool, flags: u8, seq: u32, ack: u32, payload_len: u32, millis: u64) -> FlowPacket {
+    let (src, dst) = if from_client {
+        ((CLIENT, 40000), (SERVER, 443))
+    } else {
+        ((SERVER, 443), (CLIENT, 40000))
+    };
+    FlowPacket {
+        tcp: Some(TcpSegment { flags, seq, ack, window: 1000, payload_len }),
+        ..packet(basic(BasicProtocolType::TCP), src, dst, 60 + payload_len, millis)
+    }
+}
+
+fn packet(protocol: PacketType, src: (IpAddr, u16), dst: (IpAddr, u16), wire_len: u32, millis: u64) -> FlowPacket {
+    FlowPacket {
+        src_ip: src.0,
+        dst_ip: dst.0,
+        src_port: src.1,
+        dst_port: dst.1,
+        protocol,
+        wire_len,
+        timestamp: at(millis),
+        tcp: None,
+        icmp: None,
+    }
+}
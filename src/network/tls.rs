@@ -0,0 +1,495 @@
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+use super::types::{HelloState, TlsInfo};
+
+// Largest ClientHello we wait for; real ones are well under this even with
+// post-quantum key shares
+const MAX_HELLO_LEN: usize = 32 * 1024;
+// Client segments we look at before giving up on a flow
+const MAX_HELLO_SEGMENTS: u32 = 32;
+
+const RECORD_HANDSHAKE: u8 = 22;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+
+impl HelloState {
+    // Feed the next segment the client sent. Returns the details once the
+    // whole ClientHello has arrived; segments after that are ignored
+    pub fn feed(&mut self, seq: u32, payload: &[u8]) -> Option<TlsInfo> {
+        match self {
+            HelloState::Done => return None,
+            HelloState::Waiting => {
+                // Only a stream that opens with a handshake record is TLS
+                if payload.len() < 6 || payload[0] != RECORD_HANDSHAKE || payload[1] != 3 || payload[5] != HANDSHAKE_CLIENT_HELLO {
+                    *self = HelloState::Done;
+                    return None;
+                }
+                *self = HelloState::Collecting {
+                    next_seq: seq.wrapping_add(payload.len() as u32),
+                    data: payload.to_vec(),
+                    early: Vec::new(),
+                    segments: 1,
+                };
+            },
+            HelloState::Collecting { next_seq, data, early, segments } => {
+                *segments += 1;
+                let offset = seq.wrapping_sub(*next_seq) as i32;
+                if offset > 0 {
+                    // Arrived ahead of a missing segment, hold on to it
+                    early.push((seq, payload.to_vec()));
+                } else if offset == 0 {
+                    data.extend_from_slice(payload);
+                    *next_seq = next_seq.wrapping_add(payload.len() as u32);
+                    
+                    // Take any held segments that now line up
+                    while let Some(idx) = early.iter().position(|(seq, _)| *seq == *next_seq) {
+                        let (_, bytes) = early.swap_remove(idx);
+                        *next_seq = next_seq.wrapping_add(bytes.len() as u32);
+                        data.extend_from_slice(&bytes);
+                    }
+                }
+                // Anything before next_seq is a retransmission
+            },
+        }
+        
+        let HelloState::Collecting { data, segments, .. } = self else { return None };
+        match client_hello(data) {
+            Some(Ok(body)) => {
+                let info = parse_client_hello(&body, false);
+                *self = HelloState::Done;
+                info
+            },
+            Some(Err(())) => {
+                *self = HelloState::Done;
+                None
+            },
+            None => {
+                if data.len() > MAX_HELLO_LEN || *segments >= MAX_HELLO_SEGMENTS {
+                    *self = HelloState::Done;
+                }
+                None
+            },
+        }
+    }
+}
+
+// Collect the ClientHello message from the handshake records at the start of
+// the stream. None while more bytes are needed, Err if it isn't a ClientHello
+fn client_hello(stream: &[u8]) -> Option<Result<Vec<u8>, ()>> {
+    let mut handshake = Vec::new();
+    let mut pos = 0;
+    
+    while pos + 5 <= stream.len() {
+        if stream[pos] != RECORD_HANDSHAKE {
+            return Some(Err(()));
+        }
+        let length = u16::from_be_bytes([stream[pos + 3], stream[pos + 4]]) as usize;
+        let end = (pos + 5 + length).min(stream.len());
+        handshake.extend_from_slice(&stream[pos + 5..end]);
+        pos += 5 + length;
+        
+        // A message can be split across records, and records across segments
+        if let Some(result) = hello_message(&handshake) {
+            return Some(result);
+        }
+    }
+    None
+}
+
+// The body of the ClientHello at the start of a handshake stream. None while
+// more bytes are needed, Err if the stream opens with anything else
+pub fn hello_message(handshake: &[u8]) -> Option<Result<Vec<u8>, ()>> {
+    if handshake.len() < 4 {
+        return None;
+    }
+    if handshake[0] != HANDSHAKE_CLIENT_HELLO {
+        return Some(Err(()));
+    }
+    let length = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+    if length > MAX_HELLO_LEN {
+        return Some(Err(()));
+    }
+    if handshake.len() < 4 + length {
+        return None;
+    }
+    Some(Ok(handshake[4..4 + length].to_vec()))
+}
+
+// Big-endian reader over a message that fails softly on truncated input
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+    
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+    
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    
+    // A block with a one or two byte length prefix
+    fn block8(&mut self) -> Option<Reader<'a>> {
+        let len = self.u8()? as usize;
+        self.bytes(len).map(|data| Reader { data })
+    }
+    
+    fn block16(&mut self) -> Option<Reader<'a>> {
+        let len = self.u16()? as usize;
+        self.bytes(len).map(|data| Reader { data })
+    }
+    
+    fn u16_list(mut self) -> Vec<u16> {
+        let mut values = Vec::new();
+        while let Some(value) = self.u16() {
+            values.push(value);
+        }
+        values
+    }
+}
+
+// Fields of a ClientHello that go into the fingerprints, in wire order
+#[derive(Default)]
+struct ClientHello {
+    legacy_version: u16,
+    ciphers: Vec<u16>,
+    extensions: Vec<u16>,
+    groups: Vec<u16>,
+    point_formats: Vec<u8>,
+    signature_algorithms: Vec<u16>,
+    versions: Vec<u16>,
+    sni: Option<String>,
+    alpn: Vec<String>,
+}
+
+// Details of a ClientHello body, sent over TCP or inside QUIC
+pub fn parse_client_hello(body: &[u8], quic: bool) -> Option<TlsInfo> {
+    let mut reader = Reader { data: body };
+    let mut hello = ClientHello {
+        legacy_version: reader.u16()?,
+        ..ClientHello::default()
+    };
+    reader.bytes(32)?;      // Random
+    reader.block8()?;       // Session ID
+    hello.ciphers = reader.block16()?.u16_list();
+    reader.block8()?;       // Compression methods
+    
+    // Hellos without extensions are still valid, if rare
+    let mut extensions = reader.block16().unwrap_or(Reader { data: &[] });
+    while let Some(kind) = extensions.u16() {
+        let mut data = extensions.block16()?;
+        hello.extensions.push(kind);
+        
+        match kind {
+            EXT_SERVER_NAME => {
+                let mut names = data.block16()?;
+                while let Some(name_type) = names.u8() {
+                    let name = names.block16()?;
+                    if name_type == 0 {
+                        hello.sni = Some(String::from_utf8_lossy(name.data).to_lowercase());
+                    }
+                }
+            },
+            EXT_ALPN => {
+                let mut protocols = data.block16()?;
+                while let Some(protocol) = protocols.block8() {
+                    hello.alpn.push(String::from_utf8_lossy(protocol.data).into_owned());
+                }
+            },
+            EXT_SUPPORTED_GROUPS => hello.groups = data.block16()?.u16_list(),
+            EXT_EC_POINT_FORMATS => hello.point_formats = data.block8()?.data.to_vec(),
+            EXT_SIGNATURE_ALGORITHMS => hello.signature_algorithms = data.block16()?.u16_list(),
+            EXT_SUPPORTED_VERSIONS => hello.versions = data.block8()?.u16_list(),
+            _ => {},
+        }
+    }
+    
+    // The highest version offered; TLS 1.3 clients keep 1.2 in the legacy field
+    let version = hello.versions.iter()
+        .copied()
+        .filter(|&version| !is_grease(version))
+        .max()
+        .unwrap_or(hello.legacy_version);
+    
+    Some(TlsInfo {
+        version,
+        ja3: ja3(&hello),
+        ja4: ja4(&hello, version, quic),
+        sni: hello.sni,
+        alpn: hello.alpn,
+    })
+}
+
+// Reserved values clients sprinkle in to keep servers tolerant (RFC 8701);
+// both fingerprints leave them out
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+// MD5 of "version,ciphers,extensions,groups,point formats" in decimal
+fn ja3(hello: &ClientHello) -> String {
+    let list = |values: &[u16]| {
+        values.iter()
+            .filter(|&&value| !is_grease(value))
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join("-")
+    };
+    let point_formats = hello.point_formats.iter()
+        .map(|format| format.to_string())
+        .collect::<Vec<_>>()
+        .join("-");
+    
+    let text = format!(
+        "{},{},{},{},{}",
+        hello.legacy_version,
+        list(&hello.ciphers),
+        list(&hello.extensions),
+        list(&hello.groups),
+        point_formats
+    );
+    format!("{:x}", Md5::digest(text.as_bytes()))
+}
+
+// JA4, e.g. t13d1516h2_8daaf6152771_e5627efa2ab1: a readable prefix, starting
+// with t for TCP or q for QUIC, followed by hashes of the sorted ciphers and extensions
+fn ja4(hello: &ClientHello, version: u16, quic: bool) -> String {
+    let version = match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        _ => "00",
+    };
+    let sni = if hello.sni.is_some() { 'd' } else { 'i' };
+    
+    let mut ciphers: Vec<String> = hello.ciphers.iter()
+        .filter(|&&cipher| !is_grease(cipher))
+        .map(|cipher| format!("{:04x}", cipher))
+        .collect();
+    let extensions: Vec<u16> = hello.extensions.iter()
+        .copied()
+        .filter(|&extension| !is_grease(extension))
+        .collect();
+    
+    // First and last character of the first protocol, hex when not alphanumeric
+    let ends = hello.alpn.first().and_then(|protocol| {
+        Some((*protocol.as_bytes().first()?, *protocol.as_bytes().last()?))
+    });
+    let alpn = match ends {
+        Some((first, last)) => {
+            if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                format!("{}{}", first as char, last as char)
+            } else {
+                let first = format!("{:02x}", first);
+                let last = format!("{:02x}", last);
+                format!("{}{}", &first[..1], &last[1..])
+            }
+        },
+        None => "00".to_string(),
+    };
+    
+    // Name and protocol extensions are already in the prefix
+    let mut sorted_extensions: Vec<String> = extensions.iter()
+        .filter(|&&extension| extension != EXT_SERVER_NAME && extension != EXT_ALPN)
+        .map(|extension| format!("{:04x}", extension))
+        .collect();
+    sorted_extensions.sort();
+    ciphers.sort();
+    
+    let mut extensions_text = sorted_extensions.join(",");
+    if !hello.signature_algorithms.is_empty() {
+        let algorithms = hello.signature_algorithms.iter()
+            .filter(|&&algorithm| !is_grease(algorithm))
+            .map(|algorithm| format!("{:04x}", algorithm))
+            .collect::<Vec<_>>()
+            .join(",");
+        extensions_text = format!("{}_{}", extensions_text, algorithms);
+    }
+    
+    format!(
+        "{}{}{}{:02}{:02}{}_{}_{}",
+        if quic { 'q' } else { 't' },
+        version,
+        sni,
+        ciphers.len().min(99),
+        extensions.len().min(99),
+        alpn,
+        truncated_sha256(&ciphers.join(","), ciphers.is_empty()),
+        truncated_sha256(&extensions_text, sorted_extensions.is_empty()),
+    )
+}
+
+fn truncated_sha256(text: &str, empty: bool) -> String {
+    if empty {
+        return "000000000000".to_string();
+    }
+    let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+    hash[..12].to_string()
+}
+
+// Readable protocol version, e.g. "TLS 1.3"
+pub fn version_name(version: u16) -> String {
+    match version {
+        0x0304 => "TLS 1.3".to_string(),
+        0x0303 => "TLS 1.2".to_string(),
+        0x0302 => "TLS 1.1".to_string(),
+        0x0301 => "TLS 1.0".to_string(),
+        0x0300 => "SSL 3.0".to_string(),
+        _ => format!("0x{:04x}", version),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_be_bytes()).collect()
+    }
+    
+    fn block8(data: &[u8]) -> Vec<u8> {
+        let mut block = vec![data.len() as u8];
+        block.extend_from_slice(data);
+        block
+    }
+    
+    fn block16(data: &[u8]) -> Vec<u8> {
+        let mut block = (data.len() as u16).to_be_bytes().to_vec();
+        block.extend_from_slice(data);
+        block
+    }
+    
+    fn extension(kind: u16, data: &[u8]) -> Vec<u8> {
+        let mut extension = kind.to_be_bytes().to_vec();
+        extension.extend(block16(data));
+        extension
+    }
+    
+    fn hello_body(ciphers: &[u16], extensions: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = u16s(&[0x0303]);
+        body.extend_from_slice(&[0x11; 32]);   // Random
+        body.extend(block8(&[0x22; 32]));       // Session ID
+        body.extend(block16(&u16s(ciphers)));
+        body.extend(block8(&[0]));              // Null compression
+        if !extensions.is_empty() {
+            body.extend(block16(&extensions.concat()));
+        }
+        body
+    }
+    
+    // A Chrome-style hello: shuffled extensions and GREASE in the ciphers,
+    // extensions, groups and versions
+    fn chrome_hello() -> Vec<u8> {
+        let ciphers = [
+            0x0a0a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030,
+            0xcca9, 0xcca8, 0xc013, 0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+        ];
+        let mut server_name = vec![0];
+        server_name.extend(block16(b"Example.COM"));
+        let alpn = [block8(b"h2"), block8(b"http/1.1")].concat();
+        let algorithms = [0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601];
+        
+        let extensions = [
+            extension(0x2a2a, &[]),
+            extension(EXT_SERVER_NAME, &block16(&server_name)),
+            extension(0x0017, &[]),
+            extension(0xff01, &[0]),
+            extension(EXT_SUPPORTED_GROUPS, &block16(&u16s(&[0x4a4a, 0x001d, 0x0017, 0x0018]))),
+            extension(EXT_EC_POINT_FORMATS, &block8(&[0])),
+            extension(0x0023, &[]),
+            extension(EXT_ALPN, &block16(&alpn)),
+            extension(0x0005, &[1, 0, 0, 0, 0]),
+            extension(EXT_SIGNATURE_ALGORITHMS, &block16(&u16s(&algorithms))),
+            extension(0x0012, &[]),
+            extension(0x0033, &block16(&[])),
+            extension(0x002d, &block8(&[1])),
+            extension(EXT_SUPPORTED_VERSIONS, &block8(&u16s(&[0x5a5a, 0x0304, 0x0303]))),
+            extension(0x001b, &block8(&[0, 2])),
+            extension(0x4469, &block16(&block8(b"h2"))),
+            extension(0x3a3a, &[0]),
+            extension(0x0015, &[0; 16]),
+        ];
+        hello_body(&ciphers, &extensions)
+    }
+    
+    #[test]
+    fn fingerprints_skip_grease_and_sort_for_ja4() {
+        let info = parse_client_hello(&chrome_hello(), false).unwrap();
+        
+        // 771,4865-4866-4867-49195-49199-49196-49200-52393-52392-49171-49172-156-157-47-53,
+        // 0-23-65281-10-11-35-16-5-13-18-51-45-43-27-17513-21,29-23-24,0
+        assert_eq!(info.ja3, "cd08e31494f9531f560d64c695473da9");
+        // Sorted ciphers, sorted extensions without SNI and ALPN, then the
+        // signature algorithms in wire order
+        assert_eq!(info.ja4, "t13d1516h2_8daaf6152771_e5627efa2ab1");
+        assert_eq!(info.version, 0x0304);
+        assert_eq!(info.sni.as_deref(), Some("example.com"));
+        assert_eq!(info.alpn, vec!["h2", "http/1.1"]);
+    }
+    
+    #[test]
+    fn quic_hellos_are_marked_in_ja4() {
+        let info = parse_client_hello(&chrome_hello(), true).unwrap();
+        assert_eq!(info.ja4, "q13d1516h2_8daaf6152771_e5627efa2ab1");
+    }
+    
+    #[test]
+    fn fingerprints_without_extensions() {
+        let info = parse_client_hello(&hello_body(&[0x002f], &[]), false).unwrap();
+        
+        // "771,47,,,"
+        assert_eq!(info.ja3, "fde4273625b2ac63bd01d9c500dac91b");
+        assert_eq!(info.ja4, "t12i010000_ba72b8082249_000000000000");
+        assert_eq!(info.sni, None);
+    }
+    
+    #[test]
+    fn grease_values() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+    
+    #[test]
+    fn hello_split_across_records_and_segments() {
+        let body = chrome_hello();
+        let mut handshake = vec![HANDSHAKE_CLIENT_HELLO, 0];
+        handshake.extend(block16(&body));
+        
+        // Two records, the second arriving in two segments out of order
+        let (first, second) = handshake.split_at(100);
+        let mut stream = vec![RECORD_HANDSHAKE, 3, 1];
+        stream.extend(block16(first));
+        stream.extend([RECORD_HANDSHAKE, 3, 1]);
+        stream.extend(block16(second));
+        let (start, rest) = stream.split_at(150);
+        let (middle, end) = rest.split_at(60);
+        
+        let mut state = HelloState::default();
+        assert!(state.feed(1000, start).is_none());
+        assert!(state.feed(1210, end).is_none());
+        let info = state.feed(1150, middle).unwrap();
+        assert_eq!(info.ja4, "t13d1516h2_8daaf6152771_e5627efa2ab1");
+        assert!(state.feed(1000 + stream.len() as u32, b"more").is_none());
+    }
+}
@@ -1,40 +1,419 @@
 use std::{
+    fmt,
     net::IpAddr,
-    time::Instant,
-    collections::HashMap
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    collections::{HashMap, HashSet, VecDeque}
 };
 
+// What a packet carries: its transport and, when the service table knows
+// one of its ports, the service
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum PacketType {
-    TCP_HTTP,    // Port 80
-    TCP_HTTPS,   // Port 443
-    TCP_SSH,     // Port 22
-    TCP_DNS,     // Port 53
-    TCP_Other,   // Other TCP ports
-    UDP_DNS,     // Port 53
-    UDP_DHCP,    // Ports 67, 68
-    UDP_Other,   // Other UDP ports
-    ICMP,
-    Other,
+pub struct PacketType {
+    pub basic: BasicProtocolType,
+    pub service: Option<&'static str>,  // e.g. "https", "postgres"
+}
+
+// Service name QUIC traffic is labelled with, by port or by payload
+pub const QUIC_SERVICE: &str = "quic";
+
+impl PacketType {
+    pub const ICMP: PacketType = PacketType { basic: BasicProtocolType::ICMP, service: None };
+    pub const OTHER: PacketType = PacketType { basic: BasicProtocolType::Other, service: None };
+    pub const ARP: PacketType = PacketType { basic: BasicProtocolType::Other, service: Some("arp") };
+    
+    // Service name, or the transport for traffic on unknown ports
+    pub fn label(&self) -> &'static str {
+        match (self.service, self.basic) {
+            (Some(service), _) => service,
+            (None, BasicProtocolType::TCP) => "TCP",
+            (None, BasicProtocolType::UDP) => "UDP",
+            (None, BasicProtocolType::ICMP) => "ICMP",
+            (None, BasicProtocolType::Other) => "Other",
+        }
+    }
+}
+
+// Encapsulation a flow was found inside of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Tunnel {
+    Vxlan(u32),         // VXLAN network identifier (VNI)
+    Gre(Option<u32>),   // GRE, with its key when present
+    IpInIp,             // IPv4/IPv6 encapsulated directly in IP
+}
+
+// Layer-2 segment and tunnel a flow belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Segment {
+    pub vlan: Option<u16>,          // Outer 802.1Q/802.1ad VLAN ID
+    pub inner_vlan: Option<u16>,    // Inner (customer) VLAN ID of QinQ frames
+    pub tunnel: Option<Tunnel>,     // Tunnel the flow was decapsulated from
+}
+
+impl Segment {
+    pub fn is_untagged(&self) -> bool {
+        *self == Segment::default()
+    }
+    
+    // Short label such as "vlan 100", "vlan 100.20" or "vni 5000"
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        
+        match (self.vlan, self.inner_vlan) {
+            (Some(outer), Some(inner)) => parts.push(format!("vlan {}.{}", outer, inner)),
+            (Some(vlan), None) | (None, Some(vlan)) => parts.push(format!("vlan {}", vlan)),
+            (None, None) => {},
+        }
+        
+        match self.tunnel {
+            Some(Tunnel::Vxlan(vni)) => parts.push(format!("vni {}", vni)),
+            Some(Tunnel::Gre(Some(key))) => parts.push(format!("gre {}", key)),
+            Some(Tunnel::Gre(None)) => parts.push("gre".to_string()),
+            Some(Tunnel::IpInIp) => parts.push("ipip".to_string()),
+            None => {},
+        }
+        
+        if parts.is_empty() {
+            "-".to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+}
+
+// Which endpoint of a ConnectionId an address/port pair is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowSide {
+    A,
+    B,
+}
+
+impl FlowSide {
+    pub fn other(&self) -> Self {
+        match self {
+            FlowSide::A => FlowSide::B,
+            FlowSide::B => FlowSide::A,
+        }
+    }
 }
 
-// Connection identifier for tracking network flows
+// Connection identifier for tracking network flows. Endpoints are stored in
+// canonical order so both directions of a conversation share one key
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConnectionId {
-    pub src_ip: IpAddr,
-    pub dst_ip: IpAddr,
-    pub src_port: u16,
-    pub dst_port: u16,
+    pub interface: Arc<str>,    // Interface (or capture file) the flow was seen on
+    pub segment: Segment,       // VLAN / tunnel the flow was carried in
+    pub ip_a: IpAddr,           // Lower endpoint
+    pub port_a: u16,
+    pub ip_b: IpAddr,           // Higher endpoint
+    pub port_b: u16,
     pub protocol: PacketType,
 }
 
-// Connection statistics
+impl ConnectionId {
+    // Build the canonical key for a packet, along with the side its sender is on
+    pub fn new(
+        interface: Arc<str>,
+        segment: Segment,
+        protocol: PacketType,
+        src: (IpAddr, u16),
+        dst: (IpAddr, u16),
+    ) -> (Self, FlowSide) {
+        let (a, b, sender) = if src <= dst {
+            (src, dst, FlowSide::A)
+        } else {
+            (dst, src, FlowSide::B)
+        };
+        
+        let id = ConnectionId {
+            interface,
+            segment,
+            ip_a: a.0,
+            port_a: a.1,
+            ip_b: b.0,
+            port_b: b.1,
+            protocol,
+        };
+        (id, sender)
+    }
+    
+    pub fn endpoint(&self, side: FlowSide) -> (IpAddr, u16) {
+        match side {
+            FlowSide::A => (self.ip_a, self.port_a),
+            FlowSide::B => (self.ip_b, self.port_b),
+        }
+    }
+}
+
+// Connection statistics. "tx" is traffic sent by the initiator, "rx" is
+// traffic sent back by the responder
 #[derive(Debug, Clone)]
 pub struct ConnectionStats {
-    pub first_seen: Instant,
-    pub last_seen: Instant,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
     pub packet_count: u64,
     pub byte_count: u64,
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    pub initiator: FlowSide,        // Endpoint that opened the flow
+    pub initiator_confirmed: bool,  // Initiator known from a SYN rather than guessed
+    pub tcp: Option<TcpSession>,    // Connection state, TCP flows only
+    pub icmp: Option<IcmpSession>,  // Echo pairing and errors, ICMP pseudo-flows only
+    pub process: Option<ProcessOwner>,  // Local program the flow belongs to, once known
+    pub asn: Option<Arc<AsnInfo>>,      // Network of the remote end, when an ASN database is loaded
+    pub hello: HelloState,              // Progress reading the client's first TLS message
+    pub tls: Option<Arc<TlsInfo>>,      // What the ClientHello revealed, TLS flows only
+    pub http: HttpState,                // Plain HTTP/1.x requests and responses on the flow
+    pub quic: QuicState,                // QUIC version and Initial packets, UDP flows only
+    pub detection: Option<Detection>,   // Service recognised from the payload, whatever the ports
+    pub inspected: u8,                  // Payload packets checked for a signature so far
+}
+
+// A protocol signature found in a flow's payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub service: &'static str,
+    pub method: &'static str,   // What matched, e.g. "SSH banner"
+    pub confident: bool,        // Sure enough to override the port table's guess
+}
+
+// Reassembly of the ClientHello at the start of a TCP flow
+#[derive(Debug, Clone, Default)]
+pub enum HelloState {
+    #[default]
+    Waiting,            // No data from the client yet
+    Collecting {
+        next_seq: u32,                  // Sequence number the next segment should start at
+        data: Vec<u8>,                  // Client bytes so far, in order
+        early: Vec<(u32, Vec<u8>)>,     // Segments that arrived ahead of a gap
+        segments: u32,
+    },
+    Done,               // Parsed, given up on, or not TLS at all
+}
+
+// Whether a flow carries plain HTTP/1.x, decided by the client's first bytes
+#[derive(Debug, Clone, Default)]
+pub enum HttpState {
+    #[default]
+    Unknown,            // No data from the client yet
+    NotHttp,
+    Http(Box<HttpSession>),
+}
+
+// Whether a UDP flow is QUIC, decided by its first datagram
+#[derive(Debug, Clone, Default)]
+pub enum QuicState {
+    #[default]
+    Unknown,            // No data yet
+    NotQuic,
+    Quic(Box<QuicSession>),
+}
+
+// What the long headers and client Initial packets of a QUIC flow showed
+#[derive(Debug, Clone, Default)]
+pub struct QuicSession {
+    pub version: u32,                   // From the latest long header
+    pub dcid: Vec<u8>,                  // Connection ID the client opened with, which keys the Initial packets
+    pub crypto: Vec<u8>,                // Client's CRYPTO stream so far, in order
+    pub early: Vec<(u64, Vec<u8>)>,     // CRYPTO frames that arrived ahead of a gap
+    pub initials: u32,                  // Client Initial packets looked at
+    pub done: bool,                     // ClientHello parsed or given up on
+}
+
+// The request line and headers of interest
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub host: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// Requests and responses seen on one HTTP flow
+#[derive(Debug, Clone, Default)]
+pub struct HttpSession {
+    pub requests: u64,
+    pub responses: u64,
+    pub errors: u64,                    // 4xx and 5xx responses
+    pub response_bytes: u64,            // Total of the Content-Length headers
+    pub last_request: Option<HttpRequest>,
+    pub last_status: Option<u16>,
+    pub pending: VecDeque<(Timestamp, HttpRequest)>,    // Requests waiting for a response, oldest first
+    pub client_head: Vec<u8>,           // Headers split across segments, until complete
+    pub server_head: Vec<u8>,
+}
+
+// Details of a TLS ClientHello
+#[derive(Debug, Clone)]
+pub struct TlsInfo {
+    pub version: u16,           // Highest version offered
+    pub sni: Option<String>,    // Server name the client asked for
+    pub alpn: Vec<String>,      // Application protocols offered, e.g. h2, http/1.1
+    pub ja3: String,            // JA3 fingerprint (MD5)
+    pub ja4: String,            // JA4 fingerprint
+}
+
+// A local process owning sockets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub user: String,
+}
+
+// The process behind one end of a flow
+#[derive(Debug, Clone)]
+pub struct ProcessOwner {
+    pub info: Arc<ProcessInfo>,
+    pub side: FlowSide,     // Endpoint that is the process's socket
+}
+
+impl ConnectionStats {
+    // Initiator and responder endpoints of the flow
+    pub fn client(&self, id: &ConnectionId) -> (IpAddr, u16) {
+        id.endpoint(self.initiator)
+    }
+    
+    pub fn server(&self, id: &ConnectionId) -> (IpAddr, u16) {
+        id.endpoint(self.initiator.other())
+    }
+    
+    // What the flow carries: the payload's verdict when it is sure, or when
+    // the port table had no guess, else the port table's
+    pub fn protocol(&self, guess: PacketType) -> PacketType {
+        match self.detection {
+            Some(detection) if detection.confident || guess.service.is_none() => PacketType {
+                basic: guess.basic,
+                service: Some(detection.service),
+            },
+            _ => guess,
+        }
+    }
+}
+
+// Type and code of an ICMP or ICMPv6 message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IcmpType {
+    pub v6: bool,
+    pub kind: u8,       // The message type
+    pub code: u8,
+}
+
+// Where the packet an ICMP error complains about was going
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotedPacket {
+    pub dst: IpAddr,
+    pub protocol: u8,               // IP protocol number
+    pub dst_port: Option<u16>,      // TCP, UDP and SCTP only
+}
+
+// Per-flow ICMP tracking: echoes keyed by identifier, errors by the pair of hosts
+#[derive(Debug, Clone, Default)]
+pub struct IcmpSession {
+    pub requests: u64,
+    pub replies: u64,
+    pub unanswered: u64,                    // Requests that never got a reply
+    pub pending: VecDeque<(u16, Timestamp)>,    // Requests awaiting a reply, by sequence number, oldest first
+    pub last_rtt: Option<Duration>,
+    pub srtt: Option<Duration>,             // Smoothed echo round trip
+    pub errors: u64,
+    pub last_message: Option<IcmpType>,
+    pub about: Option<QuotedPacket>,        // What the latest error was about
+    pub mtu: Option<u32>,                   // Latest next-hop MTU reported
+}
+
+// Lifecycle of a TCP connection as seen from its flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    SynSent,        // SYN seen, waiting for the SYN-ACK
+    SynReceived,    // SYN-ACK seen, waiting for the final ACK
+    Established,
+    FinWait,        // One side has sent a FIN
+    Reset,
+    Closed,         // Both sides have sent a FIN, or the handshake never completed
+}
+
+impl fmt::Display for TcpState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TcpState::SynSent => "SYN",
+            TcpState::SynReceived => "SYN-ACK",
+            TcpState::Established => "ESTABLISHED",
+            TcpState::FinWait => "FIN_WAIT",
+            TcpState::Reset => "RST",
+            TcpState::Closed => "CLOSED",
+        })
+    }
+}
+
+impl TcpState {
+    // Cycle for the Connections state filter, None meaning any state
+    pub fn next_filter(current: Option<TcpState>) -> Option<TcpState> {
+        match current {
+            None => Some(TcpState::SynSent),
+            Some(TcpState::SynSent) => Some(TcpState::SynReceived),
+            Some(TcpState::SynReceived) => Some(TcpState::Established),
+            Some(TcpState::Established) => Some(TcpState::FinWait),
+            Some(TcpState::FinWait) => Some(TcpState::Reset),
+            Some(TcpState::Reset) => Some(TcpState::Closed),
+            Some(TcpState::Closed) => None,
+        }
+    }
+    
+    pub fn in_handshake(&self) -> bool {
+        matches!(self, TcpState::SynSent | TcpState::SynReceived)
+    }
+}
+
+// Per-connection TCP tracking
+#[derive(Debug, Clone)]
+pub struct TcpSession {
+    pub state: TcpState,
+    pub client_fin: bool,
+    pub server_fin: bool,
+    pub handshake_failed: bool,     // Refused or timed out before ESTABLISHED
+    pub syn_time: Option<Timestamp>,    // When the client's SYN was seen
+    pub handshake_rtt: Option<Duration>,    // SYN to the ACK completing the handshake
+    pub srtt: Option<Duration>,     // Smoothed RTT from data and its ACKs
+    pub client: TcpDirection,       // Sequence tracking for what the client sends
+    pub server: TcpDirection,       // ...and for what the server sends
+    pub counters: TcpCounters,
+}
+
+// Sequence tracking for one direction of a TCP connection
+#[derive(Debug, Clone, Default)]
+pub struct TcpDirection {
+    pub next_seq: Option<u32>,          // Highest sequence number sent, plus one
+    pub highest_seen: Option<Timestamp>,    // When that segment was seen
+    pub last_ack: Option<u32>,          // Last ACK number this side sent
+    pub last_window: u16,
+    pub rtt_probe: Option<(u32, Timestamp)>,    // ACK number awaited for the segment being timed
+}
+
+// TCP quality problems seen on a connection
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpCounters {
+    pub segments: u64,
+    pub retransmissions: u64,
+    pub out_of_order: u64,
+    pub dup_acks: u64,
+    pub zero_windows: u64,
+}
+
+impl TcpCounters {
+    pub fn merge(&mut self, other: &TcpCounters) {
+        self.segments += other.segments;
+        self.retransmissions += other.retransmissions;
+        self.out_of_order += other.out_of_order;
+        self.dup_acks += other.dup_acks;
+        self.zero_windows += other.zero_windows;
+    }
 }
 
 // Direction of traffic for connections
@@ -51,6 +430,9 @@ pub enum ConnectionSort {
     ByteCount,
     LastSeen,
     FirstSeen,
+    Rtt,
+    Retransmissions,
+    Segment,
 }
 
 impl ConnectionSort {
@@ -60,6 +442,9 @@ impl ConnectionSort {
             ConnectionSort::ByteCount => "Byte Count",
             ConnectionSort::LastSeen => "Last Seen",
             ConnectionSort::FirstSeen => "First Seen",
+            ConnectionSort::Rtt => "RTT",
+            ConnectionSort::Retransmissions => "Retransmissions",
+            ConnectionSort::Segment => "Grouped by VLAN/Tunnel",
         }
     }
     
@@ -68,7 +453,10 @@ impl ConnectionSort {
             ConnectionSort::PacketCount => ConnectionSort::ByteCount,
             ConnectionSort::ByteCount => ConnectionSort::LastSeen,
             ConnectionSort::LastSeen => ConnectionSort::FirstSeen,
-            ConnectionSort::FirstSeen => ConnectionSort::PacketCount,
+            ConnectionSort::FirstSeen => ConnectionSort::Rtt,
+            ConnectionSort::Rtt => ConnectionSort::Retransmissions,
+            ConnectionSort::Retransmissions => ConnectionSort::Segment,
+            ConnectionSort::Segment => ConnectionSort::PacketCount,
         }
     }
 }
@@ -79,9 +467,9 @@ pub enum ConnectionFilter {
     All,
     Outbound,
     Inbound,
-    HTTP,
-    HTTPS,
-    DNS,
+    Tls,                        // Flows with a ClientHello, on any port
+    Icmp,                       // ICMP echo and error pseudo-flows
+    Service(&'static str),      // One service from the service table
 }
 
 impl ConnectionFilter {
@@ -90,20 +478,25 @@ impl ConnectionFilter {
             ConnectionFilter::All => "All",
             ConnectionFilter::Outbound => "Outbound",
             ConnectionFilter::Inbound => "Inbound",
-            ConnectionFilter::HTTP => "HTTP",
-            ConnectionFilter::HTTPS => "HTTPS",
-            ConnectionFilter::DNS => "DNS",
+            ConnectionFilter::Tls => "TLS",
+            ConnectionFilter::Icmp => "ICMP",
+            ConnectionFilter::Service(service) => service,
         }
     }
     
-    pub fn next(&self) -> Self {
+    // The fixed filters, then each of the given services in order
+    pub fn next(&self, services: &[&'static str]) -> Self {
         match self {
             ConnectionFilter::All => ConnectionFilter::Outbound,
             ConnectionFilter::Outbound => ConnectionFilter::Inbound,
-            ConnectionFilter::Inbound => ConnectionFilter::HTTP,
-            ConnectionFilter::HTTP => ConnectionFilter::HTTPS,
-            ConnectionFilter::HTTPS => ConnectionFilter::DNS,
-            ConnectionFilter::DNS => ConnectionFilter::All,
+            ConnectionFilter::Inbound => ConnectionFilter::Tls,
+            ConnectionFilter::Tls => ConnectionFilter::Icmp,
+            ConnectionFilter::Icmp => services.first()
+                .map_or(ConnectionFilter::All, |service| ConnectionFilter::Service(service)),
+            ConnectionFilter::Service(current) => services.iter()
+                .skip_while(|service| *service != current)
+                .nth(1)
+                .map_or(ConnectionFilter::All, |service| ConnectionFilter::Service(service)),
         }
     }
 }
@@ -131,6 +524,81 @@ impl GeoMode {
     }
 }
 
+// Where captured packets come from
+#[derive(Debug, Clone)]
+pub enum CaptureSource {
+    Interface(String),                              // Live capture on a network interface
+    File { path: PathBuf, replay: ReplayMode },     // Recorded .pcap/.pcapng file
+}
+
+impl CaptureSource {
+    pub fn name(&self) -> String {
+        match self {
+            CaptureSource::Interface(name) => name.clone(),
+            CaptureSource::File { path, .. } => path.display().to_string(),
+        }
+    }
+}
+
+// Pacing used when reading packets from a capture file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    Realtime,   // Keep the original gaps between packets
+    Fast,       // Process packets as fast as possible
+}
+
+// When a packet was captured, as a duration since the Unix epoch. Flows are
+// timed with these rather than the wall clock, so a capture read from a file
+// gives the same RTTs and timeouts as it did live
+pub type Timestamp = Duration;
+
+// The newest packet timestamp the capture threads have seen, the clock a
+// capture file is expired against
+#[derive(Debug, Default)]
+pub struct CaptureClock {
+    latest: AtomicU64,  // Microseconds since the Unix epoch
+}
+
+impl CaptureClock {
+    pub fn advance(&self, timestamp: Timestamp) {
+        self.latest.fetch_max(timestamp.as_micros() as u64, Ordering::Relaxed);
+    }
+    
+    pub fn latest(&self) -> Timestamp {
+        Duration::from_micros(self.latest.load(Ordering::Relaxed))
+    }
+}
+
+// The wall clock as a timestamp, which is what live packets are stamped with
+pub fn wall_clock() -> Timestamp {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+// BPF capture filter shared between the UI and the capture thread
+#[derive(Debug, Clone, Default)]
+pub struct CaptureFilter {
+    pub requested: String,          // Expression most recently entered ("" captures everything)
+    pub active: String,             // Expression currently compiled on the capture handle
+    pub generation: u64,            // Bumped on every change so the capture thread re-applies it
+    pub error: Option<String>,      // Compile error for the requested expression, if any
+}
+
+impl CaptureFilter {
+    pub fn new(expression: Option<String>) -> Self {
+        let mut filter = CaptureFilter::default();
+        if let Some(expression) = expression {
+            filter.request(expression);
+        }
+        filter
+    }
+    
+    // Ask the capture thread to switch to a new expression
+    pub fn request(&mut self, expression: String) {
+        self.requested = expression.trim().to_string();
+        self.generation += 1;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NetworkStats {
     pub interface_name: String,
@@ -147,39 +615,81 @@ pub struct NetworkStats {
 pub struct PacketStats {
     pub counts: HashMap<PacketType, u64>,
     pub history: Vec<HashMap<PacketType, u64>>,
+    pub interface_counts: HashMap<String, HashMap<PacketType, u64>>,
+    pub interface_history: HashMap<String, Vec<HashMap<PacketType, u64>>>,
+    pub max_history: usize,
     pub last_update: Instant,
 }
 
 impl PacketStats {
-    pub fn new() -> Self {
-        let mut counts = HashMap::new();
-        // TCP categories
-        counts.insert(PacketType::TCP_HTTP, 0);
-        counts.insert(PacketType::TCP_HTTPS, 0);
-        counts.insert(PacketType::TCP_SSH, 0);
-        counts.insert(PacketType::TCP_DNS, 0);
-        counts.insert(PacketType::TCP_Other, 0);
-        // UDP categories
-        counts.insert(PacketType::UDP_DNS, 0);
-        counts.insert(PacketType::UDP_DHCP, 0);
-        counts.insert(PacketType::UDP_Other, 0);
-        // Other protocols
-        counts.insert(PacketType::ICMP, 0);
-        counts.insert(PacketType::Other, 0);
-
+    pub fn new(max_history: usize, interfaces: &[String]) -> Self {
+        let counts = Self::empty_counts();
+        
+        // Per-interface counters start out with the same categories as the totals
+        let interface_counts = interfaces.iter()
+            .map(|name| (name.clone(), counts.clone()))
+            .collect();
+        let interface_history = interfaces.iter()
+            .map(|name| (name.clone(), Vec::new()))
+            .collect();
+        
         PacketStats {
             counts,
             history: Vec::new(),
+            interface_counts,
+            interface_history,
+            max_history,
             last_update: Instant::now(),
         }
     }
-
+    
+    // Services are added as they are seen; the transports are always listed
+    fn empty_counts() -> HashMap<PacketType, u64> {
+        let mut counts = HashMap::new();
+        counts.insert(PacketType { basic: BasicProtocolType::TCP, service: None }, 0);
+        counts.insert(PacketType { basic: BasicProtocolType::UDP, service: None }, 0);
+        counts.insert(PacketType::ICMP, 0);
+        counts.insert(PacketType::OTHER, 0);
+        counts
+    }
+    
+    // Count a packet towards the totals and the interface it arrived on
+    pub fn record(&mut self, interface: &str, packet_type: PacketType) {
+        *self.counts.entry(packet_type).or_insert(0) += 1;
+        *self.interface_counts.entry(interface.to_string())
+            .or_insert_with(Self::empty_counts)
+            .entry(packet_type)
+            .or_insert(0) += 1;
+    }
+    
+    // Counts for one interface, or the totals when none is selected
+    pub fn counts_for(&self, interface: Option<&str>) -> &HashMap<PacketType, u64> {
+        interface
+            .and_then(|name| self.interface_counts.get(name))
+            .unwrap_or(&self.counts)
+    }
+    
+    // History for one interface, or the totals when none is selected
+    pub fn history_for(&self, interface: Option<&str>) -> &[HashMap<PacketType, u64>] {
+        interface
+            .and_then(|name| self.interface_history.get(name))
+            .unwrap_or(&self.history)
+    }
+    
     pub fn update_history(&mut self) {
-        // Keep last 60 datapoints for a minute of history
-        if self.history.len() >= 60 {
-            self.history.remove(0);
+        let max_history = self.max_history;
+        let push = |history: &mut Vec<HashMap<PacketType, u64>>, counts: &HashMap<PacketType, u64>| {
+            // Keep one datapoint per second for the configured history length
+            if history.len() >= max_history {
+                history.remove(0);
+            }
+            history.push(counts.clone());
+        };
+        
+        push(&mut self.history, &self.counts);
+        for (name, counts) in &self.interface_counts {
+            push(self.interface_history.entry(name.clone()).or_default(), counts);
         }
-        self.history.push(self.counts.clone());
         self.last_update = Instant::now();
     }
 }
@@ -208,12 +718,19 @@ pub enum BasicProtocolType {
 }
 
 // Tab enum for better organization
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum Tab {
     Overview,
+    #[value(name = "graph")]
     PacketGraph,
+    #[value(name = "distribution")]
     PacketDistribution,
     Connections,
+    Processes,
+    Dns,
+    Http,
+    Neighbors,
+    #[value(name = "geo")]
     GeoMap,
 }
 
@@ -224,6 +741,10 @@ impl Tab {
             Tab::PacketGraph => "Packet Graph",
             Tab::PacketDistribution => "Packet Distribution",
             Tab::Connections => "Connections",
+            Tab::Processes => "Processes",
+            Tab::Dns => "DNS",
+            Tab::Http => "HTTP",
+            Tab::Neighbors => "Neighbors",
             Tab::GeoMap => "Geo Map",
         }
     }
@@ -233,7 +754,11 @@ impl Tab {
             Tab::Overview => Tab::PacketGraph,
             Tab::PacketGraph => Tab::PacketDistribution,
             Tab::PacketDistribution => Tab::Connections,
-            Tab::Connections => Tab::GeoMap,
+            Tab::Connections => Tab::Processes,
+            Tab::Processes => Tab::Dns,
+            Tab::Dns => Tab::Http,
+            Tab::Http => Tab::Neighbors,
+            Tab::Neighbors => Tab::GeoMap,
             Tab::GeoMap => Tab::Overview,
         }
     }
@@ -244,24 +769,69 @@ impl Tab {
             Tab::PacketGraph => Tab::Overview,
             Tab::PacketDistribution => Tab::PacketGraph,
             Tab::Connections => Tab::PacketDistribution,
-            Tab::GeoMap => Tab::Connections,
+            Tab::Processes => Tab::Connections,
+            Tab::Dns => Tab::Processes,
+            Tab::Http => Tab::Dns,
+            Tab::Neighbors => Tab::Http,
+            Tab::GeoMap => Tab::Neighbors,
         }
     }
 }
 
-// Simple structure to store geographic location info
+// Where an address is, according to the GeoIP database
 #[derive(Debug, Clone)]
 pub struct GeoLocation {
-    pub country: String,
-    pub region: String,
-    pub latitude: f64,
-    pub longitude: f64,
+    pub country: String,            // ISO 3166-1 code
+    pub country_name: String,
+    pub city: Option<String>,
+    pub continent: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+// Autonomous system an address is announced by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsnInfo {
+    pub number: u32,
+    pub organization: String,
+}
+
+// Traffic to and from one country
+#[derive(Debug, Clone)]
+pub struct CountryTraffic {
+    pub location: Arc<GeoLocation>,     // First location seen in the country
+    pub packets: u64,
+    pub cities: HashMap<String, u64>,   // City name -> packet count
+}
+
+// Remote hosts remembered per autonomous system; beyond this only the
+// fact that there were more is kept
+pub const MAX_NETWORK_HOSTS: usize = 1000;
+
+// Traffic to and from one autonomous system
+#[derive(Debug, Clone)]
+pub struct AsnTraffic {
+    pub info: Arc<AsnInfo>,
+    pub packets: u64,
+    pub addresses: HashSet<IpAddr>,     // Remote hosts seen in the network, up to MAX_NETWORK_HOSTS
+    pub more_addresses: bool,           // Hosts were seen beyond the cap
+}
+
+impl AsnTraffic {
+    pub fn hosts_label(&self) -> String {
+        if self.more_addresses {
+            format!("{}+", self.addresses.len())
+        } else {
+            self.addresses.len().to_string()
+        }
+    }
 }
 
 // Structure to store location visualization stats
 #[derive(Debug, Clone)]
 pub struct GeoStats {
-    pub locations: HashMap<String, (GeoLocation, u64)>, // Country code -> (location, packet count)
+    pub locations: HashMap<String, CountryTraffic>, // Country code -> traffic
+    pub networks: HashMap<u32, AsnTraffic>,         // AS number -> traffic
     pub total_countries: usize,
     pub top_country: Option<String>,
     pub timestamp: Instant,
@@ -271,38 +841,122 @@ impl GeoStats {
     pub fn new() -> Self {
         GeoStats {
             locations: HashMap::new(),
+            networks: HashMap::new(),
             total_countries: 0,
             top_country: None,
             timestamp: Instant::now(),
         }
     }
     
+    pub fn record_location(&mut self, location: &Arc<GeoLocation>) {
+        let country = self.locations.entry(location.country.clone())
+            .or_insert_with(|| CountryTraffic {
+                location: location.clone(),
+                packets: 0,
+                cities: HashMap::new(),
+            });
+        country.packets += 1;
+        if let Some(city) = &location.city {
+            *country.cities.entry(city.clone()).or_default() += 1;
+        }
+        
+        self.update_top_country();
+    }
+    
+    pub fn record_network(&mut self, info: &Arc<AsnInfo>, ip: IpAddr) {
+        let network = self.networks.entry(info.number)
+            .or_insert_with(|| AsnTraffic {
+                info: info.clone(),
+                packets: 0,
+                addresses: HashSet::new(),
+                more_addresses: false,
+            });
+        network.packets += 1;
+        if network.addresses.len() < MAX_NETWORK_HOSTS {
+            network.addresses.insert(ip);
+        } else if !network.addresses.contains(&ip) {
+            network.more_addresses = true;
+        }
+    }
+    
     pub fn update_top_country(&mut self) {
         self.top_country = self.locations.iter()
-            .max_by_key(|(_, (_, count))| *count)
+            .max_by_key(|(_, traffic)| traffic.packets)
             .map(|(country, _)| country.clone());
-            
+        
         self.total_countries = self.locations.len();
     }
 }
 
 // Helper function to convert detailed packet type to basic category
 pub fn get_basic_type(packet_type: PacketType) -> BasicProtocolType {
-    match packet_type {
-        // All TCP variants
-        PacketType::TCP_HTTP | 
-        PacketType::TCP_HTTPS | 
-        PacketType::TCP_SSH | 
-        PacketType::TCP_DNS | 
-        PacketType::TCP_Other => BasicProtocolType::TCP,
-        
-        // All UDP variants
-        PacketType::UDP_DNS | 
-        PacketType::UDP_DHCP | 
-        PacketType::UDP_Other => BasicProtocolType::UDP,
+    packet_type.basic
+}
+
+// Buckets the distribution, graph and overview group traffic into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrafficCategory {
+    Tcp,
+    Udp,
+    Quic,
+    Icmp,
+    Other,
+}
+
+// QUIC carries enough web traffic to be shown apart from the rest of UDP,
+// though it is UDP everywhere else
+pub fn traffic_category(packet_type: PacketType) -> TrafficCategory {
+    match packet_type.basic {
+        BasicProtocolType::UDP if packet_type.service == Some(QUIC_SERVICE) => TrafficCategory::Quic,
+        BasicProtocolType::TCP => TrafficCategory::Tcp,
+        BasicProtocolType::UDP => TrafficCategory::Udp,
+        BasicProtocolType::ICMP => TrafficCategory::Icmp,
+        BasicProtocolType::Other => TrafficCategory::Other,
+    }
+}
+
+// Packet counts summed per label, busiest first. Services seen over both
+// TCP and UDP, like dns, are counted together
+pub fn counts_by_label(counts: &HashMap<PacketType, u64>) -> Vec<(&'static str, u64)> {
+    let mut labels: HashMap<&'static str, u64> = HashMap::new();
+    for (packet_type, count) in counts {
+        *labels.entry(packet_type.label()).or_default() += count;
+    }
+    
+    let mut labels: Vec<(&'static str, u64)> = labels.into_iter().collect();
+    labels.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn location(country: &str, city: Option<&str>) -> Arc<GeoLocation> {
+        Arc::new(GeoLocation {
+            country: country.to_string(),
+            country_name: country.to_string(),
+            city: city.map(str::to_string),
+            continent: "Europe".to_string(),
+            latitude: None,
+            longitude: None,
+        })
+    }
+    
+    #[test]
+    fn country_traffic_and_top_country() {
+        let mut geo = GeoStats::new();
+        geo.record_location(&location("NL", Some("Amsterdam")));
+        geo.record_location(&location("DE", Some("Berlin")));
+        geo.record_location(&location("DE", Some("Berlin")));
+        geo.record_location(&location("DE", None));
         
-        // Other protocols
-        PacketType::ICMP => BasicProtocolType::ICMP,
-        PacketType::Other => BasicProtocolType::Other,
+        assert_eq!(geo.total_countries, 2);
+        assert_eq!(geo.top_country.as_deref(), Some("DE"));
+        let germany = &geo.locations["DE"];
+        assert_eq!(germany.packets, 3);
+        assert_eq!(germany.cities.get("Berlin"), Some(&2));
+        assert_eq!(germany.cities.len(), 1);
+        assert_eq!(geo.locations["NL"].packets, 1);
     }
 }
\ No newline at end of file
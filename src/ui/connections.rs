@@ -1,14 +1,23 @@
-use std::time::Instant;
+use std::{
+    cmp::Reverse,
+    net::IpAddr,
+    time::Duration,
+};
 
 use ratatui::{
     prelude::*,
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Cell, Paragraph, Row, Table, TableState,
+    },
 };
 
 use crate::app::App;
-use crate::network::types::{ConnectionFilter, ConnectionDirection, PacketType};
-use crate::utils::{format_bytes, format_duration, centered_rect};
+use crate::network::types::{ConnectionFilter, ConnectionDirection, ConnectionStats, HttpSession, IcmpSession, TcpState, TlsInfo};
+use crate::network::tls::version_name;
+use crate::network::quic::version_name as quic_version_name;
+use crate::utils::{format_bytes, format_duration, format_rtt, centered_rect};
 use crate::network::capture::get_connection_direction;
 
 pub fn draw_connections(f: &mut Frame, app: &mut App, area: Rect) {
@@ -22,10 +31,28 @@ pub fn draw_connections(f: &mut Frame, app: &mut App, area: Rect) {
         .split(area);
     
     // Create header showing current sort and filter
+    let segment_text = match app.connection_segment_filter {
+        Some(segment) => segment.label(),
+        None => "All".to_string(),
+    };
+    let state_text = match &app.connection_state_filter {
+        Some(state) => state.to_string(),
+        None => "All".to_string(),
+    };
+    let process_text = match app.connection_process_filter {
+        Some(pid) => pid.to_string(),
+        None => "All".to_string(),
+    };
+    let search_text = if app.tls_search.is_empty() { "-" } else { app.tls_search.as_str() };
     let header_text = format!(
-        "Sort: {} | Filter: {} | Use s/f to change | Arrow keys to navigate",
+        "Sort: {} | Filter: {} | VLAN/Tunnel: {} | State: {} | PID: {} | Show: {} | TLS search: {} | Use s/f/v/t/p/n to change, / to search | Arrow keys to navigate",
         app.connection_sort.to_string(),
-        app.connection_filter.to_string()
+        app.connection_filter.to_string(),
+        segment_text,
+        state_text,
+        process_text,
+        if app.show_hostnames { "Names" } else { "IPs" },
+        search_text
     );
     
     let header = Paragraph::new(header_text)
@@ -37,32 +64,57 @@ pub fn draw_connections(f: &mut Frame, app: &mut App, area: Rect) {
     // Get the connections
     if let Ok(conns) = app.connections.try_lock() {
         // Create a copy for sorting and filtering
-        let mut connections: Vec<(&crate::network::types::ConnectionId, &crate::network::types::ConnectionStats)> = conns.iter().collect();
+        let mut connections: Vec<(&crate::network::types::ConnectionId, &crate::network::types::ConnectionStats)> = conns.flows.iter()
+            .filter(|(id, _)| app.selected_interface().is_none_or(|name| *id.interface == *name))
+            .filter(|(id, _)| app.connection_segment_filter.is_none_or(|segment| id.segment == segment))
+            .filter(|(_, stats)| {
+                app.connection_process_filter.is_none_or(|pid| stats.process.as_ref().map(|owner| owner.info.pid) == Some(pid))
+            })
+            .filter(|(_, stats)| {
+                app.connection_state_filter.is_none_or(|state| stats.tcp.as_ref().map(|tcp| tcp.state) == Some(state))
+            })
+            .filter(|(_, stats)| {
+                app.tls_search.is_empty() || stats.tls.as_ref().is_some_and(|tls| tls_matches(tls, &app.tls_search))
+            })
+            .collect();
+        
+        // Only spend a column on the interface when several are monitored
+        let show_interface = app.interface_stats.len() > 1;
+        // ...and on the owning process when connections are being attributed
+        let show_process = app.process_attribution;
+        // ...and on the VLAN / tunnel when there is tagged or tunneled traffic
+        let show_segment = connections.iter().any(|(id, _)| !id.segment.is_untagged());
+        // ...and on the remote network when an ASN database is loaded
+        let show_asn = app.asn_db.is_some();
         
         // Filter connections
         connections = match app.connection_filter {
             ConnectionFilter::All => connections,
             ConnectionFilter::Outbound => connections.into_iter()
-                .filter(|(id, _)| {
-                    get_connection_direction(id.src_ip, id.dst_ip, &app.local_networks) == ConnectionDirection::Outbound
+                .filter(|(id, stats)| {
+                    get_connection_direction(stats.client(id).0, &app.local_networks) == ConnectionDirection::Outbound
                 })
                 .collect(),
             ConnectionFilter::Inbound => connections.into_iter()
-                .filter(|(id, _)| {
-                    get_connection_direction(id.src_ip, id.dst_ip, &app.local_networks) == ConnectionDirection::Inbound
+                .filter(|(id, stats)| {
+                    get_connection_direction(stats.client(id).0, &app.local_networks) == ConnectionDirection::Inbound
                 })
                 .collect(),
-            ConnectionFilter::HTTP => connections.into_iter()
-                .filter(|(id, _)| id.protocol == PacketType::TCP_HTTP)
+            ConnectionFilter::Tls => connections.into_iter()
+                .filter(|(_, stats)| stats.tls.is_some())
                 .collect(),
-            ConnectionFilter::HTTPS => connections.into_iter()
-                .filter(|(id, _)| id.protocol == PacketType::TCP_HTTPS)
+            ConnectionFilter::Icmp => connections.into_iter()
+                .filter(|(_, stats)| stats.icmp.is_some())
                 .collect(),
-            ConnectionFilter::DNS => connections.into_iter()
-                .filter(|(id, _)| id.protocol == PacketType::UDP_DNS || id.protocol == PacketType::TCP_DNS)
+            ConnectionFilter::Service(service) => connections.into_iter()
+                .filter(|(id, stats)| stats.protocol(id.protocol).service == Some(service))
                 .collect(),
         };
         
+        // TLS and HTTP details get columns once any listed flow has them
+        let show_tls = connections.iter().any(|(_, stats)| stats.tls.is_some());
+        let show_http = connections.iter().any(|(_, stats)| stats.http.session().is_some());
+        
         // Sort connections
         match app.connection_sort {
             crate::network::types::ConnectionSort::PacketCount => {
@@ -77,88 +129,312 @@ pub fn draw_connections(f: &mut Frame, app: &mut App, area: Rect) {
             crate::network::types::ConnectionSort::FirstSeen => {
                 connections.sort_by(|(_, a), (_, b)| a.first_seen.cmp(&b.first_seen));
             },
+            crate::network::types::ConnectionSort::Rtt => {
+                // Slowest first, flows without an RTT estimate last
+                connections.sort_by_key(|(_, stats)| Reverse(connection_rtt(stats)));
+            },
+            crate::network::types::ConnectionSort::Retransmissions => {
+                connections.sort_by(|(_, a), (_, b)| {
+                    let a = a.tcp.as_ref().map_or(0, |tcp| tcp.counters.retransmissions);
+                    let b = b.tcp.as_ref().map_or(0, |tcp| tcp.counters.retransmissions);
+                    b.cmp(&a)
+                });
+            },
+            crate::network::types::ConnectionSort::Segment => {
+                // Keep each VLAN / tunnel together, busiest flows first within it
+                connections.sort_by(|(a_id, a), (b_id, b)| {
+                    a_id.segment.cmp(&b_id.segment).then(b.packet_count.cmp(&a.packet_count))
+                });
+            },
         }
         
         // Create connection table
-        let table_state = TableState::default().with_selected(Some(app.connection_scroll.min(connections.len().saturating_sub(1))));
+        let selected = app.connection_scroll.min(connections.len().saturating_sub(1));
+        let table_state = TableState::default().with_selected(Some(selected));
+        
+        // Full TLS / QUIC / HTTP details of the selected flow go in a box under the table
+        let details = connections.get(selected).and_then(|(_, stats)| flow_details(stats));
+        let body_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(if details.is_some() { 4 } else { 0 }),
+            ])
+            .split(chunks[1]);
+        
+        // Names are only looked up for the rows around the selection
+        let mut host_names = if app.show_hostnames { app.host_names.try_lock().ok() } else { None };
+        if let Some(names) = host_names.as_mut() {
+            let visible = chunks[1].height as usize;
+            let first = app.connection_scroll.saturating_sub(visible);
+            for (id, _) in connections.iter().skip(first).take(visible * 2) {
+                names.request(id.ip_a);
+                names.request(id.ip_b);
+            }
+        }
+        let host = |ip: IpAddr| -> String {
+            host_names.as_ref()
+                .and_then(|names| names.name(ip))
+                .map_or_else(|| ip.to_string(), |name| name.to_string())
+        };
+        
+        // Size the address columns to fit IPv6 addresses or names when any are shown
+        let ip_width = connections.iter()
+            .map(|(id, _)| host(id.ip_a).len().max(host(id.ip_b).len()))
+            .max()
+            .unwrap_or(0)
+            .clamp(15, 39) as u16;
         
         // Prepare connection rows
-        let now = Instant::now();
+        let now = app.capture_time();
         let rows = connections.iter().map(|(id, stats)| {
-            let direction = match get_connection_direction(id.src_ip, id.dst_ip, &app.local_networks) {
+            // Show every flow from client to server
+            let (client_ip, client_port) = stats.client(id);
+            let (server_ip, server_port) = stats.server(id);
+            
+            let direction = match get_connection_direction(client_ip, &app.local_networks) {
                 ConnectionDirection::Outbound => "OUT",
                 ConnectionDirection::Inbound => "IN",
             };
             
-            let proto = match id.protocol {
-                PacketType::TCP_HTTP => "HTTP",
-                PacketType::TCP_HTTPS => "HTTPS",
-                PacketType::TCP_SSH => "SSH",
-                PacketType::TCP_DNS => "TCP-DNS",
-                PacketType::TCP_Other => "TCP",
-                PacketType::UDP_DNS => "UDP-DNS",
-                PacketType::UDP_DHCP => "DHCP",
-                PacketType::UDP_Other => "UDP",
-                PacketType::ICMP => "ICMP",
-                PacketType::Other => "OTHER",
+            let proto = stats.protocol(id.protocol).label();
+            
+            // How the service was told, with the port table's guess when the payload disagreed
+            let (detected, detected_style) = match stats.detection {
+                Some(detection) => match id.protocol.service {
+                    Some(guess) if guess != detection.service => {
+                        (format!("{} (port: {})", detection.method, guess), Style::default().fg(Color::Yellow))
+                    },
+                    _ => (detection.method.to_string(), Style::default()),
+                },
+                None if id.protocol.service.is_some() => ("port".to_string(), Style::default().fg(Color::DarkGray)),
+                None => ("-".to_string(), Style::default().fg(Color::DarkGray)),
+            };
+            
+            // Failed handshakes stand out from connections that closed normally,
+            // as do ICMP errors and pings nobody answers
+            let (state, state_style) = match &stats.tcp {
+                Some(tcp) if tcp.handshake_failed => (tcp.state.to_string(), Style::default().fg(Color::Red)),
+                Some(tcp) if tcp.state == TcpState::Reset => (tcp.state.to_string(), Style::default().fg(Color::LightRed)),
+                Some(tcp) if tcp.state == TcpState::Established => (tcp.state.to_string(), Style::default().fg(Color::Green)),
+                Some(tcp) => (tcp.state.to_string(), Style::default()),
+                None => match &stats.icmp {
+                    Some(icmp) => icmp_state(icmp),
+                    None => ("-".to_string(), Style::default().fg(Color::DarkGray)),
+                },
             };
             
-            let age = format_duration(now.duration_since(stats.first_seen));
-            let last_seen = format_duration(now.duration_since(stats.last_seen));
-            let bytes = format_bytes(stats.byte_count);
+            let rtt = connection_rtt(stats).map_or("-".to_string(), format_rtt);
             
-            Row::new(vec![
+            // Retransmissions / duplicate ACKs / out-of-order / zero windows
+            let (problems, problems_style) = match &stats.tcp {
+                Some(tcp) => {
+                    let counters = &tcp.counters;
+                    let text = format!(
+                        "{}/{}/{}/{}",
+                        counters.retransmissions, counters.dup_acks, counters.out_of_order, counters.zero_windows
+                    );
+                    let any = counters.retransmissions + counters.dup_acks + counters.out_of_order + counters.zero_windows > 0;
+                    (text, if any { Style::default().fg(Color::Yellow) } else { Style::default() })
+                },
+                None => match &stats.icmp {
+                    Some(icmp) if icmp.unanswered > 0 => (format!("{} lost", icmp.unanswered), Style::default().fg(Color::Yellow)),
+                    _ => ("-".to_string(), Style::default().fg(Color::DarkGray)),
+                },
+            };
+            
+            let age = format_duration(now.saturating_sub(stats.first_seen));
+            let last_seen = format_duration(now.saturating_sub(stats.last_seen));
+            
+            let mut cells = Vec::new();
+            if show_interface {
+                cells.push(Cell::from(id.interface.to_string()));
+            }
+            if show_segment {
+                cells.push(Cell::from(id.segment.label()));
+            }
+            if show_process {
+                let process = match &stats.process {
+                    Some(owner) => format!("{} ({})", owner.info.name, owner.info.pid),
+                    None => "-".to_string(),
+                };
+                cells.push(Cell::from(process));
+            }
+            cells.extend([
                 Cell::from(direction),
                 Cell::from(proto),
-                Cell::from(id.src_ip.to_string()),
-                Cell::from(id.dst_ip.to_string()),
-                Cell::from(format!("{}:{}", id.src_port, id.dst_port)),
-                Cell::from(format!("{}", stats.packet_count)),
-                Cell::from(bytes),
+                Cell::from(detected).style(detected_style),
+                Cell::from(state).style(state_style),
+                Cell::from(host(client_ip)),
+                Cell::from(host(server_ip)),
+                Cell::from(match &stats.icmp {
+                    // Echo flows are told apart by their identifier
+                    Some(_) if client_port != 0 => format!("id {}", client_port),
+                    Some(_) => "-".to_string(),
+                    None => format!("{}:{}", client_port, server_port),
+                }),
+            ]);
+            if show_asn {
+                match &stats.asn {
+                    Some(asn) => cells.extend([
+                        Cell::from(format!("AS{}", asn.number)),
+                        Cell::from(asn.organization.clone()),
+                    ]),
+                    None => cells.extend([
+                        Cell::from("-").style(Style::default().fg(Color::DarkGray)),
+                        Cell::from("-").style(Style::default().fg(Color::DarkGray)),
+                    ]),
+                }
+            }
+            if show_tls {
+                match &stats.tls {
+                    Some(tls) => cells.extend([
+                        Cell::from(tls.sni.clone().unwrap_or_else(|| "-".to_string())),
+                        Cell::from(tls_summary(tls)),
+                    ]),
+                    None => cells.extend([
+                        Cell::from("-").style(Style::default().fg(Color::DarkGray)),
+                        Cell::from("-").style(Style::default().fg(Color::DarkGray)),
+                    ]),
+                }
+            }
+            if show_http {
+                match stats.http.session() {
+                    Some(session) => {
+                        let style = if session.errors > 0 { Style::default().fg(Color::Yellow) } else { Style::default() };
+                        cells.push(Cell::from(http_summary(session)).style(style));
+                    },
+                    None => cells.push(Cell::from("-").style(Style::default().fg(Color::DarkGray))),
+                }
+            }
+            cells.extend([
+                Cell::from(format!("{}/{}", stats.tx_packets, stats.rx_packets)),
+                Cell::from(format!("{}/{}", format_bytes(stats.tx_bytes), format_bytes(stats.rx_bytes))),
+                Cell::from(rtt),
+                Cell::from(problems).style(problems_style),
                 Cell::from(age),
                 Cell::from(last_seen),
-            ])
+            ]);
+            
+            Row::new(cells)
         }).collect::<Vec<_>>();
         
         // Define the column widths
-        let widths = [
+        let mut widths = Vec::new();
+        let mut header = Vec::new();
+        if show_interface {
+            widths.push(Constraint::Length(10));
+            header.push(Cell::from("Iface"));
+        }
+        if show_segment {
+            widths.push(Constraint::Length(14));
+            header.push(Cell::from("VLAN/Tunnel"));
+        }
+        if show_process {
+            widths.push(Constraint::Length(22));
+            header.push(Cell::from("Process"));
+        }
+        widths.extend([
             Constraint::Length(4),  // Direction
-            Constraint::Length(8),  // Protocol
-            Constraint::Length(15), // Source IP
-            Constraint::Length(15), // Dest IP
+            Constraint::Length(12), // Protocol
+            Constraint::Length(24), // Detection method
+            Constraint::Length(11), // TCP state
+            Constraint::Length(ip_width), // Client IP
+            Constraint::Length(ip_width), // Server IP
             Constraint::Length(11), // Ports
-            Constraint::Length(8),  // Packets
-            Constraint::Length(10), // Bytes
+        ]);
+        header.extend([
+            Cell::from("Dir"),
+            Cell::from("Proto"),
+            Cell::from("Detected by"),
+            Cell::from("State"),
+            Cell::from(if app.show_hostnames { "Client" } else { "Client IP" }),
+            Cell::from(if app.show_hostnames { "Server" } else { "Server IP" }),
+            Cell::from("Ports"),
+        ]);
+        if show_asn {
+            widths.extend([Constraint::Length(9), Constraint::Length(22)]);
+            header.extend([Cell::from("ASN"), Cell::from("Organization")]);
+        }
+        if show_tls {
+            widths.extend([Constraint::Length(28), Constraint::Length(14)]);
+            header.extend([Cell::from("SNI"), Cell::from("TLS")]);
+        }
+        if show_http {
+            widths.push(Constraint::Length(32));
+            header.push(Cell::from("HTTP"));
+        }
+        widths.extend([
+            Constraint::Length(13), // Packets
+            Constraint::Length(21), // Bytes
+            Constraint::Length(9),  // RTT
+            Constraint::Length(15), // TCP problems
             Constraint::Length(8),  // Age
             Constraint::Length(10), // Last Seen
-        ];
-
+        ]);
+        header.extend([
+            Cell::from("Pkts Tx/Rx"),
+            Cell::from("Bytes Tx/Rx"),
+            Cell::from("RTT"),
+            Cell::from("Rtx/Dup/OOO/ZW"),
+            Cell::from("Age"),
+            Cell::from("Last Seen"),
+        ]);
+        
         // Create the table
+        // Flows that already left the table, so the totals stay complete
+        let expired = conns.expired_for(app.selected_interface());
+        let expired_text = format!(
+            "Expired: {} flows, {} packets, {} (idle {}, closed {}, rolled over {}, evicted {}) | Tracking {}/{}",
+            expired.flows,
+            expired.packets,
+            format_bytes(expired.bytes),
+            expired.idle,
+            expired.closed,
+            expired.active,
+            expired.evicted,
+            conns.flows.len(),
+            conns.max_flows
+        );
+        
+        let mut title = format!(
+            "Connections ({}) | Resets: {} | Failed handshakes: {}",
+            connections.len(),
+            conns.tcp_resets,
+            conns.failed_handshakes
+        );
+        if let Some(names) = &host_names {
+            let (passive, resolved) = names.counts();
+            title.push_str(&format!(" | Names: {} from DNS traffic", passive));
+            if names.reverse_lookups() {
+                title.push_str(&format!(", {} from reverse DNS", resolved));
+            }
+        }
+        
         let table = Table::new(rows, widths)
-            .header(Row::new(vec![
-                Cell::from("Dir"),
-                Cell::from("Proto"),
-                Cell::from("Source IP"),
-                Cell::from("Dest IP"),
-                Cell::from("Ports"),
-                Cell::from("Packets"),
-                Cell::from("Bytes"),
-                Cell::from("Age"),
-                Cell::from("Last Seen"),
-            ]).style(Style::default().fg(Color::Yellow)))
-            .block(Block::default().borders(Borders::ALL).title(format!("Connections ({})", connections.len())))
+            .header(Row::new(header).style(Style::default().fg(Color::Yellow)))
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title(Title::from(expired_text).position(Position::Bottom)))
             .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
             .highlight_symbol("> ");
         
-        f.render_stateful_widget(table, chunks[1], &mut table_state.clone());
+        f.render_stateful_widget(table, body_chunks[0], &mut table_state.clone());
+        
+        if let Some((title, text)) = details {
+            let details = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL).title(title));
+            f.render_widget(details, body_chunks[1]);
+        }
         
         // Show message if no connections
         if connections.is_empty() {
             let message = Paragraph::new("No connections matching current filter...")
                 .alignment(Alignment::Center)
                 .style(Style::default().fg(Color::Gray));
-                
-            let message_area = centered_rect(60, 20, chunks[1]);
+            
+            let message_area = centered_rect(60, 20, body_chunks[0]);
             f.render_widget(message, message_area);
         }
     } else {
@@ -167,4 +443,128 @@ pub fn draw_connections(f: &mut Frame, app: &mut App, area: Rect) {
             .alignment(Alignment::Center);
         f.render_widget(message, chunks[1]);
     }
-}
\ No newline at end of file
+}
+
+// Best RTT estimate for a flow: the running one, else the handshake's, or
+// the echo round trip of ICMP flows
+fn connection_rtt(stats: &ConnectionStats) -> Option<Duration> {
+    if let Some(icmp) = &stats.icmp {
+        return icmp.srtt;
+    }
+    let tcp = stats.tcp.as_ref()?;
+    tcp.srtt.or(tcp.handshake_rtt)
+}
+
+// What the payload revealed about a flow, for the details box
+fn flow_details(stats: &ConnectionStats) -> Option<(&'static str, String)> {
+    let quic = stats.quic.session();
+    if let Some(tls) = &stats.tls {
+        // QUIC flows carry theirs in Initial packets
+        let (title, transport) = match quic {
+            Some(session) => ("QUIC Initial", format!("QUIC {} | ", quic_version_name(session.version))),
+            None => ("TLS ClientHello", String::new()),
+        };
+        let text = format!(
+            "{}{} | SNI: {} | ALPN: {}\nJA3: {} | JA4: {}",
+            transport,
+            version_name(tls.version),
+            tls.sni.as_deref().unwrap_or("-"),
+            if tls.alpn.is_empty() { "-".to_string() } else { tls.alpn.join(", ") },
+            tls.ja3,
+            tls.ja4
+        );
+        return Some((title, text));
+    }
+    if let Some(session) = quic {
+        let text = format!(
+            "Version: {} | Client Initial packets: {}\nNo ClientHello recovered",
+            quic_version_name(session.version),
+            session.initials
+        );
+        return Some(("QUIC", text));
+    }
+    
+    if let Some(icmp) = &stats.icmp {
+        return Some(("ICMP", icmp_details(icmp)));
+    }
+    
+    let session = stats.http.session()?;
+    let request = session.last_request.as_ref()?;
+    let text = format!(
+        "Requests: {} | Responses: {} | Errors: {} | Content-Length total: {}\nLast: {} {}{} -> {} | User-Agent: {}",
+        session.requests,
+        session.responses,
+        session.errors,
+        format_bytes(session.response_bytes),
+        request.method,
+        request.host.as_deref().unwrap_or(""),
+        request.path,
+        session.last_status.map_or("-".to_string(), |status| status.to_string()),
+        request.user_agent.as_deref().unwrap_or("-")
+    );
+    Some(("HTTP", text))
+}
+
+// Latest error, else how the pings are going
+fn icmp_state(icmp: &IcmpSession) -> (String, Style) {
+    let (state, style) = match icmp.last_message {
+        Some(message) if message.is_error() => (message.short_name(), Style::default().fg(Color::Red)),
+        _ if icmp.replies > 0 => ("REPLIED", Style::default().fg(Color::Green)),
+        _ if icmp.unanswered > 0 => ("NO REPLY", Style::default().fg(Color::Yellow)),
+        _ => ("ECHO", Style::default()),
+    };
+    (state.to_string(), style)
+}
+
+// Echo counts and round trips, and what the latest error reported
+fn icmp_details(icmp: &IcmpSession) -> String {
+    let echo = format!(
+        "Echo: {} requests, {} replies, {} unanswered | RTT: last {}, smoothed {}",
+        icmp.requests,
+        icmp.replies,
+        icmp.unanswered,
+        icmp.last_rtt.map_or("-".to_string(), format_rtt),
+        icmp.srtt.map_or("-".to_string(), format_rtt)
+    );
+    let last = match icmp.last_message {
+        Some(message) if message.is_error() => {
+            let about = icmp.about.map_or(String::new(), |about| format!(" for {}", about.label()));
+            let mtu = icmp.mtu.map_or(String::new(), |mtu| format!(" | Next-hop MTU: {}", mtu));
+            format!("Errors: {} | Last: {}{}{}", icmp.errors, message.name(), about, mtu)
+        },
+        Some(message) => format!("Errors: {} | Last: {}", icmp.errors, message.name()),
+        None => format!("Errors: {}", icmp.errors),
+    };
+    format!("{}\n{}", echo, last)
+}
+
+// Status and latest request, e.g. "200 GET example.com/index.html", with
+// the status first so long paths don't hide it
+fn http_summary(session: &HttpSession) -> String {
+    let Some(request) = &session.last_request else { return "-".to_string() };
+    format!(
+        "{} {} {}{}",
+        session.last_status.map_or("-".to_string(), |status| status.to_string()),
+        request.method,
+        request.host.as_deref().unwrap_or(""),
+        request.path
+    )
+}
+
+// Version and first offered protocol, e.g. "1.3 h2"
+fn tls_summary(tls: &TlsInfo) -> String {
+    let version = version_name(tls.version);
+    let version = version.trim_start_matches("TLS ");
+    match tls.alpn.first() {
+        Some(protocol) => format!("{} {}", version, protocol),
+        None => version.to_string(),
+    }
+}
+
+// Case-insensitive match of part of the server name, a protocol or a fingerprint
+fn tls_matches(tls: &TlsInfo, search: &str) -> bool {
+    tls.sni.as_ref().is_some_and(|sni| sni.contains(search)) ||
+        tls.alpn.iter().any(|protocol| protocol.to_lowercase().contains(search)) ||
+        tls.ja3.contains(search) ||
+        tls.ja4.contains(search)
+}
@@ -5,7 +5,7 @@ use ratatui::{
 };
 
 use crate::app::App;
-use crate::network::types::{BasicProtocolType, PacketType, ProtocolGrouping, get_basic_type};
+use crate::network::types::{ProtocolGrouping, TrafficCategory, counts_by_label, traffic_category};
 
 pub fn draw_packet_bar_chart(f: &mut Frame, app: &mut App, area: Rect) {
     let block = Block::default()
@@ -16,8 +16,10 @@ pub fn draw_packet_bar_chart(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(block, area);
     
     if let Ok(stats) = app.packet_stats.try_lock() {
+        let counts = stats.counts_for(app.selected_interface());
+        
         // Check if we have any data
-        let total: u64 = stats.counts.values().sum();
+        let total: u64 = counts.values().sum();
         
         // Avoid division by zero
         if total == 0 {
@@ -31,43 +33,43 @@ pub fn draw_packet_bar_chart(f: &mut Frame, app: &mut App, area: Rect) {
         let data = match app.protocol_grouping {
             ProtocolGrouping::Basic => {
                 // Group by basic protocol type
-                let tcp_count: u64 = stats.counts.iter()
-                    .filter(|(k, _)| matches!(get_basic_type(**k), BasicProtocolType::TCP))
+                let tcp_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Tcp)
+                    .map(|(_, v)| *v)
+                    .sum();
+                
+                let udp_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Udp)
+                    .map(|(_, v)| *v)
+                    .sum();
+                
+                let quic_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Quic)
                     .map(|(_, v)| *v)
                     .sum();
                 
-                let udp_count: u64 = stats.counts.iter()
-                    .filter(|(k, _)| matches!(get_basic_type(**k), BasicProtocolType::UDP))
+                let icmp_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Icmp)
                     .map(|(_, v)| *v)
                     .sum();
                 
-                let icmp_count = *stats.counts.get(&PacketType::ICMP).unwrap_or(&0);
-                let other_count = *stats.counts.get(&PacketType::Other).unwrap_or(&0);
+                // Includes ARP and the rest of non-IP traffic
+                let other_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Other)
+                    .map(|(_, v)| *v)
+                    .sum();
                 
                 vec![
                     ("TCP", tcp_count),
                     ("UDP", udp_count),
+                    ("QUIC", quic_count),
                     ("ICMP", icmp_count),
                     ("Other", other_count),
                 ]
             },
             ProtocolGrouping::Detailed => {
-                // Show detailed breakdown
-                let mut data = vec![
-                    ("HTTP", *stats.counts.get(&PacketType::TCP_HTTP).unwrap_or(&0)),
-                    ("HTTPS", *stats.counts.get(&PacketType::TCP_HTTPS).unwrap_or(&0)),
-                    ("SSH", *stats.counts.get(&PacketType::TCP_SSH).unwrap_or(&0)),
-                    ("TCP_DNS", *stats.counts.get(&PacketType::TCP_DNS).unwrap_or(&0)),
-                    ("TCP_Other", *stats.counts.get(&PacketType::TCP_Other).unwrap_or(&0)),
-                    ("UDP_DNS", *stats.counts.get(&PacketType::UDP_DNS).unwrap_or(&0)),
-                    ("DHCP", *stats.counts.get(&PacketType::UDP_DHCP).unwrap_or(&0)),
-                    ("UDP_Other", *stats.counts.get(&PacketType::UDP_Other).unwrap_or(&0)),
-                    ("ICMP", *stats.counts.get(&PacketType::ICMP).unwrap_or(&0)),
-                    ("Other", *stats.counts.get(&PacketType::Other).unwrap_or(&0)),
-                ];
-                
-                // Sort by count (descending) for better visualization
-                data.sort_by(|a, b| b.1.cmp(&a.1));
+                // Show detailed breakdown, one bar per service
+                let mut data = counts_by_label(counts);
                 
                 // Limit to top 8 for better display
                 if data.len() > 8 {
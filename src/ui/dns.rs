@@ -0,0 +1,207 @@
+use ratatui::{
+    prelude::*,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+};
+
+use crate::app::App;
+use crate::network::dns::{rcode_name, record_type_name, DnsTransaction, RCODE_NXDOMAIN, RCODE_SERVFAIL, SLOW_RESPONSE};
+use crate::utils::{format_duration, format_rtt, centered_rect};
+
+// Answers shown per query before the rest are summarized
+const MAX_SHOWN_ANSWERS: usize = 3;
+
+pub fn draw_dns(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),      // Summary
+            Constraint::Percentage(35), // Top domains and slow responses
+            Constraint::Min(0),         // Recent queries
+        ])
+        .split(area);
+    
+    let Ok(dns) = app.dns_stats.try_lock() else {
+        let message = Paragraph::new("Could not access DNS data...")
+            .alignment(Alignment::Center);
+        f.render_widget(message, area);
+        return;
+    };
+    
+    // Summary of everything seen so far
+    let latency_text = match dns.average_latency() {
+        Some(average) => format!("avg {}, max {}", format_rtt(average), format_rtt(dns.latency_max)),
+        None => "-".to_string(),
+    };
+    
+    let mut query_types: Vec<(&u16, &u64)> = dns.query_types.iter().collect();
+    query_types.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+    let types_text = query_types.iter()
+        .take(5)
+        .map(|(qtype, count)| format!("{} {:.0}%", record_type_name(**qtype), **count as f64 / dns.queries.max(1) as f64 * 100.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+    
+    let summary_text = format!(
+        "Queries: {} | Responses: {} | Unanswered: {} | NXDOMAIN: {:.1}% | SERVFAIL: {:.1}%\nLatency: {} | Slow (>{}): {} | Types: {}",
+        dns.queries,
+        dns.responses,
+        dns.unanswered,
+        dns.rcode_rate(RCODE_NXDOMAIN),
+        dns.rcode_rate(RCODE_SERVFAIL),
+        latency_text,
+        format_rtt(SLOW_RESPONSE),
+        dns.slow_responses,
+        if types_text.is_empty() { "-".to_string() } else { types_text }
+    );
+    
+    let summary = Paragraph::new(summary_text)
+        .block(Block::default().borders(Borders::ALL).title("DNS Summary"));
+    f.render_widget(summary, chunks[0]);
+    
+    let middle_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(50), // Top domains
+            Constraint::Percentage(50), // Slow responses
+        ])
+        .split(chunks[1]);
+    
+    // Most queried names
+    let mut domains: Vec<_> = dns.domains.iter().collect();
+    domains.sort_by_key(|(_, domain)| std::cmp::Reverse(domain.queries));
+    
+    let domain_rows = domains.iter()
+        .take(middle_chunks[0].height as usize)
+        .map(|(name, domain)| {
+            let failures_style = if domain.failures > 0 { Style::default().fg(Color::Yellow) } else { Style::default() };
+            Row::new(vec![
+                Cell::from(name.to_string()),
+                Cell::from(domain.queries.to_string()),
+                Cell::from(domain.failures.to_string()).style(failures_style),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let domain_table = Table::new(domain_rows, [
+        Constraint::Min(20),     // Domain
+        Constraint::Length(8),   // Queries
+        Constraint::Length(8),   // Failures
+    ])
+        .header(Row::new(vec![
+            Cell::from("Domain"),
+            Cell::from("Queries"),
+            Cell::from("Failed"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Top Domains ({})", dns.domains.len())));
+    f.render_widget(domain_table, middle_chunks[0]);
+    
+    // Slowest recent responses
+    let now = app.capture_time();
+    let slow_rows = dns.slow.iter()
+        .map(|transaction| {
+            Row::new(vec![
+                Cell::from(format_duration(now.saturating_sub(transaction.time))),
+                Cell::from(transaction.name.clone()),
+                Cell::from(transaction.server.to_string()),
+                Cell::from(transaction.latency.map_or("-".to_string(), format_rtt)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let slow_table = Table::new(slow_rows, [
+        Constraint::Length(8),   // Age
+        Constraint::Min(16),     // Name
+        Constraint::Length(16),  // Server
+        Constraint::Length(9),   // Latency
+    ])
+        .header(Row::new(vec![
+            Cell::from("Age"),
+            Cell::from("Name"),
+            Cell::from("Server"),
+            Cell::from("Latency"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Slow Responses (>{})", format_rtt(SLOW_RESPONSE))));
+    f.render_widget(slow_table, middle_chunks[1]);
+    
+    // Recent queries, newest first
+    app.dns_scroll = app.dns_scroll.min(dns.log.len().saturating_sub(1));
+    
+    let log_rows = dns.log.iter()
+        .map(|transaction| {
+            let (result, result_style) = transaction_result(transaction);
+            let latency_style = if transaction.latency.is_some_and(|latency| latency >= SLOW_RESPONSE) {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            
+            Row::new(vec![
+                Cell::from(format_duration(now.saturating_sub(transaction.time))),
+                Cell::from(transaction.client.to_string()),
+                Cell::from(transaction.server.to_string()),
+                Cell::from(record_type_name(transaction.qtype)),
+                Cell::from(transaction.name.clone()),
+                Cell::from(result).style(result_style),
+                Cell::from(transaction.latency.map_or("-".to_string(), format_rtt)).style(latency_style),
+                Cell::from(answers_text(transaction)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let log_table = Table::new(log_rows, [
+        Constraint::Length(8),   // Age
+        Constraint::Length(16),  // Client
+        Constraint::Length(16),  // Server
+        Constraint::Length(6),   // Type
+        Constraint::Min(24),     // Name
+        Constraint::Length(9),   // Result
+        Constraint::Length(9),   // Latency
+        Constraint::Min(20),     // Answers
+    ])
+        .header(Row::new(vec![
+            Cell::from("Age"),
+            Cell::from("Client"),
+            Cell::from("Server"),
+            Cell::from("Type"),
+            Cell::from("Name"),
+            Cell::from("Result"),
+            Cell::from("Latency"),
+            Cell::from("Answers"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Recent Queries ({}) - ↑/↓ to scroll", dns.log.len())))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    
+    let mut table_state = TableState::default().with_selected(Some(app.dns_scroll));
+    f.render_stateful_widget(log_table, chunks[2], &mut table_state);
+    
+    if dns.log.is_empty() {
+        let message = Paragraph::new("No DNS queries seen yet...")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(message, centered_rect(60, 20, chunks[2]));
+    }
+}
+
+// Response code, or TIMEOUT for queries that never got one
+fn transaction_result(transaction: &DnsTransaction) -> (String, Style) {
+    match transaction.rcode {
+        None => ("TIMEOUT".to_string(), Style::default().fg(Color::Red)),
+        Some(0) => (rcode_name(0), Style::default().fg(Color::Green)),
+        Some(RCODE_NXDOMAIN) => (rcode_name(RCODE_NXDOMAIN), Style::default().fg(Color::Yellow)),
+        Some(rcode) => (rcode_name(rcode), Style::default().fg(Color::Red)),
+    }
+}
+
+fn answers_text(transaction: &DnsTransaction) -> String {
+    let mut text = transaction.answers.iter()
+        .take(MAX_SHOWN_ANSWERS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if transaction.answers.len() > MAX_SHOWN_ANSWERS {
+        text.push_str(&format!(" (+{})", transaction.answers.len() - MAX_SHOWN_ANSWERS));
+    }
+    text
+}
@@ -0,0 +1,79 @@
+use ratatui::{
+    prelude::*,
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph},
+};
+
+use crate::app::App;
+
+pub fn draw_filter_prompt(f: &mut Frame, app: &App, area: Rect) {
+    let input = match &app.filter_input {
+        Some(input) => input,
+        None => return,
+    };
+    
+    // Anchor the prompt near the bottom of the screen
+    let height = 5.min(area.height);
+    let prompt_area = Rect {
+        x: area.x + area.width / 10,
+        y: area.y + area.height.saturating_sub(height + 1),
+        width: area.width - area.width / 5,
+        height,
+    };
+    
+    f.render_widget(ratatui::widgets::Clear, prompt_area);
+    
+    // Show the last compile error so it can be corrected in place
+    let error = app.capture_filter.try_lock()
+        .ok()
+        .and_then(|filter| filter.error.clone());
+    
+    let mut lines = vec![Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Yellow)),
+        Span::raw(input.as_str()),
+        Span::styled("_", Style::default().fg(Color::Gray)),
+    ])];
+    
+    match error {
+        Some(error) => lines.push(Line::styled(format!("Error: {}", error), Style::default().fg(Color::Red))),
+        None => lines.push(Line::styled("e.g. tcp port 443, udp and not port 53, host 10.0.0.1", Style::default().fg(Color::Gray))),
+    }
+    lines.push(Line::styled("Enter: apply | Esc: cancel | empty: capture everything", Style::default().fg(Color::Gray)));
+    
+    let prompt = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("BPF Capture Filter"));
+    
+    f.render_widget(prompt, prompt_area);
+}
+
+pub fn draw_tls_search_prompt(f: &mut Frame, app: &App, area: Rect) {
+    let input = match &app.tls_search_input {
+        Some(input) => input,
+        None => return,
+    };
+    
+    let height = 5.min(area.height);
+    let prompt_area = Rect {
+        x: area.x + area.width / 10,
+        y: area.y + area.height.saturating_sub(height + 1),
+        width: area.width - area.width / 5,
+        height,
+    };
+    
+    f.render_widget(ratatui::widgets::Clear, prompt_area);
+    
+    let lines = vec![
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Yellow)),
+            Span::raw(input.as_str()),
+            Span::styled("_", Style::default().fg(Color::Gray)),
+        ]),
+        Line::styled("Part of a server name, ALPN protocol, JA3 hash or JA4 fingerprint, e.g. google.com, h2, t13d", Style::default().fg(Color::Gray)),
+        Line::styled("Enter: apply | Esc: cancel | empty: show everything", Style::default().fg(Color::Gray)),
+    ];
+    
+    let prompt = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Search TLS Connections"));
+    
+    f.render_widget(prompt, prompt_area);
+}
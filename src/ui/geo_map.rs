@@ -6,7 +6,7 @@ use ratatui::{
 };
 
 use crate::app::App;
-use crate::network::types::GeoMode;
+use crate::network::types::{AsnTraffic, CountryTraffic, GeoLocation, GeoMode, GeoStats};
 use crate::utils::centered_rect;
 
 // We'll use a simpler approach for geo points to avoid implementation complexity
@@ -51,6 +51,66 @@ fn draw_country_point(
     });
 }
 
+fn format_coordinates(location: &GeoLocation) -> String {
+    match (location.latitude, location.longitude) {
+        (Some(latitude), Some(longitude)) => format!("{:.4}, {:.4}", latitude, longitude),
+        _ => "-".to_string(),
+    }
+}
+
+// Percentage of a total, zero when there is no traffic yet
+fn traffic_share(packets: u64, total: u64) -> f64 {
+    if total > 0 {
+        packets as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+// Traffic per autonomous system, busiest first
+fn draw_networks(f: &mut Frame, geo: &GeoStats, area: Rect) {
+    let mut networks: Vec<&AsnTraffic> = geo.networks.values().collect();
+    networks.sort_by_key(|network| std::cmp::Reverse(network.packets));
+    let total_packets: u64 = networks.iter().map(|network| network.packets).sum();
+    
+    let rows = networks.iter().map(|network| {
+        Row::new(vec![
+            Cell::from(format!("AS{}", network.info.number)),
+            Cell::from(network.info.organization.clone()),
+            Cell::from(network.packets.to_string()),
+            Cell::from(format!("{:.1}%", traffic_share(network.packets, total_packets))),
+            Cell::from(network.hosts_label()),
+        ])
+    }).collect::<Vec<_>>();
+    
+    let widths = [
+        Constraint::Length(9),   // ASN
+        Constraint::Min(16),     // Organization
+        Constraint::Length(10),  // Packets
+        Constraint::Length(8),   // Percentage
+        Constraint::Length(6),   // Hosts
+    ];
+    
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec![
+            Cell::from("ASN"),
+            Cell::from("Organization"),
+            Cell::from("Packets"),
+            Cell::from("% Total"),
+            Cell::from("Hosts"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Networks ({} total)", networks.len())));
+    
+    f.render_widget(table, area);
+    
+    if networks.is_empty() {
+        let message = Paragraph::new("No network data collected yet...")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(message, centered_rect(60, 20, area));
+    }
+}
+
 pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
     // Create a layout with header and body
     let chunks = Layout::default()
@@ -61,11 +121,34 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
         ])
         .split(area);
     
+    // Without a database there is nothing to look addresses up in
+    if app.geoip.is_none() && app.asn_db.is_none() {
+        let header = Paragraph::new("No GeoIP database loaded")
+            .block(Block::default().borders(Borders::ALL).title("Geographic Traffic Map"))
+            .alignment(Alignment::Center);
+        f.render_widget(header, chunks[0]);
+        
+        let message = Paragraph::new(
+            "Start with --geoip-db <FILE> to see where traffic goes.\n\n\
+             Any MaxMind or DB-IP .mmdb database works, e.g. GeoLite2-City.mmdb or dbip-city-lite.mmdb.\n\
+             Add --asn-db <FILE> to also see which networks and organizations it goes to",
+        )
+            .block(Block::default().borders(Borders::ALL))
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(message, chunks[1]);
+        return;
+    }
+    
     // Create header
-    let header_text = format!(
-        "View Mode: {} | Use 'f' to change view | Shows traffic destinations by country",
-        app.geo_mode.to_string()
+    let mut header_text = format!(
+        "View Mode: {} | Use 'f' to change view | Database: {}",
+        app.geo_mode.to_string(),
+        app.geoip.as_ref().map_or("none".to_string(), |geoip| geoip.description())
     );
+    if let Some(asn_db) = &app.asn_db {
+        header_text.push_str(&format!(" | ASN: {}", asn_db.description()));
+    }
     
     let header = Paragraph::new(header_text)
         .block(Block::default().borders(Borders::ALL).title("Geographic Traffic Map"))
@@ -78,8 +161,9 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
         match app.geo_mode {
             GeoMode::CountryList => {
                 // Create a sorted list of countries by traffic
-                let mut country_list: Vec<(&String, &(crate::network::types::GeoLocation, u64))> = geo.locations.iter().collect();
-                country_list.sort_by(|(_, (_, a_count)), (_, (_, b_count))| b_count.cmp(a_count));
+                let mut country_list: Vec<(&String, &CountryTraffic)> = geo.locations.iter().collect();
+                country_list.sort_by_key(|(_, traffic)| std::cmp::Reverse(traffic.packets));
+                let total_packets: u64 = country_list.iter().map(|(_, traffic)| traffic.packets).sum();
                 
                 // Create a two-panel split for the country list and details
                 let country_chunks = Layout::default()
@@ -90,33 +174,48 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                     ])
                     .split(chunks[1]);
                 
+                // Networks go next to the countries when an ASN database is loaded
+                let (country_area, network_area) = if app.asn_db.is_some() {
+                    let list_chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([
+                            Constraint::Percentage(55), // Countries
+                            Constraint::Percentage(45), // Networks
+                        ])
+                        .split(country_chunks[0]);
+                    (list_chunks[0], Some(list_chunks[1]))
+                } else {
+                    (country_chunks[0], None)
+                };
+                
+                if let Some(network_area) = network_area {
+                    draw_networks(f, &geo, network_area);
+                }
+                
                 // Ensure app's selection index is within bounds
                 if app.geo_country_selection >= country_list.len() && !country_list.is_empty() {
                     app.geo_country_selection = country_list.len() - 1;
                 }
                 
                 // Create a table for country data
-                let rows = country_list.iter().map(|(country, (location, count))| {
-                    let percentage = if geo.locations.values().map(|(_, c)| *c).sum::<u64>() > 0 {
-                        let total: u64 = geo.locations.values().map(|(_, c)| *c).sum();
-                        format!("{:.1}%", (*count as f64 / total as f64) * 100.0)
-                    } else {
-                        "0.0%".to_string()
-                    };
+                let rows = country_list.iter().map(|(country, traffic)| {
+                    let location = &traffic.location;
                     
                     Row::new(vec![
                         Cell::from(country.to_string()),
-                        Cell::from(location.region.clone()),
-                        Cell::from(count.to_string()),
-                        Cell::from(percentage),
-                        Cell::from(format!("{:.4}, {:.4}", location.latitude, location.longitude)),
+                        Cell::from(location.country_name.clone()),
+                        Cell::from(location.continent.clone()),
+                        Cell::from(traffic.packets.to_string()),
+                        Cell::from(format!("{:.1}%", traffic_share(traffic.packets, total_packets))),
+                        Cell::from(format_coordinates(location)),
                     ])
                 }).collect::<Vec<_>>();
                 
                 // Create widths
                 let widths = [
-                    Constraint::Length(6),   // Country
-                    Constraint::Length(15),  // Region
+                    Constraint::Length(7),   // Country
+                    Constraint::Length(24),  // Name
+                    Constraint::Length(15),  // Continent
                     Constraint::Length(10),  // Count
                     Constraint::Length(8),   // Percentage
                     Constraint::Length(20),  // Coordinates
@@ -130,7 +229,8 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                 let table = Table::new(rows, widths)
                     .header(Row::new(vec![
                         Cell::from("Country"),
-                        Cell::from("Region"),
+                        Cell::from("Name"),
+                        Cell::from("Continent"),
                         Cell::from("Packets"),
                         Cell::from("% Total"),
                         Cell::from("Coordinates"),
@@ -140,40 +240,42 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                     .highlight_symbol("> ");
                 
                 // Render table with selection
-                f.render_stateful_widget(table, country_chunks[0], &mut table_state);
+                f.render_stateful_widget(table, country_area, &mut table_state);
                 
                 // Show message if no countries
                 if country_list.is_empty() {
-                    let message = Paragraph::new("No geographic data collected yet...")
+                    let text = if app.geoip.is_some() {
+                        "No geographic data collected yet..."
+                    } else {
+                        "No GeoIP database loaded (--geoip-db)"
+                    };
+                    let message = Paragraph::new(text)
                         .alignment(Alignment::Center)
                         .style(Style::default().fg(Color::Gray));
                     
-                    let message_area = centered_rect(60, 20, country_chunks[0]);
+                    let message_area = centered_rect(60, 20, country_area);
                     f.render_widget(message, message_area);
                 } else {
                     // Show detailed information for the selected country
                     let selected_idx = app.geo_country_selection;
                     if selected_idx < country_list.len() {
-                        let (country_code, (location, packet_count)) = country_list[selected_idx];
-                        
-                        // Calculate percentage of total traffic
-                        let total_packets: u64 = geo.locations.values().map(|(_, c)| *c).sum();
-                        let percentage = (*packet_count as f64 / total_packets as f64) * 100.0;
+                        let (country_code, traffic) = country_list[selected_idx];
+                        let location = &traffic.location;
                         
                         // Create a multi-column layout for details
                         let detail_chunks = Layout::default()
                             .direction(Direction::Horizontal)
                             .constraints([
                                 Constraint::Percentage(50),  // Basic info
-                                Constraint::Percentage(50),  // Additional stats
+                                Constraint::Percentage(50),  // Cities
                             ])
                             .split(country_chunks[1]);
                         
                         // Basic country info
                         let country_info = format!(
-                            "Country: {}\nRegion: {}\nLatitude: {:.4}\nLongitude: {:.4}\n\nTotal Packets: {}\nTraffic Share: {:.2}%",
-                            country_code, location.region, location.latitude, location.longitude, 
-                            packet_count, percentage
+                            "Country: {} ({})\nContinent: {}\nCoordinates: {}\n\nTotal Packets: {}\nTraffic Share: {:.2}%\nCities: {}",
+                            location.country_name, country_code, location.continent, format_coordinates(location),
+                            traffic.packets, traffic_share(traffic.packets, total_packets), traffic.cities.len()
                         );
                         
                         let info_widget = Paragraph::new(country_info)
@@ -182,31 +284,31 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                         
                         f.render_widget(info_widget, detail_chunks[0]);
                         
-                        // Additional traffic statistics
-                        // For this example, we'll just create a placeholder with recommendations
-                        // In a real app, you could analyze traffic patterns, show historical data, etc.
-                        let stats_text = format!(
-                            "Traffic Trend: {}\nAverage Packet Size: {:.1} bytes\nFirst Detected: {} mins ago\nRecommendations: {}",
-                            if *packet_count > 1000 { "High Volume ▲" } else { "Normal ◆" },
-                            // Generate a fake average packet size between 100 and 1500 bytes
-                            100.0 + (*packet_count as f64 % 1400.0),
-                            // Fake time since first detected
-                            (*packet_count % 60) + 5,
-                            // Give a recommendation based on traffic volume
-                            if *packet_count > 5000 {
-                                "Consider rate limiting traffic from this region"
-                            } else if *packet_count > 1000 {
-                                "Monitor for traffic pattern changes"
-                            } else {
-                                "No special attention required"
-                            }
-                        );
+                        // Busiest cities in the country
+                        let mut cities: Vec<(&String, &u64)> = traffic.cities.iter().collect();
+                        cities.sort_by(|(_, a), (_, b)| b.cmp(a));
                         
-                        let stats_widget = Paragraph::new(stats_text)
-                            .block(Block::default().borders(Borders::ALL).title("Traffic Analysis"))
-                            .style(Style::default().fg(Color::White));
+                        let city_rows = cities.iter().map(|(city, packets)| {
+                            Row::new(vec![
+                                Cell::from(city.to_string()),
+                                Cell::from(packets.to_string()),
+                                Cell::from(format!("{:.1}%", traffic_share(**packets, traffic.packets))),
+                            ])
+                        }).collect::<Vec<_>>();
                         
-                        f.render_widget(stats_widget, detail_chunks[1]);
+                        let city_table = Table::new(city_rows, [
+                            Constraint::Min(16),     // City
+                            Constraint::Length(10),  // Packets
+                            Constraint::Length(8),   // Share of the country
+                        ])
+                            .header(Row::new(vec![
+                                Cell::from("City"),
+                                Cell::from("Packets"),
+                                Cell::from("Share"),
+                            ]).style(Style::default().fg(Color::Yellow)))
+                            .block(Block::default().borders(Borders::ALL).title("Top Cities"));
+                        
+                        f.render_widget(city_table, detail_chunks[1]);
                     }
                 }
             },
@@ -433,7 +535,11 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                         });
                         
                         // Show traffic dots at their coordinates
-                        for (_, (location, count)) in &geo.locations {
+                        for traffic in geo.locations.values() {
+                            let location = &traffic.location;
+                            let (Some(latitude), Some(longitude)) = (location.latitude, location.longitude) else { continue };
+                            let count = &traffic.packets;
+                            
                             // Make the point size relative to the traffic volume
                             let point_size = if *count > 100 {
                                 5
@@ -445,21 +551,22 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                                 2
                             };
                             
-                            // Choose color based on region
-                            let color = match location.region.as_str() {
+                            // Choose color based on continent
+                            let color = match location.continent.as_str() {
                                 "North America" => Color::Red,
                                 "South America" => Color::Yellow,
                                 "Europe" => Color::Blue,
                                 "Asia" => Color::Green,
                                 "Oceania" => Color::Magenta,
+                                "Africa" => Color::Cyan,
                                 _ => Color::White,
                             };
                             
                             // Draw a point at the location using our helper function
                             draw_country_point(
                                 ctx,
-                                location.longitude,
-                                location.latitude,
+                                longitude,
+                                latitude,
                                 color,
                                 point_size
                             );
@@ -475,6 +582,7 @@ pub fn draw_geo_map(f: &mut Frame, app: &mut App, area: Rect) {
                     "Europe: Blue",
                     "Asia: Green",
                     "Oceania: Magenta",
+                    "Africa: Cyan",
                     "Other: White",
                 ];
                 
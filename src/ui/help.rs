@@ -23,14 +23,35 @@ q: Quit the application
 ←/→: Navigate between tabs
 l: Toggle between Linear and Logarithmic scale
 g: Toggle between Basic and Detailed protocol view
+i: Switch between all interfaces and a single one
+b: Edit the BPF capture filter (Enter applies, Esc cancels)
+w: Save the ring buffer of recent packets (--ring-buffer) to a pcapng file
 h: Show/hide this help
 
 ----- Connections Tab Shortcuts -----
-s: Change sorting (Packets, Bytes, Age, First Seen)
-f: Filter connections (All, Outbound, Inbound, HTTP, HTTPS, DNS)
+s: Change sorting (Packets, Bytes, Age, First Seen, RTT, Retransmissions, VLAN/Tunnel)
+f: Filter connections (All, Outbound, Inbound, TLS, ICMP, then each service seen)
+v: Filter by VLAN / tunnel
+t: Filter by TCP state (SYN, SYN-ACK, ESTABLISHED, FIN_WAIT, RST, CLOSED)
+p: Filter by process
+n: Show host names instead of IP addresses
+/: Search TLS connections by server name, ALPN, JA3 or JA4
 ↑/↓: Navigate connections list
 PgUp/PgDn: Page up/down in connections list
 
+----- Processes Tab Shortcuts -----
+↑/↓: Select a process
+Enter: Show the selected process's connections
+
+----- DNS Tab Shortcuts -----
+↑/↓: Scroll through recent queries
+
+----- HTTP Tab Shortcuts -----
+↑/↓: Scroll through recent requests
+
+----- Neighbors Tab Shortcuts -----
+↑/↓: Scroll through IP to MAC bindings
+
 ----- Geo Map Tab Shortcuts -----
 f: Toggle between Country List and World Map view
 ↑/↓: Navigate through countries in the Country List view
@@ -0,0 +1,194 @@
+use ratatui::{
+    prelude::*,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+};
+
+use crate::app::App;
+use crate::network::http::{status_reason, HttpExchange};
+use crate::utils::{format_bytes, format_duration, format_rtt, centered_rect};
+
+pub fn draw_http(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),      // Summary
+            Constraint::Percentage(35), // Status codes and slowest requests
+            Constraint::Min(0),         // Recent requests
+        ])
+        .split(area);
+    
+    let Ok(http) = app.http_stats.try_lock() else {
+        let message = Paragraph::new("Could not access HTTP data...")
+            .alignment(Alignment::Center);
+        f.render_widget(message, area);
+        return;
+    };
+    
+    // Summary of everything seen so far
+    let latency_text = match http.average_latency() {
+        Some(average) => format!("avg {}, max {}", format_rtt(average), format_rtt(http.latency_max)),
+        None => "-".to_string(),
+    };
+    
+    let mut methods: Vec<(&String, &u64)> = http.methods.iter().collect();
+    methods.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+    let methods_text = methods.iter()
+        .take(5)
+        .map(|(method, count)| format!("{} {:.0}%", method, **count as f64 / http.requests.max(1) as f64 * 100.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+    
+    let summary_text = format!(
+        "Requests: {} | Responses: {} | Unanswered: {} | 2xx: {:.1}% | 3xx: {:.1}% | 4xx: {:.1}% | 5xx: {:.1}%\nLatency: {} | Hosts: {} | Methods: {}",
+        http.requests,
+        http.responses,
+        http.requests.saturating_sub(http.responses),
+        http.status_class_rate(2),
+        http.status_class_rate(3),
+        http.status_class_rate(4),
+        http.status_class_rate(5),
+        latency_text,
+        http.hosts.len(),
+        if methods_text.is_empty() { "-".to_string() } else { methods_text }
+    );
+    
+    let summary = Paragraph::new(summary_text)
+        .block(Block::default().borders(Borders::ALL).title("HTTP Summary"));
+    f.render_widget(summary, chunks[0]);
+    
+    let middle_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(35), // Status codes
+            Constraint::Percentage(65), // Slowest requests
+        ])
+        .split(chunks[1]);
+    
+    // How responses break down by status
+    let mut statuses: Vec<(&u16, &u64)> = http.statuses.iter().collect();
+    statuses.sort_by_key(|(status, count)| (std::cmp::Reverse(**count), **status));
+    
+    let status_rows = statuses.iter()
+        .map(|(status, count)| {
+            Row::new(vec![
+                Cell::from(status.to_string()).style(status_style(**status)),
+                Cell::from(status_reason(**status)),
+                Cell::from(count.to_string()),
+                Cell::from(format!("{:.1}%", **count as f64 / http.responses.max(1) as f64 * 100.0)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let status_table = Table::new(status_rows, [
+        Constraint::Length(6),   // Status
+        Constraint::Min(12),     // Reason
+        Constraint::Length(8),   // Count
+        Constraint::Length(7),   // Share
+    ])
+        .header(Row::new(vec![
+            Cell::from("Status"),
+            Cell::from("Reason"),
+            Cell::from("Count"),
+            Cell::from("%"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title("Status Codes"));
+    f.render_widget(status_table, middle_chunks[0]);
+    
+    // Slowest responses so far
+    let slow_rows = http.slowest.iter()
+        .map(|exchange| {
+            Row::new(vec![
+                Cell::from(format_rtt(exchange.latency)),
+                Cell::from(exchange.status.to_string()).style(status_style(exchange.status)),
+                Cell::from(exchange.request.method.clone()),
+                Cell::from(request_target(exchange)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let slow_table = Table::new(slow_rows, [
+        Constraint::Length(9),   // Latency
+        Constraint::Length(6),   // Status
+        Constraint::Length(7),   // Method
+        Constraint::Min(20),     // Host and path
+    ])
+        .header(Row::new(vec![
+            Cell::from("Latency"),
+            Cell::from("Status"),
+            Cell::from("Method"),
+            Cell::from("Request"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title("Slowest Requests"));
+    f.render_widget(slow_table, middle_chunks[1]);
+    
+    // Recent requests, newest first
+    app.http_scroll = app.http_scroll.min(http.log.len().saturating_sub(1));
+    
+    let now = app.capture_time();
+    let log_rows = http.log.iter()
+        .map(|exchange| {
+            Row::new(vec![
+                Cell::from(format_duration(now.saturating_sub(exchange.time))),
+                Cell::from(exchange.client.0.to_string()),
+                Cell::from(format!("{}:{}", exchange.server.0, exchange.server.1)),
+                Cell::from(exchange.request.method.clone()),
+                Cell::from(request_target(exchange)),
+                Cell::from(exchange.status.to_string()).style(status_style(exchange.status)),
+                Cell::from(exchange.content_length.map_or("-".to_string(), format_bytes)),
+                Cell::from(format_rtt(exchange.latency)),
+                Cell::from(exchange.request.user_agent.clone().unwrap_or_else(|| "-".to_string())),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let log_table = Table::new(log_rows, [
+        Constraint::Length(8),   // Age
+        Constraint::Length(16),  // Client
+        Constraint::Length(22),  // Server
+        Constraint::Length(7),   // Method
+        Constraint::Min(30),     // Host and path
+        Constraint::Length(6),   // Status
+        Constraint::Length(10),  // Size
+        Constraint::Length(9),   // Latency
+        Constraint::Min(20),     // User-Agent
+    ])
+        .header(Row::new(vec![
+            Cell::from("Age"),
+            Cell::from("Client"),
+            Cell::from("Server"),
+            Cell::from("Method"),
+            Cell::from("Request"),
+            Cell::from("Status"),
+            Cell::from("Size"),
+            Cell::from("Latency"),
+            Cell::from("User-Agent"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Recent Requests ({}) - ↑/↓ to scroll", http.log.len())))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    
+    let mut table_state = TableState::default().with_selected(Some(app.http_scroll));
+    f.render_stateful_widget(log_table, chunks[2], &mut table_state);
+    
+    if http.log.is_empty() {
+        let message = Paragraph::new("No plain HTTP requests seen yet...")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(message, centered_rect(60, 20, chunks[2]));
+    }
+}
+
+// Host and path as one would type them
+fn request_target(exchange: &HttpExchange) -> String {
+    format!("{}{}", exchange.request.host.as_deref().unwrap_or(""), exchange.request.path)
+}
+
+fn status_style(status: u16) -> Style {
+    match status / 100 {
+        2 => Style::default().fg(Color::Green),
+        3 => Style::default().fg(Color::Cyan),
+        4 => Style::default().fg(Color::Yellow),
+        _ => Style::default().fg(Color::Red),
+    }
+}
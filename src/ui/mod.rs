@@ -2,12 +2,22 @@ pub mod overview;
 pub mod packet_graph;
 pub mod distribution;
 pub mod connections;
+pub mod processes;
+pub mod dns;
+pub mod http;
+pub mod neighbors;
 pub mod geo_map;
 pub mod help;
+pub mod filter;
 
 pub use overview::*;
 pub use packet_graph::*;
 pub use distribution::*;
 pub use connections::*;
+pub use processes::*;
+pub use dns::*;
+pub use http::*;
+pub use neighbors::*;
 pub use geo_map::*;
-pub use help::*;
\ No newline at end of file
+pub use help::*;
+pub use filter::*;
\ No newline at end of file
@@ -0,0 +1,166 @@
+use ratatui::{
+    prelude::*,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+};
+
+use crate::app::App;
+use crate::network::neighbors::{Neighbor, NeighborEventKind};
+use crate::utils::{format_duration, centered_rect};
+
+pub fn draw_neighbors(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),      // Summary
+            Constraint::Min(0),         // Neighbor table
+            Constraint::Percentage(30), // Events
+        ])
+        .split(area);
+    
+    let Ok(table) = app.neighbors.try_lock() else {
+        let message = Paragraph::new("Could not access neighbor data...")
+            .alignment(Alignment::Center);
+        f.render_widget(message, area);
+        return;
+    };
+    
+    let mut neighbors: Vec<&Neighbor> = table.neighbors.values()
+        .filter(|neighbor| app.selected_interface().is_none_or(|name| *neighbor.interface == *name))
+        .collect();
+    neighbors.sort_by(|a, b| a.interface.cmp(&b.interface).then(a.ip.cmp(&b.ip)));
+    
+    // Summary of everything seen so far
+    let summary_text = format!(
+        "Neighbors: {} | ARP packets: {} | NDP packets: {} | Duplicate IPs: {} | MAC changes: {}",
+        neighbors.len(),
+        table.arp_packets,
+        table.ndp_packets,
+        table.duplicates,
+        table.mac_changes
+    );
+    let summary = Paragraph::new(summary_text)
+        .block(Block::default().borders(Borders::ALL).title("Neighbor Summary"));
+    f.render_widget(summary, chunks[0]);
+    
+    // Bindings, with the suspicious ones highlighted
+    app.neighbor_scroll = app.neighbor_scroll.min(neighbors.len().saturating_sub(1));
+    let show_interface = app.interface_stats.len() > 1;
+    
+    let now = app.capture_time();
+    let rows = neighbors.iter()
+        .map(|neighbor| {
+            let (flags, style) = if neighbor.duplicate {
+                ("DUPLICATE".to_string(), Style::default().fg(Color::Red))
+            } else if let Some(previous) = neighbor.previous_mac {
+                (format!("was {}", previous), Style::default().fg(Color::Yellow))
+            } else {
+                (String::new(), Style::default())
+            };
+            
+            let mut cells = Vec::new();
+            if show_interface {
+                cells.push(Cell::from(neighbor.interface.to_string()));
+            }
+            cells.extend([
+                Cell::from(neighbor.ip.to_string()),
+                Cell::from(neighbor.mac.to_string()),
+                Cell::from(neighbor.vendor.as_deref().unwrap_or("-").to_string()),
+                Cell::from(neighbor.protocol.to_string()),
+                Cell::from(neighbor.packets.to_string()),
+                Cell::from(neighbor.macs.to_string()),
+                Cell::from(format_duration(now.saturating_sub(neighbor.first_seen))),
+                Cell::from(format_duration(now.saturating_sub(neighbor.last_seen))),
+                Cell::from(flags),
+            ]);
+            Row::new(cells).style(style)
+        })
+        .collect::<Vec<_>>();
+    
+    let mut widths = Vec::new();
+    let mut header = Vec::new();
+    if show_interface {
+        widths.push(Constraint::Length(10));
+        header.push(Cell::from("Iface"));
+    }
+    widths.extend([
+        Constraint::Length(39),  // IP
+        Constraint::Length(17),  // MAC
+        Constraint::Length(22),  // Vendor
+        Constraint::Length(5),   // Protocol
+        Constraint::Length(8),   // Packets
+        Constraint::Length(5),   // MACs
+        Constraint::Length(10),  // First seen
+        Constraint::Length(10),  // Last seen
+        Constraint::Min(20),     // Flags
+    ]);
+    header.extend([
+        Cell::from("IP"),
+        Cell::from("MAC"),
+        Cell::from("Vendor"),
+        Cell::from("Via"),
+        Cell::from("Packets"),
+        Cell::from("MACs"),
+        Cell::from("First Seen"),
+        Cell::from("Last Seen"),
+        Cell::from("Flags"),
+    ]);
+    
+    let neighbor_table = Table::new(rows, widths)
+        .header(Row::new(header).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Neighbors ({}) - ↑/↓ to scroll", neighbors.len())))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    
+    let mut table_state = TableState::default().with_selected(Some(app.neighbor_scroll));
+    f.render_stateful_widget(neighbor_table, chunks[1], &mut table_state);
+    
+    if neighbors.is_empty() {
+        let message = Paragraph::new("No ARP or Neighbor Discovery traffic seen yet...")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(message, centered_rect(60, 20, chunks[1]));
+    }
+    
+    // Duplicate addresses and MAC changes, newest first
+    let event_rows = table.events.iter()
+        .filter(|event| app.selected_interface().is_none_or(|name| *event.interface == *name))
+        .take(chunks[2].height as usize)
+        .map(|event| {
+            let style = match event.kind {
+                NeighborEventKind::DuplicateIp => Style::default().fg(Color::Red),
+                NeighborEventKind::MacChanged => Style::default().fg(Color::Yellow),
+            };
+            Row::new(vec![
+                Cell::from(format_duration(now.saturating_sub(event.time))),
+                Cell::from(event.kind.to_string()).style(style),
+                Cell::from(event.interface.to_string()),
+                Cell::from(event.ip.to_string()),
+                Cell::from(event.old_mac.to_string()),
+                Cell::from(event.new_mac.to_string()),
+                Cell::from(event.vendor.as_deref().unwrap_or("-").to_string()),
+            ])
+        })
+        .collect::<Vec<_>>();
+    
+    let event_table = Table::new(event_rows, [
+        Constraint::Length(8),   // Age
+        Constraint::Length(13),  // Kind
+        Constraint::Length(10),  // Interface
+        Constraint::Length(39),  // IP
+        Constraint::Length(17),  // Old MAC
+        Constraint::Length(17),  // New MAC
+        Constraint::Min(16),     // New vendor
+    ])
+        .header(Row::new(vec![
+            Cell::from("Age"),
+            Cell::from("Event"),
+            Cell::from("Iface"),
+            Cell::from("IP"),
+            Cell::from("Old MAC"),
+            Cell::from("New MAC"),
+            Cell::from("New Vendor"),
+        ]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().borders(Borders::ALL).title(format!("Events ({})", table.events.len())));
+    f.render_widget(event_table, chunks[2]);
+}
@@ -1,13 +1,20 @@
+use std::sync::atomic::Ordering;
+
 use chrono::Local;
 use ratatui::{
     prelude::*,
     style::{Color, Style},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table},
 };
 
 use crate::app::App;
-use crate::network::types::{BasicProtocolType, get_basic_type};
-use crate::utils::{format_bytes, format_bytes_per_sec};
+use crate::network::types::{BasicProtocolType, CaptureSource, NetworkStats, PacketType, ReplayMode, TrafficCategory, get_basic_type, traffic_category};
+use crate::utils::{format_bytes, format_bytes_per_sec, format_rtt};
+
+// Services listed per transport in the detailed packet counts
+const MAX_OVERVIEW_SERVICES: usize = 5;
+// ICMP message types listed, most frequent first
+const MAX_OVERVIEW_ICMP_TYPES: usize = 6;
 
 pub fn draw_network_overview(f: &mut Frame, app: &mut App, area: Rect) {
     let chunks = Layout::default()
@@ -17,27 +24,70 @@ pub fn draw_network_overview(f: &mut Frame, app: &mut App, area: Rect) {
             Constraint::Length(3),
             Constraint::Length(3),
             Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Length(4),
+            Constraint::Length(3),
             Constraint::Min(0),
         ])
         .split(area);
     
-    // Interface name
-    let interface_text = format!("Interface: {}", app.network_stats.interface_name);
+    // Interface names, or the capture file being replayed
+    let (source_title, interface_text) = match app.sources.first() {
+        Some(CaptureSource::File { replay, .. }) => {
+            let status = if app.capture_finished.load(Ordering::Relaxed) {
+                "replay complete"
+            } else {
+                match replay {
+                    ReplayMode::Realtime => "replaying at original speed",
+                    ReplayMode::Fast => "replaying",
+                }
+            };
+            ("Capture File", format!("File: {} ({})", app.interface_stats[0].interface_name, status))
+        },
+        _ => {
+            let names = app.interface_stats.iter()
+                .map(|stats| {
+                    if Some(stats.interface_name.as_str()) == app.selected_interface() {
+                        format!("[{}]", stats.interface_name)
+                    } else {
+                        stats.interface_name.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            
+            if app.interface_stats.len() > 1 {
+                let view = app.selected_interface().unwrap_or("all");
+                ("Interfaces", format!("Interfaces: {} | Viewing: {} (i to switch)", names, view))
+            } else {
+                ("Interface", format!("Interface: {}", names))
+            }
+        },
+    };
     let interface = Paragraph::new(interface_text)
-        .block(Block::default().borders(Borders::ALL).title("Interface"));
+        .block(Block::default().borders(Borders::ALL).title(source_title));
     f.render_widget(interface, chunks[0]);
     
+    // Traffic of the selected interface, or summed over all of them
+    let viewed: Vec<&NetworkStats> = app.interface_stats.iter()
+        .filter(|stats| app.selected_interface().is_none_or(|name| name == stats.interface_name))
+        .collect();
+    let rx_bytes: u64 = viewed.iter().map(|stats| stats.rx_bytes).sum();
+    let tx_bytes: u64 = viewed.iter().map(|stats| stats.tx_bytes).sum();
+    let rx_speed: f64 = viewed.iter().map(|stats| stats.rx_speed).sum();
+    let tx_speed: f64 = viewed.iter().map(|stats| stats.tx_speed).sum();
+    
     // Total traffic
-    let total_rx = format_bytes(app.network_stats.rx_bytes);
-    let total_tx = format_bytes(app.network_stats.tx_bytes);
+    let total_rx = format_bytes(rx_bytes);
+    let total_tx = format_bytes(tx_bytes);
     let total_text = format!("Total RX: {}\nTotal TX: {}", total_rx, total_tx);
     let total = Paragraph::new(total_text)
         .block(Block::default().borders(Borders::ALL).title("Traffic Totals"));
     f.render_widget(total, chunks[1]);
     
     // Current speeds
-    let rx_speed = format_bytes_per_sec(app.network_stats.rx_speed);
-    let tx_speed = format_bytes_per_sec(app.network_stats.tx_speed);
+    let rx_speed = format_bytes_per_sec(rx_speed);
+    let tx_speed = format_bytes_per_sec(tx_speed);
     let speed_text = format!("RX: {}\nTX: {}", rx_speed, tx_speed);
     let speeds = Paragraph::new(speed_text)
         .block(Block::default().borders(Borders::ALL).title("Current Speed"));
@@ -45,48 +95,74 @@ pub fn draw_network_overview(f: &mut Frame, app: &mut App, area: Rect) {
     
     // Packet counts
     if let Ok(stats) = app.packet_stats.try_lock() {
+        let counts = stats.counts_for(app.selected_interface());
         let packet_text = match app.protocol_grouping {
             crate::network::types::ProtocolGrouping::Basic => {
                 // Group by basic categories
-                let tcp_count: u64 = stats.counts.iter()
-                    .filter(|(k, _)| matches!(get_basic_type(**k), BasicProtocolType::TCP))
+                let tcp_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Tcp)
                     .map(|(_, v)| *v)
                     .sum();
                 
-                let udp_count: u64 = stats.counts.iter()
-                    .filter(|(k, _)| matches!(get_basic_type(**k), BasicProtocolType::UDP))
+                let udp_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Udp)
                     .map(|(_, v)| *v)
                     .sum();
                 
-                let icmp_count = *stats.counts.get(&crate::network::types::PacketType::ICMP).unwrap_or(&0);
-                let other_count = *stats.counts.get(&crate::network::types::PacketType::Other).unwrap_or(&0);
+                let quic_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Quic)
+                    .map(|(_, v)| *v)
+                    .sum();
+                
+                let icmp_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Icmp)
+                    .map(|(_, v)| *v)
+                    .sum();
+                
+                // Includes ARP and the rest of non-IP traffic
+                let other_count: u64 = counts.iter()
+                    .filter(|(k, _)| traffic_category(**k) == TrafficCategory::Other)
+                    .map(|(_, v)| *v)
+                    .sum();
                 
                 format!(
-                    "TCP: {} | UDP: {} | ICMP: {} | Other: {}", 
-                    tcp_count, udp_count, icmp_count, other_count
+                    "TCP: {} | UDP: {} | QUIC: {} | ICMP: {} | Other: {}", 
+                    tcp_count, udp_count, quic_count, icmp_count, other_count
                 )
             },
             crate::network::types::ProtocolGrouping::Detailed => {
-                // Show detailed breakdown
-                let http_count = *stats.counts.get(&crate::network::types::PacketType::TCP_HTTP).unwrap_or(&0);
-                let https_count = *stats.counts.get(&crate::network::types::PacketType::TCP_HTTPS).unwrap_or(&0);
-                let ssh_count = *stats.counts.get(&crate::network::types::PacketType::TCP_SSH).unwrap_or(&0);
-                let tcp_dns_count = *stats.counts.get(&crate::network::types::PacketType::TCP_DNS).unwrap_or(&0);
-                let tcp_other_count = *stats.counts.get(&crate::network::types::PacketType::TCP_Other).unwrap_or(&0);
-                
-                let udp_dns_count = *stats.counts.get(&crate::network::types::PacketType::UDP_DNS).unwrap_or(&0);
-                let dhcp_count = *stats.counts.get(&crate::network::types::PacketType::UDP_DHCP).unwrap_or(&0);
-                let udp_other_count = *stats.counts.get(&crate::network::types::PacketType::UDP_Other).unwrap_or(&0);
-                
-                let tcp_total = http_count + https_count + ssh_count + tcp_dns_count + tcp_other_count;
-                let udp_total = udp_dns_count + dhcp_count + udp_other_count;
+                // Each transport with its busiest services
+                let section = |basic: BasicProtocolType, name: &str| {
+                    let mut services: Vec<(&'static str, u64)> = counts.iter()
+                        .filter(|(k, count)| get_basic_type(**k) == basic && k.service.is_some() && **count > 0)
+                        .map(|(k, count)| (k.label(), *count))
+                        .collect();
+                    services.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+                    
+                    let total: u64 = counts.iter()
+                        .filter(|(k, _)| get_basic_type(**k) == basic)
+                        .map(|(_, count)| *count)
+                        .sum();
+                    let unknown = *counts.get(&PacketType { basic, service: None }).unwrap_or(&0);
+                    
+                    let mut text = format!("{}({})", name, total);
+                    for (service, count) in services.iter().take(MAX_OVERVIEW_SERVICES) {
+                        text.push_str(&format!(" {}:{}", service, count));
+                    }
+                    if services.len() > MAX_OVERVIEW_SERVICES {
+                        text.push_str(" ...");
+                    }
+                    text.push_str(&format!(" Other:{}", unknown));
+                    text
+                };
                 
                 format!(
-                    "TCP({}) HTTP:{} HTTPS:{} SSH:{} DNS:{} Other:{} | UDP({}) DNS:{} DHCP:{} Other:{} | ICMP:{} | Other:{}", 
-                    tcp_total, http_count, https_count, ssh_count, tcp_dns_count, tcp_other_count,
-                    udp_total, udp_dns_count, dhcp_count, udp_other_count,
-                    *stats.counts.get(&crate::network::types::PacketType::ICMP).unwrap_or(&0),
-                    *stats.counts.get(&crate::network::types::PacketType::Other).unwrap_or(&0)
+                    "{} | {} | ICMP:{} | Other:{} | ARP:{}", 
+                    section(BasicProtocolType::TCP, "TCP"),
+                    section(BasicProtocolType::UDP, "UDP"),
+                    *counts.get(&PacketType::ICMP).unwrap_or(&0),
+                    *counts.get(&PacketType::OTHER).unwrap_or(&0),
+                    *counts.get(&PacketType::ARP).unwrap_or(&0)
                 )
             }
         };
@@ -96,11 +172,172 @@ pub fn draw_network_overview(f: &mut Frame, app: &mut App, area: Rect) {
         f.render_widget(packets, chunks[3]);
     }
     
+    // TCP connection quality
+    if let Ok(conns) = app.connections.try_lock() {
+        let health = conns.tcp_health(app.selected_interface());
+        let counters = &health.counters;
+        
+        let percent = |count: u64| {
+            if counters.segments > 0 {
+                count as f64 * 100.0 / counters.segments as f64
+            } else {
+                0.0
+            }
+        };
+        
+        let handshake_text = match health.handshake_rtt {
+            Some(rtt) => format!("{} avg over {} handshakes", format_rtt(rtt), health.handshake_samples),
+            None => "-".to_string(),
+        };
+        let rtt_text = match (health.rtt, health.rtt_max) {
+            (Some(avg), Some(max)) => format!("{} avg, {} max", format_rtt(avg), format_rtt(max)),
+            _ => "-".to_string(),
+        };
+        
+        let health_lines = vec![
+            Line::from(format!("Handshake RTT: {} | RTT: {}", handshake_text, rtt_text)),
+            Line::from(format!(
+                "Retransmissions: {} ({:.2}%) | Out-of-order: {} | Duplicate ACKs: {} | Zero window: {} | Segments: {}",
+                counters.retransmissions,
+                percent(counters.retransmissions),
+                counters.out_of_order,
+                counters.dup_acks,
+                counters.zero_windows,
+                counters.segments
+            )).style(if counters.retransmissions > 0 && percent(counters.retransmissions) >= 1.0 {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }),
+        ];
+        
+        let health_widget = Paragraph::new(health_lines)
+            .block(Block::default().borders(Borders::ALL).title("TCP Health"));
+        f.render_widget(health_widget, chunks[4]);
+    }
+    
+    // ICMP echo round trips and the message types seen
+    let unanswered_echoes = app.connections.try_lock().map_or(0, |conns| conns.unanswered_echoes);
+    if let Ok(icmp) = app.icmp_stats.try_lock() {
+        let rtt_text = match icmp.average_rtt() {
+            Some(avg) => format!("{} avg, {} max", format_rtt(avg), format_rtt(icmp.rtt_max)),
+            None => "-".to_string(),
+        };
+        let mtu_text = icmp.smallest_mtu.map_or("-".to_string(), |mtu| mtu.to_string());
+        let types_text = icmp.top_messages().iter()
+            .take(MAX_OVERVIEW_ICMP_TYPES)
+            .map(|(message, count)| format!("{}: {}", message.name(), count))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        
+        let icmp_lines = vec![
+            Line::from(format!(
+                "Echo requests: {} | Replies: {} | Unanswered: {} | RTT: {} | Errors: {} | Smallest reported MTU: {}",
+                icmp.echo_requests,
+                icmp.echo_replies,
+                unanswered_echoes,
+                rtt_text,
+                icmp.errors,
+                mtu_text
+            )),
+            Line::from(if types_text.is_empty() { "-".to_string() } else { types_text }).style(if icmp.errors > 0 {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }),
+        ];
+        
+        let icmp_widget = Paragraph::new(icmp_lines)
+            .block(Block::default().borders(Borders::ALL).title("ICMP"));
+        f.render_widget(icmp_widget, chunks[5]);
+    }
+    
+    // IP fragments and how their reassembly went
+    if let Ok(fragments) = app.fragments.try_lock() {
+        let stats = &fragments.stats;
+        let (waiting, buffered) = fragments.in_progress();
+        let fragment_text = format!(
+            "Fragments: {} | Reassembled: {} | In progress: {} ({}) | Duplicates: {} | Overlaps: {} | Timeouts: {} | Dropped: {}",
+            stats.fragments,
+            stats.reassembled,
+            waiting,
+            format_bytes(buffered as u64),
+            stats.duplicates,
+            stats.overlaps,
+            stats.timeouts,
+            stats.dropped
+        );
+        let style = if stats.overlaps + stats.timeouts + stats.dropped > 0 {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        
+        let fragment_widget = Paragraph::new(fragment_text)
+            .style(style)
+            .block(Block::default().borders(Borders::ALL).title("IP Fragments"));
+        f.render_widget(fragment_widget, chunks[6]);
+    }
+    
+    // Per-interface breakdown when watching several interfaces at once
+    let date_area = if app.interface_stats.len() > 1 && app.selected_interface().is_none() {
+        let bottom_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(3),
+            ])
+            .split(chunks[7]);
+        
+        let packet_totals: Vec<u64> = match app.packet_stats.try_lock() {
+            Ok(stats) => app.interface_stats.iter()
+                .map(|iface| stats.counts_for(Some(&iface.interface_name)).values().sum())
+                .collect(),
+            Err(_) => vec![0; app.interface_stats.len()],
+        };
+        
+        let rows = app.interface_stats.iter().zip(packet_totals).map(|(iface, packets)| {
+            Row::new(vec![
+                Cell::from(iface.interface_name.clone()),
+                Cell::from(format_bytes_per_sec(iface.rx_speed)),
+                Cell::from(format_bytes_per_sec(iface.tx_speed)),
+                Cell::from(format_bytes(iface.rx_bytes)),
+                Cell::from(format_bytes(iface.tx_bytes)),
+                Cell::from(packets.to_string()),
+            ])
+        }).collect::<Vec<_>>();
+        
+        let widths = [
+            Constraint::Length(16), // Interface
+            Constraint::Length(14), // RX speed
+            Constraint::Length(14), // TX speed
+            Constraint::Length(12), // RX total
+            Constraint::Length(12), // TX total
+            Constraint::Length(10), // Captured packets
+        ];
+        
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec![
+                Cell::from("Interface"),
+                Cell::from("RX/s"),
+                Cell::from("TX/s"),
+                Cell::from("Total RX"),
+                Cell::from("Total TX"),
+                Cell::from("Packets"),
+            ]).style(Style::default().fg(Color::Yellow)))
+            .block(Block::default().borders(Borders::ALL).title("Per-Interface Breakdown"));
+        f.render_widget(table, bottom_chunks[0]);
+        
+        bottom_chunks[1]
+    } else {
+        chunks[7]
+    };
+    
     // Date and time
     let now = Local::now();
     let date_time = now.format("%Y-%m-%d %H:%M:%S").to_string();
     let date_widget = Paragraph::new(date_time)
         .block(Block::default().borders(Borders::ALL).title("Date & Time"))
         .alignment(Alignment::Center);
-    f.render_widget(date_widget, chunks[4]);
+    f.render_widget(date_widget, date_area);
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use ratatui::{
     prelude::*,
     style::{Color, Style},
@@ -6,7 +8,21 @@ use ratatui::{
 };
 
 use crate::app::App;
-use crate::network::types::{BasicProtocolType, GraphScale, PacketType, ProtocolGrouping, get_basic_type};
+use crate::network::types::{GraphScale, PacketType, ProtocolGrouping, TrafficCategory, counts_by_label, traffic_category};
+
+// Line colors of the detailed view, one per service shown
+const DETAILED_COLORS: [Color; 10] = [
+    Color::Red,
+    Color::LightRed,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::Green,
+    Color::LightGreen,
+    Color::Cyan,
+    Color::Blue,
+    Color::Yellow,
+    Color::DarkGray,
+];
 
 pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
     // Create title with scale and grouping info
@@ -20,7 +36,10 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
         ProtocolGrouping::Detailed => "Detailed View",
     };
     
-    let title = format!("Network Traffic ({}, {})", group_text, scale_text);
+    let title = match app.selected_interface() {
+        Some(interface) => format!("Network Traffic on {} ({}, {})", interface, group_text, scale_text),
+        None => format!("Network Traffic ({}, {})", group_text, scale_text),
+    };
     
     let block = Block::default()
         .borders(Borders::ALL)
@@ -30,7 +49,9 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(block, area);
     
     if let Ok(stats) = app.packet_stats.try_lock() {
-        if stats.history.len() < 2 {
+        let history = stats.history_for(app.selected_interface());
+        
+        if history.len() < 2 {
             // Not enough data yet
             let message = Paragraph::new("Collecting data...")
                 .alignment(Alignment::Center);
@@ -40,7 +61,7 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
         
         // Get data for the graph
         let max_points = inner_area.width as usize - 2;
-        let history_len = stats.history.len();
+        let history_len = history.len();
         let start_idx = if history_len <= max_points {
             0
         } else {
@@ -58,6 +79,7 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
                 // Basic grouping - combine by protocol type
                 let mut tcp_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_len];
                 let mut udp_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_len];
+                let mut quic_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_len];
                 let mut icmp_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_len];
                 let mut other_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_len];
                 
@@ -68,16 +90,18 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
                     // Initialize with x-coordinate
                     tcp_data[i].0 = i as f64;
                     udp_data[i].0 = i as f64;
+                    quic_data[i].0 = i as f64;
                     icmp_data[i].0 = i as f64;
                     other_data[i].0 = i as f64;
                     
                     // Aggregate counts by basic type
-                    for (packet_type, count) in stats.history[idx].iter() {
-                        match get_basic_type(*packet_type) {
-                            BasicProtocolType::TCP => tcp_data[i].1 += *count as f64,
-                            BasicProtocolType::UDP => udp_data[i].1 += *count as f64,
-                            BasicProtocolType::ICMP => icmp_data[i].1 += *count as f64,
-                            BasicProtocolType::Other => other_data[i].1 += *count as f64,
+                    for (packet_type, count) in history[idx].iter() {
+                        match traffic_category(*packet_type) {
+                            TrafficCategory::Tcp => tcp_data[i].1 += *count as f64,
+                            TrafficCategory::Udp => udp_data[i].1 += *count as f64,
+                            TrafficCategory::Quic => quic_data[i].1 += *count as f64,
+                            TrafficCategory::Icmp => icmp_data[i].1 += *count as f64,
+                            TrafficCategory::Other => other_data[i].1 += *count as f64,
                         }
                     }
                 }
@@ -88,6 +112,7 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
                         // Add 1 to avoid ln(0) which is undefined
                         tcp_data[i].1 = (tcp_data[i].1 + 1.0).ln();
                         udp_data[i].1 = (udp_data[i].1 + 1.0).ln();
+                        quic_data[i].1 = (quic_data[i].1 + 1.0).ln();
                         icmp_data[i].1 = (icmp_data[i].1 + 1.0).ln();
                         other_data[i].1 = (other_data[i].1 + 1.0).ln();
                     }
@@ -96,36 +121,41 @@ pub fn draw_packet_graph(f: &mut Frame, app: &mut App, area: Rect) {
                 vec![
                     ("TCP".to_string(), Color::LightRed, tcp_data),
                     ("UDP".to_string(), Color::LightGreen, udp_data),
+                    ("QUIC".to_string(), Color::LightMagenta, quic_data),
                     ("ICMP".to_string(), Color::LightBlue, icmp_data),
                     ("Other".to_string(), Color::LightYellow, other_data),
                 ]
             },
             ProtocolGrouping::Detailed => {
-                // Detailed view - show all protocol types
-                let packet_types = [
-                    (PacketType::TCP_HTTP, "HTTP", Color::Red),
-                    (PacketType::TCP_HTTPS, "HTTPS", Color::LightRed),
-                    (PacketType::TCP_SSH, "SSH", Color::LightMagenta),
-                    (PacketType::TCP_DNS, "TCP-DNS", Color::LightCyan),
-                    (PacketType::TCP_Other, "TCP-Other", Color::DarkGray),
-                    (PacketType::UDP_DNS, "UDP-DNS", Color::Green),
-                    (PacketType::UDP_DHCP, "DHCP", Color::LightGreen),
-                    (PacketType::UDP_Other, "UDP-Other", Color::Cyan),
-                    (PacketType::ICMP, "ICMP", Color::Blue),
-                    (PacketType::Other, "Other", Color::Yellow),
-                ];
+                // Detailed view - the busiest services over the visible window
+                let mut window_counts: HashMap<PacketType, u64> = HashMap::new();
+                for counts in history[start_idx..start_idx + data_len].iter() {
+                    for (packet_type, count) in counts.iter() {
+                        *window_counts.entry(*packet_type).or_default() += count;
+                    }
+                }
+                
+                let packet_types: Vec<(&'static str, Color)> = counts_by_label(&window_counts)
+                    .into_iter()
+                    .take(DETAILED_COLORS.len())
+                    .zip(DETAILED_COLORS)
+                    .map(|((name, _), color)| (name, color))
+                    .collect();
                 
                 let mut result = Vec::new();
                 
-                for (packet_type, name, color) in packet_types.iter() {
+                for (name, color) in packet_types.iter() {
                     let mut data: Vec<(f64, f64)> = (0..data_len)
                         .map(|i| {
                             let idx = start_idx + i;
-                            let count = stats.history[idx].get(packet_type).unwrap_or(&0);
-                            (i as f64, *count as f64)
+                            let count: u64 = history[idx].iter()
+                                .filter(|(packet_type, _)| packet_type.label() == *name)
+                                .map(|(_, count)| count)
+                                .sum();
+                            (i as f64, count as f64)
                         })
                         .collect();
-                        
+                    
                     // Apply logarithmic scale if selected
                     if matches!(app.graph_scale, GraphScale::Logarithmic) {
                         for point in data.iter_mut() {
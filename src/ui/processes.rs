@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use ratatui::{
+    prelude::*,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+};
+
+use crate::app::App;
+use crate::network::flow::sorted_processes;
+use crate::utils::{format_bytes, format_bytes_per_sec, centered_rect};
+
+pub fn draw_processes(f: &mut Frame, app: &mut App, area: Rect) {
+    if !app.process_attribution {
+        let message = Paragraph::new(
+            "Process attribution is unavailable: it needs a live capture on Linux with a readable /proc (see --proc-root)",
+        )
+            .block(Block::default().borders(Borders::ALL).title("Processes"))
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(message, area);
+        return;
+    }
+    
+    if let Ok(conns) = app.connections.try_lock() {
+        let processes = sorted_processes(&conns);
+        app.process_selection = app.process_selection.min(processes.len().saturating_sub(1));
+        
+        // Flows currently in the table for each process
+        let mut live_flows: HashMap<u32, usize> = HashMap::new();
+        for stats in conns.flows.values() {
+            if let Some(owner) = &stats.process {
+                *live_flows.entry(owner.info.pid).or_default() += 1;
+            }
+        }
+        
+        let rows = processes.iter().map(|traffic| {
+            let live = live_flows.get(&traffic.info.pid).copied().unwrap_or(0);
+            
+            Row::new(vec![
+                Cell::from(traffic.info.pid.to_string()),
+                Cell::from(traffic.info.name.clone()),
+                Cell::from(traffic.info.user.clone()),
+                Cell::from(format!("{}/{}", live, traffic.flows)),
+                Cell::from(format_bytes_per_sec(traffic.tx_rate)),
+                Cell::from(format_bytes_per_sec(traffic.rx_rate)),
+                Cell::from(format_bytes(traffic.tx_bytes)),
+                Cell::from(format_bytes(traffic.rx_bytes)),
+            ])
+        }).collect::<Vec<_>>();
+        
+        let widths = [
+            Constraint::Length(8),  // PID
+            Constraint::Length(18), // Process
+            Constraint::Length(12), // User
+            Constraint::Length(12), // Flows
+            Constraint::Length(14), // Upload rate
+            Constraint::Length(14), // Download rate
+            Constraint::Length(12), // Sent
+            Constraint::Length(12), // Received
+        ];
+        
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec![
+                Cell::from("PID"),
+                Cell::from("Process"),
+                Cell::from("User"),
+                Cell::from("Flows Now/All"),
+                Cell::from("Up/s"),
+                Cell::from("Down/s"),
+                Cell::from("Sent"),
+                Cell::from("Received"),
+            ]).style(Style::default().fg(Color::Yellow)))
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Processes ({}) | Enter shows a process's connections", processes.len())))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        
+        let mut table_state = TableState::default().with_selected(Some(app.process_selection));
+        f.render_stateful_widget(table, area, &mut table_state);
+        
+        if processes.is_empty() {
+            let message = Paragraph::new("No connections mapped to a process yet...")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::Gray));
+            f.render_widget(message, centered_rect(60, 20, area));
+        }
+    } else {
+        let message = Paragraph::new("Could not access connection data...")
+            .alignment(Alignment::Center);
+        f.render_widget(message, area);
+    }
+}
@@ -1,48 +1,94 @@
 use std::{
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
     time::Duration,
 };
 
-// Simplified network range for checking if an IP is local
+// Network range (IPv4 or IPv6) for checking if an IP is local
 #[derive(Debug, Clone)]
 pub struct IpRange {
-    base: [u8; 4],
-    mask: [u8; 4],
+    base: IpAddr,
+    prefix: u8,
 }
 
 impl IpRange {
     pub fn new(base: [u8; 4], prefix: u8) -> Self {
-        let mut mask = [0; 4];
-        for i in 0..4 {
-            let i_usize = i as usize;
-            if (i_usize * 8) < prefix as usize {
-                if (i_usize + 1) * 8 <= prefix as usize {
-                    // Full byte is masked
-                    mask[i_usize] = 0xFF;
-                } else {
-                    // Partial byte
-                    let bits = prefix as usize - (i_usize * 8);
-                    mask[i_usize] = 0xFF << (8 - bits);
-                }
-            }
-        }
-        
-        IpRange { base, mask }
+        IpRange { base: IpAddr::V4(Ipv4Addr::from(base)), prefix: prefix.min(32) }
+    }
+    
+    pub fn new_v6(base: [u16; 8], prefix: u8) -> Self {
+        let [a, b, c, d, e, f, g, h] = base;
+        IpRange { base: IpAddr::V6(Ipv6Addr::new(a, b, c, d, e, f, g, h)), prefix: prefix.min(128) }
+    }
+    
+    // Range covering an interface address and its netmask
+    pub fn from_netmask(addr: IpAddr, netmask: IpAddr) -> Option<Self> {
+        let prefix = match (addr, netmask) {
+            (IpAddr::V4(_), IpAddr::V4(mask)) => u32::from(mask).leading_ones() as u8,
+            (IpAddr::V6(_), IpAddr::V6(mask)) => u128::from(mask).leading_ones() as u8,
+            _ => return None,
+        };
+        Some(IpRange { base: addr, prefix })
     }
     
     pub fn contains(&self, ip: &IpAddr) -> bool {
-        if let IpAddr::V4(ipv4) = ip {
-            let octets = ipv4.octets();
-            for i in 0..4 {
-                let i_usize = i as usize;
-                if (octets[i_usize] & self.mask[i_usize]) != (self.base[i_usize] & self.mask[i_usize]) {
-                    return false;
-                }
-            }
-            true
-        } else {
-            false // Only supporting IPv4 for simplicity
+        match (self.base, ip) {
+            (IpAddr::V4(base), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                (u32::from(base) & mask) == (u32::from(*ip) & mask)
+            },
+            (IpAddr::V6(base), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                (u128::from(base) & mask) == (u128::from(*ip) & mask)
+            },
+            // Address families never match each other
+            _ => false,
+        }
+    }
+    
+    // First and last address in the range
+    pub fn bounds(&self) -> (IpAddr, IpAddr) {
+        match self.base {
+            IpAddr::V4(base) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                let first = u32::from(base) & mask;
+                (IpAddr::V4(Ipv4Addr::from(first)), IpAddr::V4(Ipv4Addr::from(first | !mask)))
+            },
+            IpAddr::V6(base) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                let first = u128::from(base) & mask;
+                (IpAddr::V6(Ipv6Addr::from(first)), IpAddr::V6(Ipv6Addr::from(first | !mask)))
+            },
+        }
+    }
+}
+
+// Parse CIDR notation such as "10.0.0.0/8" or "2001:db8::/64"; a bare address
+// is treated as a single host
+impl FromStr for IpRange {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        
+        let base = addr.parse::<IpAddr>()
+            .map_err(|_| format!("invalid IP network '{}'", s))?;
+        let max_prefix = if base.is_ipv4() { 32 } else { 128 };
+        
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>()
+                .map_err(|_| format!("invalid prefix length in '{}'", s))?,
+            None => max_prefix,
+        };
+        
+        if prefix > max_prefix {
+            return Err(format!("prefix length must be at most {} in '{}'", max_prefix, s));
         }
+        
+        Ok(IpRange { base, prefix })
     }
 }
 
@@ -65,7 +111,13 @@ pub fn is_private_ip(ip: IpAddr) -> bool {
             // 169.254.0.0/16
             (octets[0] == 169 && octets[1] == 254)
         },
-        IpAddr::V6(_) => false  // Simplified for the example
+        IpAddr::V6(ipv6) => {
+            let segments = ipv6.segments();
+            // fc00::/7 (unique local)
+            (segments[0] & 0xfe00) == 0xfc00 ||
+            // fe80::/10 (link-local)
+            (segments[0] & 0xffc0) == 0xfe80
+        }
     }
 }
 
@@ -77,7 +129,8 @@ pub fn is_loopback_ip(ip: IpAddr) -> bool {
             // 127.0.0.0/8
             octets[0] == 127
         },
-        IpAddr::V6(_) => false  // Simplified for the example
+        // ::1
+        IpAddr::V6(ipv6) => ipv6.is_loopback()
     }
 }
 
@@ -126,6 +179,18 @@ pub fn format_duration(duration: Duration) -> String {
     }
 }
 
+// Round-trip times need sub-millisecond precision on a LAN
+pub fn format_rtt(duration: Duration) -> String {
+    let millis = duration.as_secs_f64() * 1000.0;
+    if millis < 10.0 {
+        format!("{:.2} ms", millis)
+    } else if millis < 1000.0 {
+        format!("{:.1} ms", millis)
+    } else {
+        format!("{:.2} s", millis / 1000.0)
+    }
+}
+
 // Helper to create centered rect
 pub fn centered_rect(percent_x: u16, percent_y: u16, r: ratatui::prelude::Rect) -> ratatui::prelude::Rect {
     use ratatui::prelude::*;
@@ -138,7 +203,7 @@ pub fn centered_rect(percent_x: u16, percent_y: u16, r: ratatui::prelude::Rect)
             Constraint::Percentage((100 - percent_y) / 2),
         ])
         .split(r);
-
+    
     Layout::default()
         .direction(Direction::Horizontal)
         .constraints([